@@ -1,7 +1,14 @@
 #![feature(exit_status_error)]
 
 
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
 use std::process::Command;
+use sha2::{ Sha256, Digest };
+use flate2::write::GzEncoder;
+use flate2::Compression;
 
 
 fn main() {
@@ -15,4 +22,30 @@ fn main() {
     println!("cargo::rerun-if-changed=lighthousemc-editor-common");
     println!("cargo::rerun-if-changed=lighthousemc-editor-frontend");
 
+    let out_dir = Path::new(&env::var("OUT_DIR").unwrap()).join("frontend");
+    fs::create_dir_all(&out_dir).unwrap();
+    hash_asset(&out_dir, "lighthousemc_editor_frontend", "js", "LIGHTHOUSEMC_EDITOR_FRONTEND_JS");
+    hash_asset(&out_dir, "lighthousemc_editor_frontend_bg", "wasm", "LIGHTHOUSEMC_EDITOR_FRONTEND_WASM");
+
+}
+
+
+/// Copies a frontend asset to a file named after its contents, along with brotli and gzip compressed copies.
+/// The file name is passed to the crate in `env_name`, and its hash in `{env_name}_HASH`.
+fn hash_asset(out_dir : &Path, name : &str, ext : &str, env_name : &str) {
+    let data = fs::read(format!("lighthousemc-editor-frontend/pkg/{}.{}", name, ext)).unwrap();
+    let hash = Sha256::digest(&data).iter().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    let file_name = format!("{}.{}.{}", name, &hash[..16], ext);
+    fs::write(out_dir.join(&file_name), &data).unwrap();
+
+    let mut brotli = brotli::CompressorWriter::new(Vec::new(), 4096, 11, 22);
+    brotli.write_all(&data).unwrap();
+    fs::write(out_dir.join(format!("{}.br", file_name)), brotli.into_inner()).unwrap();
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::best());
+    gzip.write_all(&data).unwrap();
+    fs::write(out_dir.join(format!("{}.gz", file_name)), gzip.finish().unwrap()).unwrap();
+
+    println!("cargo:rustc-env={}={}", env_name, file_name);
+    println!("cargo:rustc-env={}_HASH={}", env_name, &hash[..16]);
 }
@@ -2,8 +2,8 @@
 
 
 use lighthousemc_editor::EditorPlugin;
-use lighthousemc_editor::instances::EditorInstance;
-use lighthousemc_editor::instances::session::EditorSession;
+use lighthousemc_editor::registry::EditorRegistry;
+use lighthousemc_editor::instances::session::EditorPermission;
 use lighthousemc_database::LighthouseDB;
 use voxidian_logger::LOGS;
 use axecs::prelude::*;
@@ -28,42 +28,38 @@ async fn main() {
     app.add_plugin(CtrlCPlugin::default());
     app.add_plugin(EditorPlugin::new(
         "127.0.0.1:5123",
-        "127.0.0.1:25565".into(),
-        Arc::clone(&db)
+        "127.0.0.1:25565".into()
     ).await.unwrap());
 
-    app.add_systems(Startup, create_session_and_instance.pass(db));
+    app.add_systems(Startup, create_sessions.pass(db));
 
     app.run().await;
 }
 
 
-async fn create_session_and_instance(
+async fn create_sessions(
     In(database) : In<Arc<LighthouseDB>>,
     cmds         : Commands
 ) {
-    let plot_id = 6;
+    let plot_id  = 6;
+    let registry = EditorRegistry::new(cmds, database);
 
-    let instance = unsafe{ EditorInstance::create(plot_id, database) }.await.unwrap().unwrap();
-    cmds.spawn(instance).await;
-
-    let session = unsafe{ EditorSession::create_with(
+    // The plot's instance is opened along with the first session.
+    let session_code = registry.open_session::<32>(
         plot_id,
         Uuid::new_v4(),
         "Totobirb".into(),
         Duration::from_secs(60),
-        "A".into()
-    ) }.unwrap();
-    voxidian_logger::pass!("http://127.0.0.1:5123/editor#DO-NOT-SHARE_{}", session.session_code());
-    cmds.spawn(session).await;
+        EditorPermission::Write
+    ).await.unwrap();
+    voxidian_logger::pass!("http://127.0.0.1:5123/editor#DO-NOT-SHARE_{}", session_code);
 
-    let session = unsafe{ EditorSession::create_with(
+    let session_code = registry.open_session::<32>(
         plot_id,
         Uuid::new_v4(),
         "Other Person".into(),
         Duration::from_secs(60),
-        "B".into()
-    ) }.unwrap();
-    voxidian_logger::pass!("http://127.0.0.1:5123/editor#DO-NOT-SHARE_{}", session.session_code());
-    cmds.spawn(session).await;
+        EditorPermission::Suggest
+    ).await.unwrap();
+    voxidian_logger::pass!("http://127.0.0.1:5123/editor#DO-NOT-SHARE_{}", session_code);
 }
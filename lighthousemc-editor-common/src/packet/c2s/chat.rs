@@ -0,0 +1,26 @@
+use super::*;
+
+
+/// Sends a message to the plot's chat.
+#[derive(Debug)]
+pub struct ChatC2SPacket {
+    pub message : String
+}
+
+impl PacketMeta for ChatC2SPacket {
+    const PREFIX : u8 = 11;
+}
+
+impl PacketEncode for ChatC2SPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(&self.message);
+    }
+}
+
+impl PacketDecode for ChatC2SPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            message : buf.read_decode()?
+        })
+    }
+}
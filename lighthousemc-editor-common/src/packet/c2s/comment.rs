@@ -0,0 +1,80 @@
+use super::*;
+
+
+/// Starts, replies to, or resolves a comment thread.
+#[derive(Debug)]
+pub struct CommentC2SPacket {
+    pub action : CommentAction
+}
+
+impl PacketMeta for CommentC2SPacket {
+    const PREFIX : u8 = 12;
+}
+
+impl PacketEncode for CommentC2SPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        match (&self.action) {
+            CommentAction::Create { file_id, start, end, text } => {
+                buf.encode_write(0u8);
+                buf.encode_write(file_id);
+                buf.encode_write(*start as u32);
+                buf.encode_write(*end   as u32);
+                buf.encode_write(text);
+            },
+            CommentAction::Reply { thread_id, text } => {
+                buf.encode_write(1u8);
+                buf.encode_write(thread_id);
+                buf.encode_write(text);
+            },
+            CommentAction::SetResolved { thread_id, resolved } => {
+                buf.encode_write(2u8);
+                buf.encode_write(thread_id);
+                buf.encode_write(resolved);
+            }
+        }
+    }
+}
+
+impl PacketDecode for CommentC2SPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            action : match (buf.read_decode::<u8>()?) {
+                0 => CommentAction::Create {
+                    file_id : buf.read_decode()?,
+                    start   : buf.read_decode::<u32>()? as usize,
+                    end     : buf.read_decode::<u32>()? as usize,
+                    text    : buf.read_decode()?
+                },
+                1 => CommentAction::Reply {
+                    thread_id : buf.read_decode()?,
+                    text      : buf.read_decode()?
+                },
+                2 => CommentAction::SetResolved {
+                    thread_id : buf.read_decode()?,
+                    resolved  : buf.read_decode()?
+                },
+                _ => { return Err(DecodeError::InvalidData(Cow::Borrowed("Unknown comment action"))); }
+            }
+        })
+    }
+}
+
+
+#[derive(Debug)]
+pub enum CommentAction {
+    /// Starts a thread on a range of a text file. Offsets are in UTF-16 code units.
+    Create {
+        file_id : u64,
+        start   : usize,
+        end     : usize,
+        text    : String
+    },
+    Reply {
+        thread_id : u64,
+        text      : String
+    },
+    SetResolved {
+        thread_id : u64,
+        resolved  : bool
+    }
+}
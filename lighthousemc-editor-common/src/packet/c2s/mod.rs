@@ -10,6 +10,24 @@ mod patch_file;
 pub use patch_file::*;
 mod selections;
 pub use selections::*;
+mod search;
+pub use search::*;
+mod request_action;
+pub use request_action::*;
+mod query_audit_log;
+pub use query_audit_log::*;
+mod set_blame_visible;
+pub use set_blame_visible::*;
+mod viewport;
+pub use viewport::*;
+mod chat;
+pub use chat::*;
+mod comment;
+pub use comment::*;
+mod set_suggesting;
+pub use set_suggesting::*;
+mod review_suggestion;
+pub use review_suggestion::*;
 
 
 use super::*;
@@ -21,5 +39,14 @@ packet_group!{ pub enum C2SPackets {
     OpenFile(OpenFileC2SPacket),
     CloseFile(CloseFileC2SPacket),
     PatchFile(PatchFileC2SPacket),
-    Selections(SelectionsC2SPacket)
+    Selections(SelectionsC2SPacket),
+    Search(SearchC2SPacket),
+    RequestAction(RequestActionC2SPacket),
+    QueryAuditLog(QueryAuditLogC2SPacket),
+    SetBlameVisible(SetBlameVisibleC2SPacket),
+    Viewport(ViewportC2SPacket),
+    Chat(ChatC2SPacket),
+    Comment(CommentC2SPacket),
+    SetSuggesting(SetSuggestingC2SPacket),
+    ReviewSuggestion(ReviewSuggestionC2SPacket)
 } }
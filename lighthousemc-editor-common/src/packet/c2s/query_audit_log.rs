@@ -0,0 +1,30 @@
+use super::*;
+
+
+/// Asks for the plot's audit log, newest first.
+#[derive(Debug)]
+pub struct QueryAuditLogC2SPacket {
+    /// Only entries older than this unix timestamp in milliseconds. `None` for the most recent entries.
+    pub before : Option<u64>,
+    pub limit  : u32
+}
+
+impl PacketMeta for QueryAuditLogC2SPacket {
+    const PREFIX : u8 = 8;
+}
+
+impl PacketEncode for QueryAuditLogC2SPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.before);
+        buf.encode_write(self.limit);
+    }
+}
+
+impl PacketDecode for QueryAuditLogC2SPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            before : buf.read_decode()?,
+            limit  : buf.read_decode()?
+        })
+    }
+}
@@ -0,0 +1,38 @@
+use super::*;
+
+
+/// Asks the host server to do something with the plot.
+#[derive(Debug)]
+pub struct RequestActionC2SPacket {
+    pub action : EditorAction
+}
+
+impl PacketMeta for RequestActionC2SPacket {
+    const PREFIX : u8 = 7;
+}
+
+impl PacketEncode for RequestActionC2SPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.action as u8);
+    }
+}
+
+impl PacketDecode for RequestActionC2SPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            action : match (buf.read_decode::<u8>()?) {
+                0 => EditorAction::Save,
+                1 => EditorAction::Build,
+                _ => { return Err(DecodeError::InvalidData(Cow::Borrowed("Unknown editor action"))); }
+            }
+        })
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum EditorAction {
+    Save  = 0,
+    Build = 1
+}
@@ -0,0 +1,30 @@
+use super::*;
+
+
+/// Accepts or rejects a suggested change.
+/// Accepting requires write access. A suggestion can be rejected by its author, withdrawing it.
+#[derive(Debug)]
+pub struct ReviewSuggestionC2SPacket {
+    pub suggestion_id : u64,
+    pub accept        : bool
+}
+
+impl PacketMeta for ReviewSuggestionC2SPacket {
+    const PREFIX : u8 = 14;
+}
+
+impl PacketEncode for ReviewSuggestionC2SPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.suggestion_id);
+        buf.encode_write(self.accept);
+    }
+}
+
+impl PacketDecode for ReviewSuggestionC2SPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            suggestion_id : buf.read_decode()?,
+            accept        : buf.read_decode()?
+        })
+    }
+}
@@ -0,0 +1,54 @@
+use super::*;
+
+
+#[derive(Debug)]
+pub struct SearchC2SPacket {
+    /// Chosen by the client. Results are tagged with this id so that stale results can be ignored.
+    pub search_id : u32,
+    pub query     : String,
+    pub options   : SearchOptions,
+    /// Comma separated path globs. Globs starting with `!` exclude files. Empty includes every file.
+    pub globs     : String,
+    /// If set, every match is replaced with this text.
+    pub replace   : Option<String>
+}
+
+impl PacketMeta for SearchC2SPacket {
+    const PREFIX : u8 = 6;
+}
+
+impl PacketEncode for SearchC2SPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.search_id);
+        buf.encode_write(&self.query);
+        buf.encode_write(self.options.regex);
+        buf.encode_write(self.options.case_sensitive);
+        buf.encode_write(self.options.whole_word);
+        buf.encode_write(&self.globs);
+        buf.encode_write(&self.replace);
+    }
+}
+
+impl PacketDecode for SearchC2SPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            search_id : buf.read_decode()?,
+            query     : buf.read_decode()?,
+            options   : SearchOptions {
+                regex          : buf.read_decode()?,
+                case_sensitive : buf.read_decode()?,
+                whole_word     : buf.read_decode()?
+            },
+            globs     : buf.read_decode()?,
+            replace   : buf.read_decode()?
+        })
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SearchOptions {
+    pub regex          : bool,
+    pub case_sensitive : bool,
+    pub whole_word     : bool
+}
@@ -0,0 +1,26 @@
+use super::*;
+
+
+/// Starts or stops the server sending the blame of open files.
+#[derive(Debug)]
+pub struct SetBlameVisibleC2SPacket {
+    pub visible : bool
+}
+
+impl PacketMeta for SetBlameVisibleC2SPacket {
+    const PREFIX : u8 = 9;
+}
+
+impl PacketEncode for SetBlameVisibleC2SPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.visible);
+    }
+}
+
+impl PacketDecode for SetBlameVisibleC2SPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            visible : buf.read_decode()?
+        })
+    }
+}
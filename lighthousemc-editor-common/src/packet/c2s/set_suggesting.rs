@@ -0,0 +1,27 @@
+use super::*;
+
+
+/// Starts or stops sending edits as suggestions instead of changing files directly.
+/// Clients without write access always suggest.
+#[derive(Debug)]
+pub struct SetSuggestingC2SPacket {
+    pub suggesting : bool
+}
+
+impl PacketMeta for SetSuggestingC2SPacket {
+    const PREFIX : u8 = 13;
+}
+
+impl PacketEncode for SetSuggestingC2SPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.suggesting);
+    }
+}
+
+impl PacketDecode for SetSuggestingC2SPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            suggesting : buf.read_decode()?
+        })
+    }
+}
@@ -0,0 +1,54 @@
+use super::*;
+
+
+/// The file that the client is looking at, and which of its lines are visible.
+#[derive(Debug)]
+pub struct ViewportC2SPacket {
+    pub viewport : Option<Viewport>
+}
+
+impl PacketMeta for ViewportC2SPacket {
+    const PREFIX : u8 = 10;
+}
+
+impl PacketEncode for ViewportC2SPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(&self.viewport);
+    }
+}
+
+impl PacketDecode for ViewportC2SPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            viewport : buf.read_decode()?
+        })
+    }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub file_id    : u64,
+    /// Zero based index of the first visible line. `0` for binary files.
+    pub first_line : u32,
+    /// Zero based index of the last visible line. `0` for binary files.
+    pub last_line  : u32
+}
+
+impl PacketEncode for Viewport {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.file_id);
+        buf.encode_write(self.first_line);
+        buf.encode_write(self.last_line);
+    }
+}
+
+impl PacketDecode for Viewport {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            file_id    : buf.read_decode()?,
+            first_line : buf.read_decode()?,
+            last_line  : buf.read_decode()?
+        })
+    }
+}
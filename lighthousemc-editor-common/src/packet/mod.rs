@@ -34,6 +34,15 @@ macro packet_group(
             Err(DecodeError::UnknownPacketPrefix(prefix))
         }
     }
+    impl $( < $( $lt , )* > )? $ident $( < $( $lt , )* > )? {
+        /// The name of the packet in this group with a prefix.
+        pub fn name_of_prefix(prefix : u8) -> Option<&'static str> {
+            $( if (prefix == <$variantinner as PacketMeta>::PREFIX) {
+                return Some(stringify!($variantname));
+            } )*
+            None
+        }
+    }
 }
 
 
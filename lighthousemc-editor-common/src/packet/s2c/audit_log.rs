@@ -0,0 +1,62 @@
+use super::*;
+
+
+/// A page of the plot's audit log, newest first.
+#[derive(Debug)]
+pub struct AuditLogS2CPacket<'l> {
+    /// Whether these entries follow on from the previously sent ones, rather than replacing them.
+    pub is_continuation : bool,
+    pub entries         : Cow<'l, [AuditLogEntry<'l>]>
+}
+
+impl<'l> PacketMeta for AuditLogS2CPacket<'l> {
+    const PREFIX : u8 = 10;
+}
+
+impl<'l> PacketEncode for AuditLogS2CPacket<'l> {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.is_continuation);
+        buf.encode_write(self.entries.len() as u32);
+        for entry in &*self.entries {
+            buf.encode_write(entry.timestamp);
+            buf.encode_write(&entry.client_name);
+            buf.encode_write(entry.colour);
+            buf.encode_write(entry.file_id);
+            buf.encode_write(&entry.description);
+        }
+    }
+}
+
+impl<'l> PacketDecode for AuditLogS2CPacket<'l> {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            is_continuation : buf.read_decode()?,
+            entries         : {
+                let     count   = buf.read_decode::<u32>()? as usize;
+                let mut entries = Vec::with_capacity(count);
+                for _ in 0..count {
+                    entries.push(AuditLogEntry {
+                        timestamp   : buf.read_decode()?,
+                        client_name : buf.read_decode()?,
+                        colour      : buf.read_decode()?,
+                        file_id     : buf.read_decode()?,
+                        description : buf.read_decode()?
+                    });
+                }
+                Cow::Owned(entries)
+            }
+        })
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct AuditLogEntry<'l> {
+    /// Unix timestamp in milliseconds.
+    pub timestamp   : u64,
+    pub client_name : Cow<'l, str>,
+    pub colour      : u8,
+    /// The file this entry is about, if any.
+    pub file_id     : Option<u64>,
+    pub description : Cow<'l, str>
+}
@@ -0,0 +1,65 @@
+use super::*;
+
+
+/// Who last changed each line of a text file.
+#[derive(Debug, Clone)]
+pub struct BlameS2CPacket<'l> {
+    pub file_id : u64,
+    /// Runs of consecutive lines with the same author, from the top of the file.
+    pub ranges  : Cow<'l, [BlameRange<'l>]>
+}
+
+impl<'l> PacketMeta for BlameS2CPacket<'l> {
+    const PREFIX : u8 = 11;
+}
+
+impl<'l> PacketEncode for BlameS2CPacket<'l> {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.file_id);
+        buf.encode_write(self.ranges.len() as u32);
+        for range in &*self.ranges {
+            buf.encode_write(range.lines);
+            buf.encode_write(range.author.is_some());
+            if let Some(author) = &range.author {
+                buf.encode_write(&author.client_name);
+                buf.encode_write(author.colour);
+            }
+        }
+    }
+}
+
+impl<'l> PacketDecode for BlameS2CPacket<'l> {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            file_id : buf.read_decode()?,
+            ranges  : {
+                let     count  = buf.read_decode::<u32>()? as usize;
+                let mut ranges = Vec::with_capacity(count);
+                for _ in 0..count {
+                    ranges.push(BlameRange {
+                        lines  : buf.read_decode()?,
+                        author : if (buf.read_decode::<bool>()?) { Some(BlameAuthor {
+                            client_name : buf.read_decode()?,
+                            colour      : buf.read_decode()?
+                        }) } else { None }
+                    });
+                }
+                Cow::Owned(ranges)
+            }
+        })
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct BlameRange<'l> {
+    pub lines  : u32,
+    /// `None` if the lines have not been changed since the file was loaded.
+    pub author : Option<BlameAuthor<'l>>
+}
+
+#[derive(Debug, Clone)]
+pub struct BlameAuthor<'l> {
+    pub client_name : Cow<'l, str>,
+    pub colour      : u8
+}
@@ -0,0 +1,80 @@
+use super::*;
+
+
+/// Messages in the plot's chat, oldest first.
+#[derive(Debug, Clone)]
+pub struct ChatS2CPacket<'l> {
+    /// Whether these messages are the chat history, sent on login, rather than new messages.
+    pub is_history : bool,
+    pub messages   : Cow<'l, [ChatMessage<'l>]>
+}
+
+impl<'l> PacketMeta for ChatS2CPacket<'l> {
+    const PREFIX : u8 = 15;
+}
+
+impl<'l> PacketEncode for ChatS2CPacket<'l> {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.is_history);
+        buf.encode_write(self.messages.len() as u32);
+        for message in &*self.messages {
+            buf.encode_write(message.timestamp);
+            buf.encode_write(message.source as u8);
+            buf.encode_write(&message.sender_name);
+            buf.encode_write(message.colour);
+            buf.encode_write(&message.message);
+        }
+    }
+}
+
+impl<'l> PacketDecode for ChatS2CPacket<'l> {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            is_history : buf.read_decode()?,
+            messages   : {
+                let     count    = buf.read_decode::<u32>()? as usize;
+                let mut messages = Vec::with_capacity(count);
+                for _ in 0..count {
+                    messages.push(ChatMessage {
+                        timestamp   : buf.read_decode()?,
+                        source      : match (buf.read_decode::<u8>()?) {
+                            0 => ChatSource::Editor,
+                            1 => ChatSource::Game,
+                            2 => ChatSource::Server,
+                            _ => { return Err(DecodeError::InvalidData(Cow::Borrowed("Unknown chat source"))); }
+                        },
+                        sender_name : buf.read_decode()?,
+                        colour      : buf.read_decode()?,
+                        message     : buf.read_decode()?
+                    });
+                }
+                Cow::Owned(messages)
+            }
+        })
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ChatMessage<'l> {
+    /// Unix timestamp in milliseconds.
+    pub timestamp   : u64,
+    pub source      : ChatSource,
+    /// Empty for messages from the server.
+    pub sender_name : Cow<'l, str>,
+    pub colour      : u8,
+    pub message     : Cow<'l, str>
+}
+
+
+/// Where a chat message was sent from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ChatSource {
+    /// A collaborator in the editor.
+    Editor = 0,
+    /// A player in game.
+    Game   = 1,
+    /// The host server itself, eg. an announcement.
+    Server = 2
+}
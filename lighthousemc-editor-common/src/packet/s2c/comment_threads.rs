@@ -0,0 +1,92 @@
+use super::*;
+
+
+/// Adds or replaces comment threads.
+#[derive(Debug, Clone)]
+pub struct CommentThreadsS2CPacket<'l> {
+    /// Whether these are every thread on the plot, sent on login, rather than threads that changed.
+    pub is_initial : bool,
+    pub threads    : Cow<'l, [CommentThread<'l>]>
+}
+
+impl<'l> PacketMeta for CommentThreadsS2CPacket<'l> {
+    const PREFIX : u8 = 16;
+}
+
+impl<'l> PacketEncode for CommentThreadsS2CPacket<'l> {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.is_initial);
+        buf.encode_write(self.threads.len() as u32);
+        for thread in &*self.threads {
+            buf.encode_write(thread.thread_id);
+            buf.encode_write(thread.file_id);
+            buf.encode_write(thread.start as u32);
+            buf.encode_write(thread.end   as u32);
+            buf.encode_write(thread.resolved);
+            buf.encode_write(thread.comments.len() as u32);
+            for comment in &*thread.comments {
+                buf.encode_write(comment.timestamp);
+                buf.encode_write(&comment.client_name);
+                buf.encode_write(comment.colour);
+                buf.encode_write(&comment.text);
+            }
+        }
+    }
+}
+
+impl<'l> PacketDecode for CommentThreadsS2CPacket<'l> {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            is_initial : buf.read_decode()?,
+            threads    : {
+                let     count   = buf.read_decode::<u32>()? as usize;
+                let mut threads = Vec::with_capacity(count);
+                for _ in 0..count {
+                    threads.push(CommentThread {
+                        thread_id : buf.read_decode()?,
+                        file_id   : buf.read_decode()?,
+                        start     : buf.read_decode::<u32>()? as usize,
+                        end       : buf.read_decode::<u32>()? as usize,
+                        resolved  : buf.read_decode()?,
+                        comments  : {
+                            let     count    = buf.read_decode::<u32>()? as usize;
+                            let mut comments = Vec::with_capacity(count);
+                            for _ in 0..count {
+                                comments.push(Comment {
+                                    timestamp   : buf.read_decode()?,
+                                    client_name : buf.read_decode()?,
+                                    colour      : buf.read_decode()?,
+                                    text        : buf.read_decode()?
+                                });
+                            }
+                            Cow::Owned(comments)
+                        }
+                    });
+                }
+                Cow::Owned(threads)
+            }
+        })
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct CommentThread<'l> {
+    pub thread_id : u64,
+    pub file_id   : u64,
+    /// Offsets of the commented range, in UTF-16 code units.
+    pub start     : usize,
+    pub end       : usize,
+    pub resolved  : bool,
+    /// Oldest first. The first comment starts the thread.
+    pub comments  : Cow<'l, [Comment<'l>]>
+}
+
+#[derive(Debug, Clone)]
+pub struct Comment<'l> {
+    /// Unix timestamp in milliseconds.
+    pub timestamp   : u64,
+    pub client_name : Cow<'l, str>,
+    pub colour      : u8,
+    pub text        : Cow<'l, str>
+}
@@ -3,7 +3,9 @@ use super::*;
 
 #[derive(Debug)]
 pub struct DisconnectS2CPacket<'l> {
-    pub reason : Cow<'l, str>
+    pub reason         : Cow<'l, str>,
+    /// Set when the server is restarting, after which the client should reconnect.
+    pub retry_after_ms : Option<u32>
 }
 
 impl<'l> PacketMeta for DisconnectS2CPacket<'l> {
@@ -13,11 +15,15 @@ impl<'l> PacketMeta for DisconnectS2CPacket<'l> {
 impl<'l> PacketEncode for DisconnectS2CPacket<'l> {
     fn encode(&self, buf : &mut PacketBuf) -> () {
         buf.encode_write(&self.reason);
+        buf.encode_write(self.retry_after_ms);
     }
 }
 
 impl<'l> PacketDecode for DisconnectS2CPacket<'l> {
     fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
-        Ok(Self { reason : buf.read_decode()? })
+        Ok(Self {
+            reason         : buf.read_decode()?,
+            retry_after_ms : buf.read_decode()?
+        })
     }
 }
@@ -14,6 +14,28 @@ mod selections;
 pub use selections::*;
 mod close_file;
 pub use close_file::*;
+mod search_results;
+pub use search_results::*;
+mod search_done;
+pub use search_done::*;
+mod audit_log;
+pub use audit_log::*;
+mod blame;
+pub use blame::*;
+mod presence;
+pub use presence::*;
+mod presence_leave;
+pub use presence_leave::*;
+mod viewport;
+pub use viewport::*;
+mod chat;
+pub use chat::*;
+mod comment_threads;
+pub use comment_threads::*;
+mod suggestions;
+pub use suggestions::*;
+mod suggestion_mode;
+pub use suggestion_mode::*;
 
 
 use super::*;
@@ -28,5 +50,16 @@ packet_group!{ pub enum S2CPackets<'l> {
     OvewriteFile(OverwriteFileS2CPacket<'l>),
     PatchFile(PatchFileS2CPacket),
     Selections(SelectionsS2CPacket<'l>),
-    CloseFile(CloseFileS2CPacket)
+    CloseFile(CloseFileS2CPacket),
+    SearchResults(SearchResultsS2CPacket<'l>),
+    SearchDone(SearchDoneS2CPacket<'l>),
+    AuditLog(AuditLogS2CPacket<'l>),
+    Blame(BlameS2CPacket<'l>),
+    Presence(PresenceS2CPacket<'l>),
+    PresenceLeave(PresenceLeaveS2CPacket),
+    Viewport(ViewportS2CPacket),
+    Chat(ChatS2CPacket<'l>),
+    CommentThreads(CommentThreadsS2CPacket<'l>),
+    Suggestions(SuggestionsS2CPacket<'l>),
+    SuggestionMode(SuggestionModeS2CPacket)
 } }
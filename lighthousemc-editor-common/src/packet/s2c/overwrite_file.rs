@@ -15,7 +15,11 @@ impl<'l> PacketEncode for OverwriteFileS2CPacket<'l> {
     fn encode(&self, buf : &mut PacketBuf) -> () {
         buf.encode_write(self.file_id);
         match (&self.contents) {
-            FileContents::NonText    => buf.encode_write(false),
+            FileContents::Binary { size, mime } => {
+                buf.encode_write(false);
+                buf.encode_write(size);
+                buf.encode_write(mime);
+            },
             FileContents::Text(text) => {
                 buf.encode_write(true);
                 buf.encode_write(text);
@@ -31,7 +35,10 @@ impl<'l> PacketDecode for OverwriteFileS2CPacket<'l> {
             contents : {
                 let is_text = buf.read_decode::<bool>()?;
                 if (is_text) { FileContents::Text(buf.read_decode()?) }
-                else { FileContents::NonText }
+                else { FileContents::Binary {
+                    size : buf.read_decode()?,
+                    mime : buf.read_decode()?
+                } }
             }
         })
     }
@@ -40,15 +47,19 @@ impl<'l> PacketDecode for OverwriteFileS2CPacket<'l> {
 
 #[derive(Debug, Clone)]
 pub enum FileContents<'l> {
-    NonText,
+    /// The file is not valid UTF8. The data itself is fetched over HTTP.
+    Binary {
+        size : u64,
+        mime : Cow<'l, str>
+    },
     Text(Cow<'l, str>)
 }
 
 impl<'l> FileContents<'l> {
     pub fn as_ref(&'l self) -> FileContents<'l> {
         match (self) {
-            Self::NonText    => Self::NonText,
-            Self::Text(text) => Self::Text(Cow::Borrowed(text))
+            Self::Binary { size, mime } => Self::Binary { size : *size, mime : Cow::Borrowed(mime) },
+            Self::Text(text)            => Self::Text(Cow::Borrowed(text))
         }
     }
 }
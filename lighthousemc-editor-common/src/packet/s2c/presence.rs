@@ -0,0 +1,44 @@
+use super::*;
+use uuid::Uuid;
+
+
+/// Adds or updates a collaborator in the presence list.
+#[derive(Debug, Clone)]
+pub struct PresenceS2CPacket<'l> {
+    pub client_uuid  : Uuid,
+    pub client_name  : Cow<'l, str>,
+    pub colour       : u8,
+    /// Unix timestamp in milliseconds of when the collaborator connected.
+    pub connected_at : u64,
+    /// The file that the collaborator is looking at, if any.
+    pub file_id      : Option<u64>,
+    pub idle         : bool
+}
+
+impl<'l> PacketMeta for PresenceS2CPacket<'l> {
+    const PREFIX : u8 = 12;
+}
+
+impl<'l> PacketEncode for PresenceS2CPacket<'l> {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.client_uuid);
+        buf.encode_write(&self.client_name);
+        buf.encode_write(self.colour);
+        buf.encode_write(self.connected_at);
+        buf.encode_write(self.file_id);
+        buf.encode_write(self.idle);
+    }
+}
+
+impl<'l> PacketDecode for PresenceS2CPacket<'l> {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            client_uuid  : buf.read_decode()?,
+            client_name  : buf.read_decode()?,
+            colour       : buf.read_decode()?,
+            connected_at : buf.read_decode()?,
+            file_id      : buf.read_decode()?,
+            idle         : buf.read_decode()?
+        })
+    }
+}
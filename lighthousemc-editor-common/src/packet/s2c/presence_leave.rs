@@ -0,0 +1,27 @@
+use super::*;
+use uuid::Uuid;
+
+
+/// Removes a collaborator from the presence list.
+#[derive(Debug, Clone)]
+pub struct PresenceLeaveS2CPacket {
+    pub client_uuid : Uuid
+}
+
+impl PacketMeta for PresenceLeaveS2CPacket {
+    const PREFIX : u8 = 13;
+}
+
+impl PacketEncode for PresenceLeaveS2CPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.client_uuid);
+    }
+}
+
+impl PacketDecode for PresenceLeaveS2CPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            client_uuid : buf.read_decode()?
+        })
+    }
+}
@@ -0,0 +1,36 @@
+use super::*;
+
+
+#[derive(Debug)]
+pub struct SearchDoneS2CPacket<'l> {
+    pub search_id     : u32,
+    pub total_matches : u32,
+    /// Whether the matches were replaced.
+    pub replaced      : bool,
+    /// Set if the search could not be run, eg. because of an invalid regex.
+    pub error         : Option<Cow<'l, str>>
+}
+
+impl<'l> PacketMeta for SearchDoneS2CPacket<'l> {
+    const PREFIX : u8 = 9;
+}
+
+impl<'l> PacketEncode for SearchDoneS2CPacket<'l> {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.search_id);
+        buf.encode_write(self.total_matches);
+        buf.encode_write(self.replaced);
+        buf.encode_write(&self.error);
+    }
+}
+
+impl<'l> PacketDecode for SearchDoneS2CPacket<'l> {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            search_id     : buf.read_decode()?,
+            total_matches : buf.read_decode()?,
+            replaced      : buf.read_decode()?,
+            error         : buf.read_decode()?
+        })
+    }
+}
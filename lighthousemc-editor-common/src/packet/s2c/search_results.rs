@@ -0,0 +1,75 @@
+use super::*;
+
+
+/// The matches in a single file. Sent once for each file with at least one match.
+#[derive(Debug)]
+pub struct SearchResultsS2CPacket<'l> {
+    pub search_id : u32,
+    pub file_id   : u64,
+    pub matches   : Cow<'l, [SearchMatch<'l>]>
+}
+
+impl<'l> PacketMeta for SearchResultsS2CPacket<'l> {
+    const PREFIX : u8 = 8;
+}
+
+impl<'l> PacketEncode for SearchResultsS2CPacket<'l> {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.search_id);
+        buf.encode_write(self.file_id);
+        buf.encode_write(self.matches.len() as u32);
+        for search_match in &*self.matches {
+            buf.encode_write(search_match.line);
+            buf.encode_write(search_match.column_start);
+            buf.encode_write(search_match.line_end);
+            buf.encode_write(search_match.column_end);
+            buf.encode_write(&search_match.preview);
+            buf.encode_write(search_match.preview_start);
+            buf.encode_write(search_match.preview_end);
+        }
+    }
+}
+
+impl<'l> PacketDecode for SearchResultsS2CPacket<'l> {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            search_id : buf.read_decode()?,
+            file_id   : buf.read_decode()?,
+            matches   : {
+                let     count   = buf.read_decode::<u32>()? as usize;
+                let mut matches = Vec::with_capacity(count);
+                for _ in 0..count {
+                    matches.push(SearchMatch {
+                        line          : buf.read_decode()?,
+                        column_start  : buf.read_decode()?,
+                        line_end      : buf.read_decode()?,
+                        column_end    : buf.read_decode()?,
+                        preview       : buf.read_decode()?,
+                        preview_start : buf.read_decode()?,
+                        preview_end   : buf.read_decode()?
+                    });
+                }
+                Cow::Owned(matches)
+            }
+        })
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct SearchMatch<'l> {
+    /// Zero based line index.
+    pub line          : u32,
+    /// Zero based UTF-16 column of the start of the match, as used by the code editor.
+    pub column_start  : u32,
+    /// Zero based line index of the end of the match. Differs from `line` for multi-line matches.
+    pub line_end      : u32,
+    /// Zero based UTF-16 column of the end of the match on `line_end`.
+    pub column_end    : u32,
+    /// The line containing the start of the match, possibly shortened.
+    pub preview       : Cow<'l, str>,
+    /// Byte offset of the start of the match in `preview`.
+    pub preview_start : u32,
+    /// Byte offset of the end of the match in `preview`.
+    pub preview_end   : u32
+}
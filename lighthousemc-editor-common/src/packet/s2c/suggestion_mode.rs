@@ -0,0 +1,30 @@
+use super::*;
+
+
+/// Whether the client's edits are currently sent as suggestions.
+#[derive(Debug, Clone)]
+pub struct SuggestionModeS2CPacket {
+    pub suggesting : bool,
+    /// Whether the client may edit files directly and accept suggestions.
+    pub can_write  : bool
+}
+
+impl PacketMeta for SuggestionModeS2CPacket {
+    const PREFIX : u8 = 18;
+}
+
+impl PacketEncode for SuggestionModeS2CPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.suggesting);
+        buf.encode_write(self.can_write);
+    }
+}
+
+impl PacketDecode for SuggestionModeS2CPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            suggesting : buf.read_decode()?,
+            can_write  : buf.read_decode()?
+        })
+    }
+}
@@ -0,0 +1,75 @@
+use super::*;
+
+
+/// Replaces every pending suggestion on a file.
+#[derive(Debug, Clone)]
+pub struct SuggestionsS2CPacket<'l> {
+    pub file_id     : u64,
+    pub suggestions : Cow<'l, [Suggestion<'l>]>
+}
+
+impl<'l> PacketMeta for SuggestionsS2CPacket<'l> {
+    const PREFIX : u8 = 17;
+}
+
+impl<'l> PacketEncode for SuggestionsS2CPacket<'l> {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.file_id);
+        buf.encode_write(self.suggestions.len() as u32);
+        for suggestion in &*self.suggestions {
+            buf.encode_write(suggestion.suggestion_id);
+            buf.encode_write(&suggestion.client_name);
+            buf.encode_write(suggestion.colour);
+            buf.encode_write(suggestion.applied);
+            buf.encode_write(suggestion.stale);
+            buf.encode_write(suggestion.start as u32);
+            buf.encode_write(suggestion.end   as u32);
+            buf.encode_write(&suggestion.original);
+            buf.encode_write(&suggestion.replacement);
+        }
+    }
+}
+
+impl<'l> PacketDecode for SuggestionsS2CPacket<'l> {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            file_id     : buf.read_decode()?,
+            suggestions : {
+                let     count       = buf.read_decode::<u32>()? as usize;
+                let mut suggestions = Vec::with_capacity(count);
+                for _ in 0..count {
+                    suggestions.push(Suggestion {
+                        suggestion_id : buf.read_decode()?,
+                        client_name   : buf.read_decode()?,
+                        colour        : buf.read_decode()?,
+                        applied       : buf.read_decode()?,
+                        stale         : buf.read_decode()?,
+                        start         : buf.read_decode::<u32>()? as usize,
+                        end           : buf.read_decode::<u32>()? as usize,
+                        original      : buf.read_decode()?,
+                        replacement   : buf.read_decode()?
+                    });
+                }
+                Cow::Owned(suggestions)
+            }
+        })
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Suggestion<'l> {
+    pub suggestion_id : u64,
+    pub client_name   : Cow<'l, str>,
+    pub colour        : u8,
+    /// Whether the suggestion is already applied to the text that the client sees, because the client made it.
+    pub applied       : bool,
+    /// Whether the text that the suggestion replaces was edited since, so that it can no longer be accepted.
+    pub stale         : bool,
+    /// The range in the text that the client sees, in UTF-16 code units.
+    /// This is the text that would be replaced, or the replacement itself if `applied`.
+    pub start         : usize,
+    pub end           : usize,
+    pub original      : Cow<'l, str>,
+    pub replacement   : Cow<'l, str>
+}
@@ -0,0 +1,31 @@
+use super::*;
+use super::c2s::Viewport;
+use uuid::Uuid;
+
+
+/// The file that another client is looking at, and which of its lines are visible.
+#[derive(Debug, Clone)]
+pub struct ViewportS2CPacket {
+    pub client_uuid : Uuid,
+    pub viewport    : Option<Viewport>
+}
+
+impl PacketMeta for ViewportS2CPacket {
+    const PREFIX : u8 = 14;
+}
+
+impl PacketEncode for ViewportS2CPacket {
+    fn encode(&self, buf : &mut PacketBuf) -> () {
+        buf.encode_write(self.client_uuid);
+        buf.encode_write(&self.viewport);
+    }
+}
+
+impl PacketDecode for ViewportS2CPacket {
+    fn decode(buf : &mut PacketBuf) -> Result<Self, DecodeError> {
+        Ok(Self {
+            client_uuid : buf.read_decode()?,
+            viewport    : buf.read_decode()?
+        })
+    }
+}
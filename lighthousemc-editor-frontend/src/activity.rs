@@ -0,0 +1,90 @@
+use lighthousemc_editor_common::packet::s2c::AuditLogS2CPacket;
+use lighthousemc_editor_common::packet::c2s::QueryAuditLogC2SPacket;
+use std::sync::atomic::{ AtomicU64, Ordering };
+use wasm_bindgen::prelude::*;
+use js_sys::Date;
+
+
+/// How many entries are requested at a time.
+const PAGE_SIZE : u32 = 100;
+
+/// Timestamp of the oldest entry shown, or `0` if nothing is shown.
+static OLDEST : AtomicU64 = AtomicU64::new(0);
+
+
+pub fn init() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let refresh_button = document.get_element_by_id("editor_activity_refresh").unwrap();
+    let click_callback = Closure::<dyn FnMut() -> ()>::new(move || { refresh(); });
+    refresh_button.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+    click_callback.forget();
+
+    let more_button = document.get_element_by_id("editor_activity_more").unwrap();
+    let click_callback = Closure::<dyn FnMut() -> ()>::new(move || {
+        let oldest = OLDEST.load(Ordering::SeqCst);
+        if (oldest != 0) {
+            crate::ws::WS.send(QueryAuditLogC2SPacket { before : Some(oldest), limit : PAGE_SIZE });
+        }
+    });
+    more_button.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+    click_callback.forget();
+}
+
+
+/// Requests the most recent entries, replacing the ones shown.
+pub fn refresh() {
+    crate::ws::WS.send(QueryAuditLogC2SPacket { before : None, limit : PAGE_SIZE });
+}
+
+
+pub fn on_log(log : AuditLogS2CPacket<'static>) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    let entries_element = document.get_element_by_id("editor_activity_entries").unwrap();
+
+    if (! log.is_continuation) {
+        entries_element.set_inner_html("");
+        OLDEST.store(0, Ordering::SeqCst);
+    }
+
+    for entry in &*log.entries {
+        let row = document.create_element("div").unwrap();
+        row.class_list().toggle_with_force("editor_activity_entry", true).unwrap();
+
+        let time = document.create_element("div").unwrap();
+        time.class_list().toggle_with_force("editor_activity_time", true).unwrap();
+        let date = Date::new(&JsValue::from_f64(entry.timestamp as f64));
+        time.set_text_content(Some(&String::from(date.to_locale_string("default", &JsValue::UNDEFINED))));
+        row.append_child(&time).unwrap();
+
+        let text = document.create_element("div").unwrap();
+        let name = document.create_element("span").unwrap();
+        name.class_list().toggle_with_force("editor_activity_name", true).unwrap();
+        name.set_attribute("style", &format!("color: hsl({}, 100%, 62.5%);", (entry.colour as u32) * 2)).unwrap();
+        name.set_text_content(Some(&entry.client_name));
+        text.append_child(&name).unwrap();
+        let description = document.create_element("span").unwrap();
+        description.set_text_content(Some(&format!(" {}", entry.description)));
+        text.append_child(&description).unwrap();
+        row.append_child(&text).unwrap();
+
+        if let Some(file_id) = entry.file_id {
+            row.class_list().toggle_with_force("editor_activity_entry_file", true).unwrap();
+            let click_callback = Closure::<dyn FnMut() -> ()>::new(move || {
+                if let Some(path) = crate::state::file_path(file_id) {
+                    crate::state::open_file(file_id, path, true);
+                }
+            });
+            row.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+            click_callback.forget();
+        }
+
+        entries_element.append_child(&row).unwrap();
+        OLDEST.store(entry.timestamp, Ordering::SeqCst);
+    }
+
+    let more_button = document.get_element_by_id("editor_activity_more").unwrap();
+    more_button.class_list().toggle_with_force("editor_activity_more_hidden", (log.entries.len() as u32) < PAGE_SIZE).unwrap();
+}
@@ -0,0 +1,164 @@
+use lighthousemc_editor_common::packet::s2c::{ ChatS2CPacket, ChatMessage, ChatSource };
+use lighthousemc_editor_common::packet::c2s::ChatC2SPacket;
+use std::sync::atomic::{ AtomicU32, Ordering };
+use wasm_bindgen::prelude::*;
+use web_sys::{ Document, Element, HtmlInputElement, KeyboardEvent };
+use js_sys::Date;
+
+
+/// How many messages have arrived since the chat panel was last opened.
+static UNREAD : AtomicU32 = AtomicU32::new(0);
+
+
+pub fn init() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let input = document.get_element_by_id("editor_chat_input").unwrap();
+    let keydown_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : KeyboardEvent| {
+        if (event.key() == "Enter") { send(); }
+    });
+    input.add_event_listener_with_callback("keydown", keydown_callback.as_ref().unchecked_ref()).unwrap();
+    keydown_callback.forget();
+}
+
+
+/// Opens the chat panel and focuses the message input.
+pub fn focus() {
+    crate::sidebar::open("chat");
+    input().focus().unwrap();
+}
+
+/// Adds the focused file and cursor line to the message being written, as a link.
+pub fn share_location() {
+    let Some((_, path)) = crate::filetabs::currently_focused() else { return; };
+    let location = match (crate::code::cursor_line()) {
+        Some(line) => format!("{}:{}", path, line),
+        None       => path
+    };
+    let input = input();
+    let value = input.value();
+    input.set_value(&format!("{}{}{} ", value, if (value.is_empty() || value.ends_with(' ')) { "" } else { " " }, location));
+    focus();
+}
+
+/// Clears the unread counter. Called when the chat panel is opened.
+pub fn mark_read() {
+    UNREAD.store(0, Ordering::Relaxed);
+    update_unread();
+}
+
+fn send() {
+    let input   = input();
+    let message = input.value();
+    if (message.trim().is_empty()) { return; }
+    crate::ws::WS.send(ChatC2SPacket { message });
+    input.set_value("");
+}
+
+fn input() -> HtmlInputElement {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    document.get_element_by_id("editor_chat_input").unwrap().dyn_into::<HtmlInputElement>().unwrap()
+}
+
+
+pub fn on_chat(chat : ChatS2CPacket<'static>) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    let messages_element = document.get_element_by_id("editor_chat_messages").unwrap();
+
+    if (chat.is_history) {
+        messages_element.set_inner_html("");
+    }
+    for message in &*chat.messages {
+        messages_element.append_child(&render_message(&document, message)).unwrap();
+    }
+    messages_element.set_scroll_top(messages_element.scroll_height());
+
+    let panel_open = document.get_element_by_id("editor_chat").unwrap().class_list().contains("editor_sidebar_selected");
+    if (! chat.is_history && ! panel_open) {
+        UNREAD.fetch_add(chat.messages.len() as u32, Ordering::Relaxed);
+        update_unread();
+    }
+}
+
+fn update_unread() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    let unread   = UNREAD.load(Ordering::Relaxed);
+    let badge    = document.get_element_by_id("editor_chat_unread").unwrap();
+    badge.set_text_content(Some(&unread.to_string()));
+    badge.class_list().toggle_with_force("editor_chat_unread_hidden", unread == 0).unwrap();
+}
+
+
+fn render_message(document : &Document, message : &ChatMessage<'_>) -> Element {
+    let row = document.create_element("div").unwrap();
+    row.class_list().toggle_with_force("editor_chat_message", true).unwrap();
+    row.class_list().toggle_with_force("editor_chat_message_server", message.source == ChatSource::Server).unwrap();
+    let date = Date::new(&JsValue::from_f64(message.timestamp as f64));
+    row.set_attribute("title", &String::from(date.to_locale_string("default", &JsValue::UNDEFINED))).unwrap();
+
+    if (message.source != ChatSource::Server) {
+        let name = document.create_element("span").unwrap();
+        name.class_list().toggle_with_force("editor_chat_name", true).unwrap();
+        name.set_attribute("style", &format!("color: hsl({}, 100%, 62.5%);", (message.colour as u32) * 2)).unwrap();
+        name.set_text_content(Some(&message.sender_name));
+        row.append_child(&name).unwrap();
+        if (message.source == ChatSource::Game) {
+            let tag = document.create_element("span").unwrap();
+            tag.class_list().toggle_with_force("editor_chat_game", true).unwrap();
+            tag.set_text_content(Some("in game"));
+            row.append_child(&tag).unwrap();
+        }
+    }
+
+    // Split the message into plain text and `path:line` links.
+    let mut text = String::new();
+    for word in message.message.split_inclusive(char::is_whitespace) {
+        let trimmed = word.trim_end();
+        let core    = trimmed.trim_start_matches('(').trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+        let Some((file_id, path, line)) = parse_location(core) else {
+            text += word;
+            continue;
+        };
+        let start = trimmed.find(core).unwrap();
+        text += &trimmed[..start];
+        flush_text(document, &row, &mut text);
+
+        let link = document.create_element("span").unwrap();
+        link.class_list().toggle_with_force("editor_chat_link", true).unwrap();
+        link.set_text_content(Some(core));
+        let click_callback = Closure::<dyn FnMut() -> ()>::new(move || {
+            if (crate::state::open_file(file_id, path.clone(), true)) {
+                crate::code::reveal(file_id, line - 1, 0, line - 1, 0);
+            }
+        });
+        link.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+        click_callback.forget();
+        row.append_child(&link).unwrap();
+
+        text += &word[(start + core.len())..];
+    }
+    flush_text(document, &row, &mut text);
+
+    row
+}
+
+fn flush_text(document : &Document, row : &Element, text : &mut String) {
+    if (text.is_empty()) { return; }
+    let span = document.create_element("span").unwrap();
+    span.set_text_content(Some(text));
+    row.append_child(&span).unwrap();
+    text.clear();
+}
+
+/// Parses `path:line` into the file and one based line it points at, if the file exists.
+fn parse_location(word : &str) -> Option<(u64, String, u32)> {
+    let (path, line) = word.rsplit_once(':')?;
+    let line = line.parse::<u32>().ok().filter(|line| *line > 0)?;
+    let path = path.trim_start_matches('/');
+    let file_id = crate::state::find_file(path)?;
+    Some((file_id, path.to_string(), line))
+}
@@ -0,0 +1,95 @@
+use crate::state::{ FilesEntry, FilesEntryContents };
+use wasm_bindgen::prelude::*;
+use web_sys::Element;
+
+
+/// The number of bytes shown in the hex view.
+const HEX_VIEW_LIMIT : usize = 16384;
+
+
+pub fn open(file_id : u64) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let files = crate::state::FILES.read_files();
+    let Some(FilesEntry { fsname, is_open : Some(Some(FilesEntryContents::Binary { size, mime })), .. }) = files.get(&file_id) else { return; };
+
+    let view = document.get_element_by_id("editor_right_main_binary_view").unwrap();
+    view.set_inner_html("");
+    view.set_attribute("editor_binary_file_id", &file_id.to_string()).unwrap();
+
+    document.get_element_by_id("editor_right_main_binary_name").unwrap().set_inner_html(fsname);
+    document.get_element_by_id("editor_right_main_binary_info").unwrap().set_inner_html(&format!("{} · {}", mime, format_size(*size)));
+
+    let download = document.get_element_by_id("editor_right_main_binary_download").unwrap();
+    let download_callback = Closure::<dyn FnMut() -> ()>::new(move || { crate::files::download(file_id); });
+    download.dyn_ref::<web_sys::HtmlElement>().unwrap().set_onclick(Some(download_callback.as_ref().unchecked_ref()));
+    download_callback.forget();
+
+    if (mime.starts_with("image/")) {
+        let image = document.create_element("img").unwrap();
+        image.class_list().toggle_with_force("editor_binary_image", true).unwrap();
+        image.set_attribute("src", &crate::files::file_url(file_id)).unwrap();
+        image.set_attribute("alt", fsname).unwrap();
+        view.append_child(&image).unwrap();
+    } else {
+        let loader = document.create_element("div").unwrap();
+        loader.class_list().toggle_with_force("loader", true).unwrap();
+        loader.set_inner_html("<div></div><div></div><div></div>");
+        view.append_child(&loader).unwrap();
+        crate::files::fetch(file_id, move |data| {
+            // The user may have switched to another binary file in the meantime.
+            if (view.get_attribute("editor_binary_file_id").as_deref() == Some(&file_id.to_string())) {
+                fill_hex_view(&view, &data);
+            }
+        });
+    }
+}
+
+
+fn fill_hex_view(view : &Element, data : &[u8]) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    view.set_inner_html("");
+    let hex = document.create_element("pre").unwrap();
+    hex.class_list().toggle_with_force("editor_binary_hex", true).unwrap();
+    let mut inner = String::new();
+    for (i, row) in data[..data.len().min(HEX_VIEW_LIMIT)].chunks(16).enumerate() {
+        inner += &format!("<span class=\"editor_binary_hex_offset\">{:08X}</span>  ", i * 16);
+        for col in 0..16 {
+            match (row.get(col)) {
+                Some(byte) => { inner += &format!("{:02X} ", byte); },
+                None       => { inner += "   "; }
+            }
+            if (col == 7) { inner += " "; }
+        }
+        inner += " <span class=\"editor_binary_hex_ascii\">";
+        for byte in row {
+            inner += &match (byte) {
+                b'<'        => "&lt;".to_string(),
+                b'>'        => "&gt;".to_string(),
+                b'&'        => "&amp;".to_string(),
+                0x20..=0x7E => (*byte as char).to_string(),
+                _           => ".".to_string()
+            };
+        }
+        inner += "</span>\n";
+    }
+    if (data.len() > HEX_VIEW_LIMIT) {
+        inner += &format!("\n… {} more bytes. Download the file to see all of it.", data.len() - HEX_VIEW_LIMIT);
+    }
+    hex.set_inner_html(&inner);
+    view.append_child(&hex).unwrap();
+}
+
+
+fn format_size(size : u64) -> String {
+    if (size < 1024) {
+        format!("{} B", size)
+    } else if (size < 1024 * 1024) {
+        format!("{:.1} KiB", size as f64 / 1024.0)
+    } else {
+        format!("{:.1} MiB", size as f64 / (1024.0 * 1024.0))
+    }
+}
@@ -0,0 +1,156 @@
+use crate::code::monaco::{ self, Editor, EditorSelection, EditorHoverMessage };
+use lighthousemc_editor_common::packet::s2c::BlameS2CPacket;
+use lighthousemc_editor_common::packet::c2s::SetBlameVisibleC2SPacket;
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::Mutex;
+use std::cell::LazyCell;
+use std::collections::HashMap;
+use std::borrow::Cow;
+use wasm_bindgen::prelude::*;
+use js_sys::{ Object, Reflect };
+use serde::Serialize as Ser;
+
+
+/// Width of the line number gutter while blame is shown, in characters.
+const BLAME_GUTTER_CHARS : u32 = 16;
+
+
+pub fn init_css() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let     head  = document.get_element_by_id("head").unwrap();
+    let     style = document.create_element("style").unwrap();
+    let mut inner = String::new();
+
+    for i in 0..180 {
+        let hue = i * 2;
+        inner += &format!(".editor_code_blame_{} {{ background: hsl({},100%,62.5%); width: 4px !important; margin-left: 4px; }}", i, hue);
+    }
+
+    style.set_inner_html(&inner);
+    head.append_child(&style).unwrap();
+}
+
+
+static VISIBLE : AtomicBool = AtomicBool::new(false);
+
+static BLAME : BlameContainer = BlameContainer::new();
+struct BlameContainer {
+    files : LazyCell<Mutex<HashMap<u64, BlameFile>>>
+}
+struct BlameFile {
+    /// The author name and colour of each line.
+    lines           : Vec<Option<(String, u8)>>,
+    old_decorations : Vec<String>,
+    line_numbers    : Option<Closure<dyn FnMut(u32) -> String>>
+}
+impl BlameContainer { const fn new() -> Self { Self {
+    files : LazyCell::new(|| Mutex::new(HashMap::new()))
+} } }
+unsafe impl Sync for BlameContainer { }
+
+
+#[derive(Ser)]
+struct BlameDecoration<'l> {
+    options : BlameDecorationOptions<'l>,
+    range   : EditorSelection
+}
+#[derive(Ser)]
+struct BlameDecorationOptions<'l> {
+    #[serde(rename = "linesDecorationsClassName")]
+    lines_decorations_class_name : Cow<'l, str>,
+    #[serde(rename = "hoverMessage")]
+    hover_message                : EditorHoverMessage<'l>,
+    #[serde(rename = "isWholeLine")]
+    is_whole_line                : bool,
+    stickiness                   : u8
+}
+
+
+/// Shows or hides who last changed each line of the open files.
+pub fn toggle() {
+    let visible = ! VISIBLE.fetch_xor(true, Ordering::SeqCst);
+    crate::ws::WS.send(SetBlameVisibleC2SPacket { visible });
+    if (! visible) {
+        let editors = monaco::EDITORS.read();
+        for (file_id, file) in BLAME.files.lock().unwrap().drain() {
+            if let Some(editor) = editors.get(&file_id) {
+                editor.get_model().delta_decorations(file.old_decorations, Vec::new());
+                let options = Object::new();
+                Reflect::set(&options, &"lineNumbers".into(), &"on".into()).unwrap();
+                Reflect::set(&options, &"lineNumbersMinChars".into(), &5.into()).unwrap();
+                editor.update_options(&options);
+            }
+        }
+    }
+}
+
+
+pub fn on_blame(blame : BlameS2CPacket<'static>) {
+    if (! VISIBLE.load(Ordering::SeqCst)) { return; }
+    let mut lines = Vec::new();
+    for range in &*blame.ranges {
+        let author = range.author.as_ref().map(|author| (author.client_name.to_string(), author.colour));
+        lines.extend((0..range.lines).map(|_| author.clone()));
+    }
+    BLAME.files.lock().unwrap().entry(blame.file_id).or_insert_with(|| BlameFile {
+        lines           : Vec::new(),
+        old_decorations : Vec::new(),
+        line_numbers    : None
+    }).lines = lines;
+    if let Some(editor) = monaco::EDITORS.read().get(&blame.file_id) {
+        update_known(blame.file_id, editor);
+    }
+}
+
+
+/// Redraws the blame gutter of an editor, if blame is shown.
+pub(super) fn update_known(file_id : u64, editor : &Editor) {
+    let mut files = BLAME.files.lock().unwrap();
+    let Some(file) = files.get_mut(&file_id) else { return; };
+
+    let mut new_decorations = Vec::new();
+    let mut labels          = Vec::with_capacity(file.lines.len());
+    let mut start           = 0;
+    while (start < file.lines.len()) {
+        let author = &file.lines[start];
+        let end    = start + file.lines[start..].iter().take_while(|line| *line == author).count();
+        if let Some((client_name, colour)) = author {
+            new_decorations.push(serde_wasm_bindgen::to_value(&BlameDecoration {
+                options : BlameDecorationOptions {
+                    lines_decorations_class_name : format!("editor_code_blame_{}", colour).into(),
+                    hover_message                : EditorHoverMessage { value : client_name.into() },
+                    is_whole_line                : true,
+                    stickiness                   : 1
+                },
+                range   : EditorSelection {
+                    start_line   : start + 1,
+                    start_column : 1,
+                    end_line     : end,
+                    end_column   : 1
+                }
+            }).unwrap());
+        }
+        labels.push(author.as_ref().map_or_else(String::new, |(client_name, _)| client_name.clone()));
+        labels.extend((start + 1..end).map(|_| String::new()));
+        start = end;
+    }
+    file.old_decorations = editor.get_model().delta_decorations(file.old_decorations.clone(), new_decorations);
+
+    let line_numbers = Closure::<dyn FnMut(u32) -> String>::new(move |line : u32| {
+        labels.get((line as usize).wrapping_sub(1)).cloned().unwrap_or_default()
+    });
+    let options = Object::new();
+    Reflect::set(&options, &"lineNumbers".into(), line_numbers.as_ref()).unwrap();
+    Reflect::set(&options, &"lineNumbersMinChars".into(), &BLAME_GUTTER_CHARS.into()).unwrap();
+    editor.update_options(&options);
+    // The previous function is no longer used by the editor once it has been replaced.
+    file.line_numbers = Some(line_numbers);
+}
+
+
+/// Forgets the blame of a file whose editor was destroyed.
+pub(super) fn forget(file_id : u64) {
+    BLAME.files.lock().unwrap().remove(&file_id);
+}
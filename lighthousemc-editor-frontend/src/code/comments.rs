@@ -0,0 +1,83 @@
+use crate::code::monaco::{ self, Editor, EditorDecoration, EditorDecorationOptions, EditorSelection, EditorHoverMessage, EditorPosition };
+use crate::code::diffsync;
+use lighthousemc_editor_common::packet::s2c::CommentThread;
+use std::sync::{ RwLock, RwLockReadGuard, RwLockWriteGuard, Mutex };
+use std::cell::LazyCell;
+use std::collections::{ BTreeMap, HashMap };
+
+
+pub(crate) static THREADS : ThreadsContainer = ThreadsContainer::new();
+pub(crate) struct ThreadsContainer {
+    threads         : LazyCell<RwLock<BTreeMap<u64, CommentThread<'static>>>>,
+    old_decorations : LazyCell<Mutex<HashMap<u64, Vec<String>>>>
+}
+impl ThreadsContainer { const fn new() -> Self { Self {
+    threads         : LazyCell::new(|| RwLock::new(BTreeMap::new())),
+    old_decorations : LazyCell::new(|| Mutex::new(HashMap::new()))
+} } }
+impl ThreadsContainer {
+    pub(crate) fn read(&self) -> RwLockReadGuard<BTreeMap<u64, CommentThread<'static>>> {
+        self.threads.read().unwrap()
+    }
+    pub(crate) fn write(&self) -> RwLockWriteGuard<BTreeMap<u64, CommentThread<'static>>> {
+        self.threads.write().unwrap()
+    }
+}
+unsafe impl Sync for ThreadsContainer { }
+
+
+/// Redraws the comment markers of a file, if its editor has been created.
+pub(crate) fn update(file_id : u64) {
+    if let Some(editor) = monaco::EDITORS.read().get(&file_id) {
+        update_known(file_id, editor);
+    }
+}
+
+pub(super) fn update_known(file_id : u64, editor : &Editor) {
+    let model = editor.get_model();
+    let mut new_decorations = Vec::new();
+    for (_, thread) in &*THREADS.read() {
+        if (thread.file_id == file_id && ! thread.resolved) {
+            let start = serde_wasm_bindgen::from_value::<EditorPosition>(model.get_position_at(thread.start)).unwrap();
+            let end   = serde_wasm_bindgen::from_value::<EditorPosition>(model.get_position_at(thread.end  )).unwrap();
+            let hover = thread.comments.first().map_or_else(String::new, |comment| match (thread.comments.len()) {
+                1       => format!("**{}**: {}", comment.client_name, comment.text),
+                2       => format!("**{}**: {}\n\n*1 reply*", comment.client_name, comment.text),
+                replies => format!("**{}**: {}\n\n*{} replies*", comment.client_name, comment.text, replies - 1)
+            });
+            new_decorations.push(serde_wasm_bindgen::to_value(&EditorDecoration {
+                options : EditorDecorationOptions {
+                    class_name    : if (thread.start == thread.end) { "editor_code_comment_single" } else { "editor_code_comment_range" }.into(),
+                    hover_message : EditorHoverMessage { value : hover.into() },
+                    is_whole_line : false,
+                    stickiness    : 1
+                },
+                range   : EditorSelection {
+                    start_line   : start.line,
+                    start_column : start.column,
+                    end_line     : end.line,
+                    end_column   : end.column
+                }
+            }).unwrap());
+        }
+    }
+    let mut old_decorations = THREADS.old_decorations.lock().unwrap();
+    let     old             = old_decorations.remove(&file_id).unwrap_or_default();
+    old_decorations.insert(file_id, model.delta_decorations(old, new_decorations));
+}
+
+
+/// Moves the anchors of the threads on a file to follow a change to its text.
+pub(super) fn shift(file_id : u64, old_text : &str, new_text : &str) {
+    for thread in THREADS.write().values_mut() {
+        if (thread.file_id == file_id) {
+            (thread.start, thread.end) = diffsync::shift_selection(old_text, new_text, thread.start, thread.end);
+        }
+    }
+}
+
+
+/// Forgets the markers of a file whose editor was destroyed.
+pub(super) fn forget(file_id : u64) {
+    THREADS.old_decorations.lock().unwrap().remove(&file_id);
+}
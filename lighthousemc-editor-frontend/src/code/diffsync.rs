@@ -4,6 +4,8 @@
 use crate::state::FilesEntryContents;
 use crate::code::monaco::{ self, EditorPosition, EditorSelection, EditorSetSelection };
 use crate::code::remote_cursors;
+use crate::code::comments;
+use crate::code::suggestions;
 use lighthousemc_editor_common::packet::c2s::PatchFileC2SPacket;
 use lighthousemc_editor_common::dmp::{ DiffMatchPatch, Efficient, PatchInput, Patches };
 
@@ -66,6 +68,7 @@ pub fn apply_patches_from_server(file_id : u64, patches : Patches<Efficient>) {
                 for selection in &mut selections {
                     (selection.start, selection.end) = shift_selection(&intermediate_client_text, &new_client_text, selection.start, selection.end);
                 }
+                comments::shift(file_id, &intermediate_client_text, &new_client_text);
                 super::selection_changed();
                 intermediate_client_text = new_client_text;
             }
@@ -92,6 +95,8 @@ pub fn apply_patches_from_server(file_id : u64, patches : Patches<Efficient>) {
                 }
             }*/
             remote_cursors::update_known(file_id, client_editor);
+            comments::update_known(file_id, client_editor);
+            suggestions::update_known(file_id, client_editor);
 
             break;
         }
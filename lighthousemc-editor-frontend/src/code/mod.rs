@@ -1,15 +1,24 @@
     mod monaco;
+    mod binary;
+pub mod blame;
+pub mod comments;
 pub mod diffsync;
+pub mod suggestions;
 pub mod remote_cursors;
 
 
-use crate::code::monaco::{ EditorSelection, EditorPosition };
-use lighthousemc_editor_common::packet::c2s::{ SelectionsC2SPacket, SelectionRange };
+use crate::code::monaco::{ EditorSelection, EditorPosition, RevealTarget };
+use lighthousemc_editor_common::packet::c2s::{ SelectionsC2SPacket, SelectionRange, ViewportC2SPacket, Viewport };
 use std::sync::atomic::Ordering;
+use std::sync::Mutex;
 use wasm_bindgen::prelude::*;
 use web_sys::KeyboardEvent;
 
 
+/// The viewport last sent to the server.
+static LAST_VIEWPORT : Mutex<Option<Viewport>> = Mutex::new(None);
+
+
 pub fn init() {
     let window   = web_sys::window().unwrap();
     let document = window.document().unwrap();
@@ -17,6 +26,7 @@ pub fn init() {
     monaco::init_theme();
 
     remote_cursors::init_css();
+    blame::init_css();
 
     let timeout_callback = Closure::<dyn FnMut() -> ()>::new(move || {
         if (remote_cursors::SELECTION_CHANGED.swap(false, Ordering::Relaxed)) {
@@ -35,6 +45,12 @@ pub fn init() {
                 selections
             });
         }
+        let viewport = current_viewport();
+        let mut last_viewport = LAST_VIEWPORT.lock().unwrap();
+        if (*last_viewport != viewport) {
+            *last_viewport = viewport;
+            crate::ws::WS.send(ViewportC2SPacket { viewport });
+        }
     });
     crate::set_interval(timeout_callback.as_ref().unchecked_ref(), 250);
     timeout_callback.forget();
@@ -45,10 +61,43 @@ pub fn init() {
 
             (true, false, "r") => { event.prevent_default(); },
 
-            (true, false, "s") => { event.prevent_default(); },
+            (true, false, "s") => {
+                event.prevent_default();
+                crate::palette::run_command("Save");
+            },
+
+            (true, false, "p") => {
+                event.prevent_default();
+                crate::palette::open(crate::palette::PaletteMode::Files);
+            },
+
+            (true, false, "P") => {
+                event.prevent_default();
+                crate::palette::open(crate::palette::PaletteMode::Commands);
+            },
+
+            (true, false, "b") => {
+                event.prevent_default();
+                crate::filetree::toggle();
+            },
+
+            (true, true, "b") => {
+                event.prevent_default();
+                blame::toggle();
+            },
+
+            (true, true, "m") => {
+                event.prevent_default();
+                crate::comments::start_new();
+            },
 
             (true, false, "f") => { event.prevent_default(); },
 
+            (true, false, "F") => {
+                event.prevent_default();
+                crate::search::focus();
+            },
+
             (true, false, "w") => {
                 event.prevent_default();
                 if let Some((file_id, file_path)) = crate::filetabs::currently_focused() {
@@ -79,7 +128,10 @@ pub fn init() {
 
 pub fn open_noopen() { open("editor_right_main_noopen"); }
 
-pub fn open_nontext() { open("editor_right_main_nontext"); }
+pub fn open_binary(file_id : u64) {
+    open("editor_right_main_binary");
+    binary::open(file_id);
+}
 
 pub fn open_load() { open("editor_right_main_loader"); }
 
@@ -92,6 +144,62 @@ pub fn open_monaco(file_id : u64) {
     monaco::open(file_id);
 }
 
+/// Selects and scrolls to a range in a file. Lines and columns are zero based, with columns in UTF-16 code units.
+pub fn reveal(file_id : u64, start_line : u32, start_column : u32, end_line : u32, end_column : u32) {
+    monaco::reveal(file_id, RevealTarget::Range(EditorSelection {
+        start_line   : start_line   as usize + 1,
+        start_column : start_column as usize + 1,
+        end_line     : end_line     as usize + 1,
+        end_column   : end_column   as usize + 1
+    }));
+    selection_changed();
+}
+
+/// Selects and scrolls to a range in a file, given as offsets in UTF-16 code units.
+pub fn reveal_offsets(file_id : u64, start : usize, end : usize) {
+    monaco::reveal(file_id, RevealTarget::Offsets(start, end));
+    selection_changed();
+}
+
+/// The file and first selection of the focused text editor, as offsets in UTF-16 code units.
+pub fn current_selection() -> Option<(u64, usize, usize)> {
+    let     editors   = monaco::EDITORS.read();
+    let     file_id   = monaco::currently_focused()?;
+    let     editor    = editors.get(&file_id)?;
+    let     model     = editor.get_model();
+    let     selection = serde_wasm_bindgen::from_value::<EditorSelection>(editor.get_selections().into_iter().next()?).ok()?;
+    let mut start     = model.get_offset_at(serde_wasm_bindgen::to_value(&EditorPosition { line : selection.start_line , column : selection.start_column }).unwrap());
+    let mut end       = model.get_offset_at(serde_wasm_bindgen::to_value(&EditorPosition { line : selection.end_line   , column : selection.end_column   }).unwrap());
+    if (start > end) { (start, end) = (end, start); }
+    Some((file_id, start, end))
+}
+
+/// The one based line of an offset in UTF-16 code units, if the file is open in a text editor.
+pub fn offset_to_line(file_id : u64, offset : usize) -> Option<usize> {
+    let editors = monaco::EDITORS.read();
+    Some(serde_wasm_bindgen::from_value::<EditorPosition>(editors.get(&file_id)?.get_model().get_position_at(offset)).ok()?.line)
+}
+
+/// The line of the first cursor in the focused file, if it is open in a text editor. One based.
+pub fn cursor_line() -> Option<usize> {
+    let editors   = monaco::EDITORS.read();
+    let editor    = editors.get(&monaco::currently_focused()?)?;
+    let selection = editor.get_selections().into_iter().next()?;
+    Some(serde_wasm_bindgen::from_value::<EditorSelection>(selection).ok()?.start_line)
+}
+
+/// Scrolls a file so that a line is at the top, without moving the cursor. Zero based.
+pub fn scroll_to(file_id : u64, line : u32) {
+    monaco::reveal(file_id, RevealTarget::ScrollTo(line as usize + 1));
+}
+
+/// The focused file and which of its lines are visible. Files without a text editor, such as binary files, report no lines.
+fn current_viewport() -> Option<Viewport> {
+    let (file_id, _) = crate::filetabs::currently_focused()?;
+    let (first_line, last_line) = monaco::visible_lines(file_id).map_or((0, 0), |(first, last)| ((first - 1) as u32, (last - 1) as u32));
+    Some(Viewport { file_id, first_line, last_line })
+}
+
 fn open(selected : &str) {
     let window    = web_sys::window().unwrap();
     let document  = window.document().unwrap();
@@ -1,5 +1,8 @@
 use crate::code::remote_cursors::REMOTE_SELECTIONS;
 use crate::code::diffsync;
+use crate::code::blame;
+use crate::code::comments;
+use crate::code::suggestions;
 use std::cell::LazyCell;
 use std::sync::{ RwLock, RwLockReadGuard, RwLockWriteGuard, Arc, Mutex };
 use std::collections::HashMap;
@@ -29,6 +32,18 @@ impl EditorsContainer {
 unsafe impl Sync for EditorsContainer { }
 
 
+/// A range to reveal once the editor of a file has been created.
+static PENDING_REVEAL : Mutex<Option<(u64, RevealTarget)>> = Mutex::new(None);
+
+pub enum RevealTarget {
+    Range(EditorSelection),
+    /// Start and end offsets in UTF-16 code units.
+    Offsets(usize, usize),
+    /// Scrolls so that a line is at the top, without moving the cursor. One based.
+    ScrollTo(usize)
+}
+
+
 mod js { use super::*;
 
     #[wasm_bindgen]
@@ -80,6 +95,30 @@ mod js { use super::*;
         /// https://microsoft.github.io/monaco-editor/docs.html#interfaces/editor.IStandaloneCodeEditor.html#onDidChangeModelContent
         #[wasm_bindgen(method, js_name = "onDidChangeModelContent")]
         pub fn on_did_change_model_content(this : &Editor, callback : &JsValue);
+
+        /// https://microsoft.github.io/monaco-editor/docs.html#interfaces/editor.IStandaloneCodeEditor.html#revealRangeInCenter.revealRangeInCenter-1
+        #[wasm_bindgen(method, js_name = "revealRangeInCenter")]
+        pub fn reveal_range_in_center(this : &Editor, range : JsValue);
+
+        /// https://microsoft.github.io/monaco-editor/docs.html#interfaces/editor.IStandaloneCodeEditor.html#getVisibleRanges.getVisibleRanges-1
+        #[wasm_bindgen(method, js_name = "getVisibleRanges")]
+        pub fn get_visible_ranges(this : &Editor) -> Vec<JsValue>;
+
+        /// https://microsoft.github.io/monaco-editor/docs.html#interfaces/editor.IStandaloneCodeEditor.html#getTopForLineNumber.getTopForLineNumber-1
+        #[wasm_bindgen(method, js_name = "getTopForLineNumber")]
+        pub fn get_top_for_line_number(this : &Editor, line_number : usize) -> f64;
+
+        /// https://microsoft.github.io/monaco-editor/docs.html#interfaces/editor.IStandaloneCodeEditor.html#setScrollTop.setScrollTop-1
+        #[wasm_bindgen(method, js_name = "setScrollTop")]
+        pub fn set_scroll_top(this : &Editor, scroll_top : f64);
+
+        /// https://microsoft.github.io/monaco-editor/docs.html#interfaces/editor.IStandaloneCodeEditor.html#focus.focus-1
+        #[wasm_bindgen(method)]
+        pub fn focus(this : &Editor);
+
+        /// https://microsoft.github.io/monaco-editor/docs.html#interfaces/editor.IStandaloneCodeEditor.html#updateOptions.updateOptions-1
+        #[wasm_bindgen(method, js_name = "updateOptions")]
+        pub fn update_options(this : &Editor, options : &JsValue);
     }
 
     #[wasm_bindgen]
@@ -312,6 +351,7 @@ pub fn create(file_id : u64, file_name : &str, initial_script : String, open : b
             if (event.reason != 1 && event.reason != 0) {
                 if let Some(currently_focused) = currently_focused() && currently_focused == file_id {
                     super::selection_changed();
+                    crate::follow::stop();
                 }
             }
         });
@@ -333,6 +373,7 @@ pub fn create(file_id : u64, file_name : &str, initial_script : String, open : b
                             }
                         }
                     }
+                    comments::shift(file_id, &old_content, &new_content);
                     super::selection_changed();
                 }
                 *old_content = new_content;
@@ -342,12 +383,54 @@ pub fn create(file_id : u64, file_name : &str, initial_script : String, open : b
         change_model_content_callback.forget();
 
         crate::code::remote_cursors::update_known(file_id, &editor);
+        blame::update_known(file_id, &editor);
+        comments::update_known(file_id, &editor);
+        suggestions::update_known(file_id, &editor);
+
+        let pending_reveal = PENDING_REVEAL.lock().unwrap().take_if(|(pending_file_id, _)| *pending_file_id == file_id);
+        if let Some((_, target)) = pending_reveal {
+            reveal_in(&editor, target);
+        }
 
         EDITORS.write().insert(file_id, editor);
     });
 }
 
 
+/// Selects a range in a file and scrolls it into view, waiting for the editor to be created if needed.
+pub fn reveal(file_id : u64, target : RevealTarget) {
+    if let Some(editor) = EDITORS.read().get(&file_id) {
+        reveal_in(editor, target);
+    } else {
+        *PENDING_REVEAL.lock().unwrap() = Some((file_id, target));
+    }
+}
+
+fn reveal_in(editor : &Editor, target : RevealTarget) {
+    let range = match (target) {
+        RevealTarget::ScrollTo(line)      => {
+            editor.set_scroll_top(editor.get_top_for_line_number(line));
+            return;
+        },
+        RevealTarget::Range(range)        => range,
+        RevealTarget::Offsets(start, end) => {
+            let model = editor.get_model();
+            let start = serde_wasm_bindgen::from_value::<EditorPosition>(model.get_position_at(start)).unwrap();
+            let end   = serde_wasm_bindgen::from_value::<EditorPosition>(model.get_position_at(end  )).unwrap();
+            EditorSelection { start_line : start.line, start_column : start.column, end_line : end.line, end_column : end.column }
+        }
+    };
+    editor.set_selections(vec![ serde_wasm_bindgen::to_value(&EditorSetSelection {
+        start_line   : range.start_line,
+        start_column : range.start_column,
+        end_line     : range.end_line,
+        end_column   : range.end_column
+    }).unwrap() ]);
+    editor.reveal_range_in_center(serde_wasm_bindgen::to_value(&range).unwrap());
+    editor.focus();
+}
+
+
 pub fn open(file_id : u64) {
     let window   = web_sys::window().unwrap();
     let document = window.document().unwrap();
@@ -376,6 +459,19 @@ pub fn destroy(file_id : u64) {
     }
 
     EDITORS.write().remove(&file_id);
+    blame::forget(file_id);
+    comments::forget(file_id);
+    suggestions::forget(file_id);
+}
+
+
+/// The first and last visible lines of a file, if its editor has been created. One based.
+pub fn visible_lines(file_id : u64) -> Option<(usize, usize)> {
+    let editors = EDITORS.read();
+    let ranges  = editors.get(&file_id)?.get_visible_ranges();
+    let first   = serde_wasm_bindgen::from_value::<EditorSelection>(ranges.first()?.clone()).ok()?;
+    let last    = serde_wasm_bindgen::from_value::<EditorSelection>(ranges.last()?.clone()).ok()?;
+    Some((first.start_line, last.end_line))
 }
 
 
@@ -395,7 +491,7 @@ pub fn currently_focused() -> Option<u64> {
 
 
 fn require<F : Fn() -> () + 'static>(f : F) {
-    let config = MonacoConfig { paths : MonacoConfigPaths { vs : "https://unpkg.com/monaco-editor@latest/min/vs".into() } };
+    let config = MonacoConfig { paths : MonacoConfigPaths { vs : format!("{}/vendor/monaco-editor/min/vs", crate::editor_path()).into() } };
     js::config(&serde_wasm_bindgen::to_value(&config).unwrap());
 
     let from = Array::new();
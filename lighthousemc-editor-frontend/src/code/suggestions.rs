@@ -0,0 +1,113 @@
+use crate::code::monaco::{ self, Editor, EditorSelection, EditorHoverMessage, EditorPosition };
+use lighthousemc_editor_common::packet::s2c::Suggestion;
+use std::sync::{ RwLock, RwLockReadGuard, Mutex };
+use std::cell::LazyCell;
+use std::collections::HashMap;
+use std::borrow::Cow;
+use serde::Serialize as Ser;
+
+
+pub(crate) static SUGGESTIONS : SuggestionsContainer = SuggestionsContainer::new();
+pub(crate) struct SuggestionsContainer {
+    files           : LazyCell<RwLock<HashMap<u64, Vec<Suggestion<'static>>>>>,
+    old_decorations : LazyCell<Mutex<HashMap<u64, Vec<String>>>>
+}
+impl SuggestionsContainer { const fn new() -> Self { Self {
+    files           : LazyCell::new(|| RwLock::new(HashMap::new())),
+    old_decorations : LazyCell::new(|| Mutex::new(HashMap::new()))
+} } }
+impl SuggestionsContainer {
+    pub(crate) fn read(&self) -> RwLockReadGuard<HashMap<u64, Vec<Suggestion<'static>>>> {
+        self.files.read().unwrap()
+    }
+}
+unsafe impl Sync for SuggestionsContainer { }
+
+
+#[derive(Ser)]
+struct SuggestionDecoration<'l> {
+    options : SuggestionDecorationOptions<'l>,
+    range   : EditorSelection
+}
+#[derive(Ser)]
+struct SuggestionDecorationOptions<'l> {
+    #[serde(rename = "className")]
+    class_name    : Cow<'l, str>,
+    #[serde(rename = "hoverMessage")]
+    hover_message : EditorHoverMessage<'l>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    after         : Option<SuggestionInjectedText<'l>>,
+    stickiness    : u8
+}
+#[derive(Ser)]
+struct SuggestionInjectedText<'l> {
+    content           : Cow<'l, str>,
+    #[serde(rename = "inlineClassName")]
+    inline_class_name : Cow<'l, str>
+}
+
+
+/// Replaces the suggestions on a file.
+pub(crate) fn set(file_id : u64, suggestions : Vec<Suggestion<'static>>) {
+    {
+        let mut files = SUGGESTIONS.files.write().unwrap();
+        if (suggestions.is_empty()) { files.remove(&file_id); }
+        else { files.insert(file_id, suggestions); }
+    }
+    update(file_id);
+}
+
+/// Redraws the suggestions on a file, if its editor has been created.
+pub(crate) fn update(file_id : u64) {
+    if let Some(editor) = monaco::EDITORS.read().get(&file_id) {
+        update_known(file_id, editor);
+    }
+}
+
+pub(super) fn update_known(file_id : u64, editor : &Editor) {
+    let model = editor.get_model();
+    let position_at = |offset : usize| serde_wasm_bindgen::from_value::<EditorPosition>(model.get_position_at(offset)).unwrap();
+    let mut new_decorations = Vec::new();
+    for suggestion in SUGGESTIONS.read().get(&file_id).into_iter().flatten() {
+        let start = position_at(suggestion.start);
+        let end   = position_at(suggestion.end);
+        let range = EditorSelection { start_line : start.line, start_column : start.column, end_line : end.line, end_column : end.column };
+        if (suggestion.applied) {
+            // The client's own suggestion, already in its text.
+            new_decorations.push(serde_wasm_bindgen::to_value(&SuggestionDecoration {
+                options : SuggestionDecorationOptions {
+                    class_name    : "editor_code_suggestion_applied".into(),
+                    hover_message : EditorHoverMessage { value : format!("*Your suggestion, replacing:*\n```\n{}\n```", suggestion.original).into() },
+                    after         : None,
+                    stickiness    : 1
+                },
+                range
+            }).unwrap());
+        } else {
+            // Someone else's suggestion: strike out what it replaces, and show the replacement after it.
+            new_decorations.push(serde_wasm_bindgen::to_value(&SuggestionDecoration {
+                options : SuggestionDecorationOptions {
+                    class_name    : "editor_code_suggestion_removed".into(),
+                    hover_message : EditorHoverMessage { value : format!("**{}** suggests:\n```\n{}\n```", suggestion.client_name, suggestion.replacement).into() },
+                    after         : (! suggestion.replacement.is_empty()).then(|| SuggestionInjectedText {
+                        content           : suggestion.replacement.replace('\n', " ⏎ ").into(),
+                        inline_class_name : "editor_code_suggestion_inserted".into()
+                    }),
+                    stickiness    : 1
+                },
+                range
+            }).unwrap());
+        }
+    }
+    let mut old_decorations = SUGGESTIONS.old_decorations.lock().unwrap();
+    let     old             = old_decorations.remove(&file_id).unwrap_or_default();
+    old_decorations.insert(file_id, model.delta_decorations(old, new_decorations));
+}
+
+
+/// Forgets the suggestions on a file whose editor was destroyed. The server sends them again when it is reopened.
+pub(super) fn forget(file_id : u64) {
+    SUGGESTIONS.files.write().unwrap().remove(&file_id);
+    SUGGESTIONS.old_decorations.lock().unwrap().remove(&file_id);
+    crate::suggestions::update();
+}
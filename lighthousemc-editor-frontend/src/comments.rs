@@ -0,0 +1,242 @@
+use crate::code::comments::THREADS;
+use lighthousemc_editor_common::packet::s2c::CommentThreadsS2CPacket;
+use lighthousemc_editor_common::packet::c2s::{ CommentC2SPacket, CommentAction };
+use std::sync::Mutex;
+use std::collections::{ BTreeSet, HashMap };
+use wasm_bindgen::prelude::*;
+use web_sys::{ HtmlInputElement, HtmlTextAreaElement, KeyboardEvent };
+use js_sys::Date;
+
+
+/// The file and range that the comment being written is about, in UTF-16 code units.
+static NEW_COMMENT : Mutex<Option<(u64, usize, usize)>> = Mutex::new(None);
+
+
+pub fn init() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let show_resolved = document.get_element_by_id("editor_comments_show_resolved").unwrap();
+    let change_callback = Closure::<dyn FnMut() -> ()>::new(move || { update(); });
+    show_resolved.add_event_listener_with_callback("change", change_callback.as_ref().unchecked_ref()).unwrap();
+    change_callback.forget();
+
+    let text = document.get_element_by_id("editor_comments_new_text").unwrap();
+    let keydown_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : KeyboardEvent| {
+        match ((event.ctrl_key(), event.key().as_str())) {
+            (true, "Enter") => { event.prevent_default(); post_new(); },
+            (_, "Escape")   => { event.prevent_default(); cancel_new(); },
+            _               => { }
+        }
+    });
+    text.add_event_listener_with_callback("keydown", keydown_callback.as_ref().unchecked_ref()).unwrap();
+    keydown_callback.forget();
+
+    let post = document.get_element_by_id("editor_comments_new_post").unwrap();
+    let click_callback = Closure::<dyn FnMut() -> ()>::new(move || { post_new(); });
+    post.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+    click_callback.forget();
+
+    let cancel = document.get_element_by_id("editor_comments_new_cancel").unwrap();
+    let click_callback = Closure::<dyn FnMut() -> ()>::new(move || { cancel_new(); });
+    cancel.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+    click_callback.forget();
+}
+
+
+/// Starts writing a comment on the selection in the focused file.
+pub fn start_new() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let Some((file_id, start, end)) = crate::code::current_selection() else { return; };
+    *NEW_COMMENT.lock().unwrap() = Some((file_id, start, end));
+    document.get_element_by_id("editor_comments_new_location").unwrap().set_text_content(Some(&location(file_id, start)));
+    document.get_element_by_id("editor_comments_new").unwrap().class_list().toggle_with_force("editor_comments_new_hidden", false).unwrap();
+    crate::sidebar::open("comments");
+    new_text().focus().unwrap();
+}
+
+fn post_new() {
+    let Some((file_id, start, end)) = *NEW_COMMENT.lock().unwrap() else { return; };
+    let text = new_text().value();
+    if (text.trim().is_empty()) { return; }
+    crate::ws::WS.send(CommentC2SPacket { action : CommentAction::Create { file_id, start, end, text } });
+    cancel_new();
+}
+
+fn cancel_new() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    *NEW_COMMENT.lock().unwrap() = None;
+    new_text().set_value("");
+    document.get_element_by_id("editor_comments_new").unwrap().class_list().toggle_with_force("editor_comments_new_hidden", true).unwrap();
+}
+
+fn new_text() -> HtmlTextAreaElement {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    document.get_element_by_id("editor_comments_new_text").unwrap().dyn_into::<HtmlTextAreaElement>().unwrap()
+}
+
+
+pub fn on_threads(threads : CommentThreadsS2CPacket<'static>) {
+    let mut file_ids = BTreeSet::new();
+    {
+        let mut known = THREADS.write();
+        if (threads.is_initial) {
+            file_ids.extend(known.values().map(|thread| thread.file_id));
+            known.clear();
+        }
+        for thread in threads.threads.into_owned() {
+            file_ids.insert(thread.file_id);
+            known.insert(thread.thread_id, thread);
+        }
+    }
+    for file_id in file_ids {
+        crate::code::comments::update(file_id);
+    }
+    update();
+}
+
+
+/// Redraws the comment panel, keeping any replies being written.
+pub fn update() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let list          = document.get_element_by_id("editor_comments_list").unwrap();
+    let show_resolved = document.get_element_by_id("editor_comments_show_resolved").unwrap().dyn_into::<HtmlInputElement>().unwrap().checked();
+
+    // Remember replies being written.
+    let mut drafts  = HashMap::new();
+    let     focused = document.active_element().and_then(|element| element.get_attribute("editor_comments_thread_id"));
+    let     inputs  = list.get_elements_by_class_name("editor_comments_reply");
+    for i in 0..inputs.length() {
+        let input = inputs.get_with_index(i).unwrap();
+        let Some(thread_id) = input.get_attribute("editor_comments_thread_id") else { continue; };
+        let value = input.dyn_into::<HtmlInputElement>().unwrap().value();
+        if (! value.is_empty()) { drafts.insert(thread_id, value); }
+    }
+    list.set_inner_html("");
+
+    let     threads = THREADS.read();
+    let mut sorted  = threads.values().map(|thread| (crate::state::file_path(thread.file_id), thread)).filter(|(path, _)| path.is_some()).collect::<Vec<_>>();
+    sorted.sort_by(|(path_a, a), (path_b, b)| (path_a, a.start, a.thread_id).cmp(&(path_b, b.start, b.thread_id)));
+
+    let unresolved = sorted.iter().filter(|(_, thread)| ! thread.resolved).count();
+    document.get_element_by_id("editor_comments_count").unwrap().set_text_content(Some(&unresolved.to_string()));
+
+    let now = Date::now();
+    let mut shown = 0;
+    for (_, thread) in sorted {
+        if (thread.resolved && ! show_resolved) { continue; }
+        shown += 1;
+        let thread_id = thread.thread_id;
+        let file_id   = thread.file_id;
+        let (start, end) = (thread.start, thread.end);
+
+        let element = document.create_element("div").unwrap();
+        element.class_list().toggle_with_force("editor_comments_thread", true).unwrap();
+        element.class_list().toggle_with_force("editor_comments_thread_resolved", thread.resolved).unwrap();
+
+        let header = document.create_element("div").unwrap();
+        header.class_list().toggle_with_force("editor_comments_location", true).unwrap();
+        header.set_text_content(Some(&location(file_id, start)));
+        let click_callback = Closure::<dyn FnMut() -> ()>::new(move || {
+            let Some(path) = crate::state::file_path(file_id) else { return; };
+            if (crate::state::open_file(file_id, path, true)) {
+                crate::code::reveal_offsets(file_id, start, end);
+            }
+        });
+        header.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+        click_callback.forget();
+        element.append_child(&header).unwrap();
+
+        for comment in &*thread.comments {
+            let comment_element = document.create_element("div").unwrap();
+            comment_element.class_list().toggle_with_force("editor_comments_comment", true).unwrap();
+            let name = document.create_element("span").unwrap();
+            name.class_list().toggle_with_force("editor_comments_name", true).unwrap();
+            name.set_attribute("style", &format!("color: hsl({}, 100%, 62.5%);", (comment.colour as u32) * 2)).unwrap();
+            name.set_text_content(Some(&comment.client_name));
+            comment_element.append_child(&name).unwrap();
+            let time = document.create_element("span").unwrap();
+            time.class_list().toggle_with_force("editor_comments_time", true).unwrap();
+            time.set_text_content(Some(&time_ago(now - comment.timestamp as f64)));
+            comment_element.append_child(&time).unwrap();
+            let text = document.create_element("div").unwrap();
+            text.class_list().toggle_with_force("editor_comments_text", true).unwrap();
+            text.set_text_content(Some(&comment.text));
+            comment_element.append_child(&text).unwrap();
+            element.append_child(&comment_element).unwrap();
+        }
+
+        let actions = document.create_element("div").unwrap();
+        actions.class_list().toggle_with_force("hbox", true).unwrap();
+        let reply = document.create_element("input").unwrap().dyn_into::<HtmlInputElement>().unwrap();
+        reply.class_list().toggle_with_force("editor_comments_reply", true).unwrap();
+        reply.set_attribute("editor_comments_thread_id", &thread_id.to_string()).unwrap();
+        reply.set_attribute("placeholder", "Reply").unwrap();
+        reply.set_attribute("maxlength", "2000").unwrap();
+        if let Some(draft) = drafts.get(&thread_id.to_string()) { reply.set_value(draft); }
+        let reply1 = reply.clone();
+        let keydown_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : KeyboardEvent| {
+            if (event.key() == "Enter") {
+                let text = reply1.value();
+                if (text.trim().is_empty()) { return; }
+                crate::ws::WS.send(CommentC2SPacket { action : CommentAction::Reply { thread_id, text } });
+                reply1.set_value("");
+            }
+        });
+        reply.add_event_listener_with_callback("keydown", keydown_callback.as_ref().unchecked_ref()).unwrap();
+        keydown_callback.forget();
+        actions.append_child(&reply).unwrap();
+        let resolved = thread.resolved;
+        let resolve  = document.create_element("div").unwrap();
+        resolve.class_list().toggle_with_force("editor_comments_resolve", true).unwrap();
+        resolve.set_text_content(Some(if (resolved) { "Reopen" } else { "Resolve" }));
+        let click_callback = Closure::<dyn FnMut() -> ()>::new(move || {
+            crate::ws::WS.send(CommentC2SPacket { action : CommentAction::SetResolved { thread_id, resolved : ! resolved } });
+        });
+        resolve.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+        click_callback.forget();
+        actions.append_child(&resolve).unwrap();
+        element.append_child(&actions).unwrap();
+
+        list.append_child(&element).unwrap();
+        if (focused.as_deref() == Some(&thread_id.to_string())) {
+            reply.focus().unwrap();
+        }
+    }
+
+    if (shown == 0) {
+        let empty = document.create_element("div").unwrap();
+        empty.class_list().toggle_with_force("editor_comments_empty", true).unwrap();
+        empty.set_text_content(Some(if (unresolved == 0) { "No open comments. Select some code and run \"Add Comment\" (Ctrl+Alt+M) to start one." } else { "No comments to show." }));
+        list.append_child(&empty).unwrap();
+    }
+}
+
+
+/// The path of a file, and the line of an offset if the file is open.
+fn location(file_id : u64, offset : usize) -> String {
+    let path = crate::state::file_path(file_id).unwrap_or_default();
+    match (crate::code::offset_to_line(file_id, offset)) {
+        Some(line) => format!("{}:{}", path, line),
+        None       => path
+    }
+}
+
+fn time_ago(ms : f64) -> String {
+    let minutes = (ms / 60000.0).max(0.0) as u64;
+    match (minutes) {
+        0         => "just now".to_string(),
+        1         => "1 minute ago".to_string(),
+        2..60     => format!("{} minutes ago", minutes),
+        60..120   => "1 hour ago".to_string(),
+        120..2880 => format!("{} hours ago", minutes / 60),
+        _         => format!("{} days ago", minutes / 1440)
+    }
+}
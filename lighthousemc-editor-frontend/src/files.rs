@@ -0,0 +1,65 @@
+use wasm_bindgen::prelude::*;
+use web_sys::{ File, RequestInit, Headers, Response };
+use js_sys::{ ArrayBuffer, Uint8Array };
+
+
+/// The HTTP path of a file on this plot.
+pub fn file_url(file_id : u64) -> String {
+    format!("{}/file/{file_id}", crate::editor_path())
+}
+
+
+/// Opens a download prompt for a file.
+pub fn download(file_id : u64) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    let link     = document.create_element("a").unwrap();
+    link.set_attribute("href", &format!("{}?download", file_url(file_id))).unwrap();
+    link.set_attribute("download", "").unwrap();
+    document.body().unwrap().append_child(&link).unwrap();
+    link.dyn_ref::<web_sys::HtmlElement>().unwrap().click();
+    link.remove();
+}
+
+
+/// Replaces the contents of a file with a file from the user's computer.
+///
+/// The server broadcasts the new contents to everyone with the file open once it has been received.
+pub fn upload(file_id : u64, file : File) {
+    let window = web_sys::window().unwrap();
+    let init   = RequestInit::new();
+    init.set_method("PUT");
+    init.set_body(&file);
+    // Required by the server, as other sites can not send it from a visitor's browser.
+    let headers = Headers::new().unwrap();
+    headers.set("x-lighthousemc-editor", "1").unwrap();
+    init.set_headers(&headers);
+    let on_response = Closure::<dyn FnMut(_) -> ()>::new(move |response : JsValue| {
+        let response = response.dyn_into::<Response>().unwrap();
+        if (! response.ok()) {
+            crate::warn(&format!("Failed to upload file {}: {}", file_id, response.status()));
+        }
+    });
+    let _ = window.fetch_with_str_and_init(&file_url(file_id), &init).then(&on_response);
+    on_response.forget();
+}
+
+
+/// Fetches the raw contents of a file, calling `f` once it has been received.
+pub fn fetch<F : FnOnce(Vec<u8>) -> () + 'static>(file_id : u64, f : F) {
+    let window = web_sys::window().unwrap();
+    let mut f = Some(f);
+    let on_response = Closure::<dyn FnMut(_) -> ()>::new(move |response : JsValue| {
+        let response = response.dyn_into::<Response>().unwrap();
+        let Some(f) = f.take() else { return; };
+        let mut f = Some(f);
+        let on_data = Closure::<dyn FnMut(_) -> ()>::new(move |data : JsValue| {
+            let data = Uint8Array::new(&data.dyn_into::<ArrayBuffer>().unwrap()).to_vec();
+            if let Some(f) = f.take() { f(data); }
+        });
+        let _ = response.array_buffer().unwrap().then(&on_data);
+        on_data.forget();
+    });
+    let _ = window.fetch_with_str(&file_url(file_id)).then(&on_response);
+    on_response.forget();
+}
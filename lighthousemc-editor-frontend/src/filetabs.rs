@@ -47,6 +47,18 @@ pub fn open_file(file_id : u64, path : String) {
         name.set_inner_html(filename);
         div.append_child(&name).unwrap();
 
+        let download = document.create_element("div").unwrap();
+        download.class_list().toggle_with_force("editor_filetab_download", true).unwrap();
+        download.set_attribute("title", "Download").unwrap();
+        download.set_inner_html("⤓");
+        div.append_child(&download).unwrap();
+        let download_callback = Closure::<dyn FnMut(_) -> ()>::new(move |e : PointerEvent| {
+            crate::files::download(file_id);
+            e.stop_propagation();
+        });
+        download.add_event_listener_with_callback("click", download_callback.as_ref().unchecked_ref()).unwrap();
+        download_callback.forget();
+
         let close = document.create_element("div").unwrap();
         close.class_list().toggle_with_force("editor_filetab_close", true).unwrap();
         close.set_inner_html("×");
@@ -99,7 +111,7 @@ pub fn close(file_id : u64) {
             if let Some(FilesEntry { is_open, .. }) = crate::state::FILES.read_files().get(&file_id) {
                 match (is_open) {
                     Some(Some(FilesEntryContents::Text(_))) => { crate::code::open_monaco(file_id); },
-                    Some(Some(FilesEntryContents::NonText)) => { crate::code::open_nontext(); },
+                    Some(Some(FilesEntryContents::Binary { .. })) => { crate::code::open_binary(file_id); },
                     Some(None) => { crate::code::open_load(); },
                     None => { crate::code::open_noopen(); }
                 }
@@ -149,14 +161,16 @@ pub fn overwrite(file_id : u64, fsname : &str, contents : &FileContents) {
         let tab = children.get_with_index(i).unwrap();
         let other_file_id = tab.get_attribute("editor_filetab_file_id").unwrap().parse::<u64>().unwrap();
         if (file_id == other_file_id) {
+            // The file may already have an editor if it is being overwritten after an upload.
+            crate::code::destroy_monaco(file_id);
             if (tab.id() == "editor_filetab_selected") {
                 match (contents) {
-                    FileContents::NonText => { crate::code::open_nontext(); },
+                    FileContents::Binary { .. } => { crate::code::open_binary(file_id); },
                     FileContents::Text(text) => { crate::code::create_monaco(file_id, fsname, text, true) },
                 }
             } else {
                 match (contents) {
-                    FileContents::NonText => { },
+                    FileContents::Binary { .. } => { },
                     FileContents::Text(text) => { crate::code::create_monaco(file_id, fsname, text, false) },
                 }
             }
@@ -3,7 +3,7 @@ use std::cell::LazyCell;
 use std::sync::{ Mutex, MutexGuard, RwLock };
 use std::cmp::Ordering;
 use wasm_bindgen::prelude::*;
-use web_sys::{ DomTokenList, Element, MouseEvent };
+use web_sys::{ DomTokenList, Element, MouseEvent, DragEvent };
 
 
 static FILETREE : FileTreeRootContainer = FileTreeRootContainer::new();
@@ -82,6 +82,33 @@ pub fn init() {
     });
     document.add_event_listener_with_callback("mousemove", mousemove_callback.as_ref().unchecked_ref()).unwrap();
     mousemove_callback.forget();
+
+    // Files dropped outside of the file tree would otherwise be opened by the browser.
+    let prevent_drop_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : DragEvent| {
+        event.prevent_default();
+    });
+    document.add_event_listener_with_callback("dragover", prevent_drop_callback.as_ref().unchecked_ref()).unwrap();
+    document.add_event_listener_with_callback("drop", prevent_drop_callback.as_ref().unchecked_ref()).unwrap();
+    prevent_drop_callback.forget();
+}
+
+
+/// Collapses the file tree, or restores it to its previous width.
+pub fn toggle() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let left   = document.get_element_by_id("editor_left").unwrap();
+    let hsplit = document.get_element_by_id("editor_resize_hsplit").unwrap();
+    if (hsplit.class_list().contains("filetree_collapse")) {
+        let width = left.get_attribute("editor_left_restore_width").unwrap_or_default();
+        left.set_attribute("style", &width).unwrap();
+        hsplit.class_list().toggle_with_force("filetree_collapse", false).unwrap();
+    } else {
+        left.set_attribute("editor_left_restore_width", &left.get_attribute("style").unwrap_or_default()).unwrap();
+        left.set_attribute("style", "width: 0;").unwrap();
+        hsplit.class_list().toggle_with_force("filetree_collapse", true).unwrap();
+    }
 }
 
 
@@ -162,6 +189,35 @@ pub fn add(entry : FileTreeEntry<'static>) {
         div.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
         click_callback.forget();
 
+        // Upload by dropping a file onto this entry.
+        let div1 = div.clone();
+        let dragover_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : DragEvent| {
+            event.prevent_default();
+            div1.class_list().toggle_with_force("editor_filetree_dragover", true).unwrap();
+        });
+        div.add_event_listener_with_callback("dragover", dragover_callback.as_ref().unchecked_ref()).unwrap();
+        dragover_callback.forget();
+
+        let div1 = div.clone();
+        let dragleave_callback = Closure::<dyn FnMut(_) -> ()>::new(move |_ : DragEvent| {
+            div1.class_list().toggle_with_force("editor_filetree_dragover", false).unwrap();
+        });
+        div.add_event_listener_with_callback("dragleave", dragleave_callback.as_ref().unchecked_ref()).unwrap();
+        dragleave_callback.forget();
+
+        let div1    = div.clone();
+        let file_id = entry.entry_id;
+        let drop_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : DragEvent| {
+            event.prevent_default();
+            event.stop_propagation();
+            div1.class_list().toggle_with_force("editor_filetree_dragover", false).unwrap();
+            if let Some(file) = event.data_transfer().and_then(|data| data.files()).and_then(|files| files.get(0)) {
+                crate::files::upload(file_id, file);
+            }
+        });
+        div.add_event_listener_with_callback("drop", drop_callback.as_ref().unchecked_ref()).unwrap();
+        drop_callback.forget();
+
     }
 
     // Add to parent
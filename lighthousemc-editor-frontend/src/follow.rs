@@ -0,0 +1,94 @@
+use lighthousemc_editor_common::packet::s2c::ViewportS2CPacket;
+use lighthousemc_editor_common::packet::c2s::Viewport;
+use lighthousemc_editor_common::Uuid;
+use std::cell::LazyCell;
+use std::sync::{ RwLock, Mutex };
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+
+/// The collaborator being followed.
+static FOLLOWING : Mutex<Option<Uuid>> = Mutex::new(None);
+
+static VIEWPORTS : ViewportsContainer = ViewportsContainer::new();
+struct ViewportsContainer {
+    viewports : LazyCell<RwLock<HashMap<Uuid, Viewport>>>
+}
+impl ViewportsContainer { const fn new() -> Self { Self {
+    viewports : LazyCell::new(|| RwLock::new(HashMap::new()))
+} } }
+unsafe impl Sync for ViewportsContainer { }
+
+
+pub fn init() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let stop_callback = Closure::<dyn FnMut() -> ()>::new(move || { stop(); });
+    document.get_element_by_id("editor_follow_banner_stop").unwrap().add_event_listener_with_callback("click", stop_callback.as_ref().unchecked_ref()).unwrap();
+    stop_callback.forget();
+}
+
+
+pub fn on_viewport(viewport : ViewportS2CPacket) {
+    {
+        let mut viewports = VIEWPORTS.viewports.write().unwrap();
+        match (viewport.viewport) {
+            Some(inner) => { viewports.insert(viewport.client_uuid, inner); },
+            None        => { viewports.remove(&viewport.client_uuid); }
+        }
+    }
+    if (following() == Some(viewport.client_uuid)) {
+        mirror(viewport.client_uuid);
+    }
+}
+
+pub fn on_leave(client_uuid : Uuid) {
+    VIEWPORTS.viewports.write().unwrap().remove(&client_uuid);
+    if (following() == Some(client_uuid)) {
+        stop();
+    }
+}
+
+
+pub fn following() -> Option<Uuid> {
+    *FOLLOWING.lock().unwrap()
+}
+
+/// Starts mirroring the file and viewport of a collaborator.
+pub fn follow(client_uuid : Uuid, client_name : &str) {
+    *FOLLOWING.lock().unwrap() = Some(client_uuid);
+    update_banner(Some(client_name));
+    crate::presence::update();
+    mirror(client_uuid);
+}
+
+/// Stops following, if following anyone.
+pub fn stop() {
+    if (FOLLOWING.lock().unwrap().take().is_some()) {
+        update_banner(None);
+        crate::presence::update();
+    }
+}
+
+
+/// Opens the file that a collaborator is looking at, and scrolls to their viewport.
+fn mirror(client_uuid : Uuid) {
+    let Some(viewport) = VIEWPORTS.viewports.read().unwrap().get(&client_uuid).copied() else { return; };
+    let Some(path) = crate::state::file_path(viewport.file_id) else { return; };
+    if (crate::filetabs::currently_focused().map(|(file_id, _)| file_id) != Some(viewport.file_id)) {
+        crate::state::open_file(viewport.file_id, path, true);
+    }
+    crate::code::scroll_to(viewport.file_id, viewport.first_line);
+}
+
+fn update_banner(client_name : Option<&str>) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let banner = document.get_element_by_id("editor_follow_banner").unwrap();
+    banner.class_list().toggle_with_force("editor_follow_banner_hidden", client_name.is_none()).unwrap();
+    if let Some(client_name) = client_name {
+        document.get_element_by_id("editor_follow_banner_name").unwrap().set_text_content(Some(client_name));
+    }
+}
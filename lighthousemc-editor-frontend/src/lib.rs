@@ -12,9 +12,19 @@ mod iter;
 // Connection
 mod ws;
 mod state;
+mod files;
 
 // UX
 mod cover;
+mod sidebar;
+mod search;
+mod activity;
+mod presence;
+mod follow;
+mod chat;
+mod comments;
+mod suggestions;
+mod palette;
 mod filetree;
 mod filetabs;
 mod code;
@@ -29,12 +39,30 @@ pub fn start() {
     panic::set_hook(Box::new(|info| {
         error(&format!("{}\n{}", info, Error::new().stack()));
     }));
+    sidebar::init();
     filetree::init();
+    search::init();
+    activity::init();
+    presence::init();
+    follow::init();
+    chat::init();
+    comments::init();
+    suggestions::init();
+    palette::init();
     code::init();
     ws::start();
 }
 
 
+/// The HTTP path of the editor page, without a trailing slash.
+/// The server fills it in, as the editor may be served under a prefix behind a reverse proxy.
+pub fn editor_path() -> String {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    document.document_element().and_then(|html| html.get_attribute("data-editor-path")).unwrap_or_else(|| "/editor".to_string())
+}
+
+
 #[wasm_bindgen]
 extern "C" {
 
@@ -0,0 +1,310 @@
+use lighthousemc_editor_common::packet::c2s::{ RequestActionC2SPacket, EditorAction };
+use std::sync::Mutex;
+use wasm_bindgen::prelude::*;
+use web_sys::{ Element, HtmlInputElement, KeyboardEvent, MouseEvent };
+
+
+/// The most results shown at once.
+const MAX_RESULTS : usize = 50;
+
+
+static PALETTE : Mutex<Option<PaletteState>> = Mutex::new(None);
+struct PaletteState {
+    mode     : PaletteMode,
+    items    : Vec<PaletteItem>,
+    selected : usize
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PaletteMode {
+    Files,
+    Commands
+}
+
+#[derive(Clone)]
+enum PaletteItem {
+    File {
+        file_id : u64,
+        path    : String
+    },
+    Command(Command)
+}
+
+
+#[derive(Clone, Copy)]
+struct Command {
+    name     : &'static str,
+    shortcut : &'static str,
+    run      : fn() -> ()
+}
+const COMMANDS : &[Command] = &[
+    Command { name : "Go to File",              shortcut : "Ctrl+P",       run : || open(PaletteMode::Files) },
+    Command { name : "Search in Files",         shortcut : "Ctrl+Shift+F", run : || crate::search::focus() },
+    Command { name : "Close Tab",               shortcut : "Ctrl+W",       run : close_tab },
+    Command { name : "Close All Tabs",          shortcut : "Ctrl+Shift+W", run : close_all_tabs },
+    Command { name : "Reopen Closed Tab",       shortcut : "Ctrl+Shift+T", run : || crate::state::reopen_history() },
+    Command { name : "Toggle File Tree",        shortcut : "Ctrl+B",       run : || crate::filetree::toggle() },
+    Command { name : "Toggle Blame",            shortcut : "Ctrl+Alt+B",   run : || crate::code::blame::toggle() },
+    Command { name : "Add Comment",             shortcut : "Ctrl+Alt+M",   run : || crate::comments::start_new() },
+    Command { name : "Toggle Suggestion Mode",  shortcut : "",             run : || crate::suggestions::toggle() },
+    Command { name : "Open Chat",               shortcut : "",             run : || crate::chat::focus() },
+    Command { name : "Share Location in Chat",  shortcut : "",             run : || crate::chat::share_location() },
+    Command { name : "Save",                    shortcut : "Ctrl+S",       run : save },
+    Command { name : "Build",                   shortcut : "",             run : build }
+];
+
+
+pub fn init() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let input = document.get_element_by_id("palette_input").unwrap();
+    let input_callback = Closure::<dyn FnMut() -> ()>::new(move || { refresh(); });
+    input.add_event_listener_with_callback("input", input_callback.as_ref().unchecked_ref()).unwrap();
+    input_callback.forget();
+
+    let keydown_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : KeyboardEvent| {
+        match (event.key().as_str()) {
+            "ArrowDown" => { event.prevent_default(); move_selection(1); },
+            "ArrowUp"   => { event.prevent_default(); move_selection(-1); },
+            "Enter"     => { event.prevent_default(); accept(None); },
+            "Escape"    => { event.prevent_default(); close(); },
+            _           => { }
+        }
+    });
+    input.add_event_listener_with_callback("keydown", keydown_callback.as_ref().unchecked_ref()).unwrap();
+    keydown_callback.forget();
+
+    let blur_callback = Closure::<dyn FnMut() -> ()>::new(move || { close(); });
+    input.add_event_listener_with_callback("blur", blur_callback.as_ref().unchecked_ref()).unwrap();
+    blur_callback.forget();
+}
+
+
+pub fn open(mode : PaletteMode) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    *PALETTE.lock().unwrap() = Some(PaletteState { mode, items : Vec::new(), selected : 0 });
+    document.get_element_by_id("palette").unwrap().class_list().toggle_with_force("palette_open", true).unwrap();
+    let input = input();
+    input.set_value("");
+    input.set_placeholder(match (mode) {
+        PaletteMode::Files    => "Go to file",
+        PaletteMode::Commands => "Run a command"
+    });
+    input.focus().unwrap();
+    refresh();
+}
+
+pub fn close() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    if (PALETTE.lock().unwrap().take().is_some()) {
+        document.get_element_by_id("palette").unwrap().class_list().toggle_with_force("palette_open", false).unwrap();
+    }
+}
+
+
+fn input() -> HtmlInputElement {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    document.get_element_by_id("palette_input").unwrap().dyn_into::<HtmlInputElement>().unwrap()
+}
+
+
+fn refresh() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let mut palette = PALETTE.lock().unwrap();
+    let Some(palette) = &mut *palette else { return; };
+    let query = input().value();
+    // Typing `>` in the file finder switches to commands.
+    let (mode, query) = match (query.strip_prefix('>')) {
+        Some(query) => (PaletteMode::Commands, query.trim_start().to_string()),
+        None        => (palette.mode, query)
+    };
+
+    let mut ranked = Vec::new();
+    match (mode) {
+        PaletteMode::Files => {
+            let recent   = crate::state::recent_files();
+            let file_ids = crate::state::FILES.read_files().keys().copied().collect::<Vec<_>>();
+            for file_id in file_ids {
+                let Some(path) = crate::state::file_path(file_id) else { continue; };
+                let Some((score, indices)) = fuzzy_match(&query, &path) else { continue; };
+                // Recently opened files rank higher, most recent first.
+                let recency = recent.iter().position(|recent_file_id| *recent_file_id == file_id).map_or(0, |i| 50 - (i as i32).min(50));
+                ranked.push((score + recency, indices, PaletteItem::File { file_id, path }));
+            }
+        },
+        PaletteMode::Commands => {
+            for command in COMMANDS {
+                let Some((score, indices)) = fuzzy_match(&query, command.name) else { continue; };
+                ranked.push((score, indices, PaletteItem::Command(*command)));
+            }
+        }
+    }
+    ranked.sort_by(|(a_score, _, a), (b_score, _, b)| b_score.cmp(a_score).then_with(|| a.label().cmp(b.label())));
+    ranked.truncate(MAX_RESULTS);
+
+    let list = document.get_element_by_id("palette_results").unwrap();
+    list.set_inner_html("");
+    for (i, (_, indices, item)) in ranked.iter().enumerate() {
+        let row = document.create_element("div").unwrap();
+        row.class_list().toggle_with_force("palette_result", true).unwrap();
+        row.class_list().toggle_with_force("hbox", true).unwrap();
+        row.class_list().toggle_with_force("palette_result_selected", i == 0).unwrap();
+        match (item) {
+            PaletteItem::File { path, .. } => {
+                let icon = document.create_element("i").unwrap();
+                crate::filetree::set_filename_icon_classes(path, &icon.class_list());
+                row.append_child(&icon).unwrap();
+                let label = document.create_element("div").unwrap();
+                label.class_list().toggle_with_force("palette_result_label", true).unwrap();
+                append_highlighted(&label, path, indices);
+                row.append_child(&label).unwrap();
+            },
+            PaletteItem::Command(command) => {
+                let label = document.create_element("div").unwrap();
+                label.class_list().toggle_with_force("palette_result_label", true).unwrap();
+                append_highlighted(&label, command.name, indices);
+                row.append_child(&label).unwrap();
+                let shortcut = document.create_element("div").unwrap();
+                shortcut.class_list().toggle_with_force("palette_result_shortcut", true).unwrap();
+                shortcut.set_text_content(Some(command.shortcut));
+                row.append_child(&shortcut).unwrap();
+            }
+        }
+        // `mousedown` rather than `click`, as the input loses focus and closes the palette first otherwise.
+        let mousedown_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : MouseEvent| {
+            event.prevent_default();
+            accept(Some(i));
+        });
+        row.add_event_listener_with_callback("mousedown", mousedown_callback.as_ref().unchecked_ref()).unwrap();
+        mousedown_callback.forget();
+        list.append_child(&row).unwrap();
+    }
+
+    palette.items    = ranked.into_iter().map(|(_, _, item)| item).collect();
+    palette.selected = 0;
+}
+
+fn append_highlighted(parent : &Element, text : &str, indices : &[usize]) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    for (i, ch) in text.chars().enumerate() {
+        let span = document.create_element(if (indices.contains(&i)) { "b" } else { "span" }).unwrap();
+        span.set_text_content(Some(&ch.to_string()));
+        parent.append_child(&span).unwrap();
+    }
+}
+
+
+fn move_selection(delta : isize) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let mut palette = PALETTE.lock().unwrap();
+    let Some(palette) = &mut *palette else { return; };
+    if (palette.items.is_empty()) { return; }
+    palette.selected = (palette.selected as isize + delta).rem_euclid(palette.items.len() as isize) as usize;
+    let rows = document.get_element_by_id("palette_results").unwrap().children();
+    for i in 0..rows.length() {
+        let row = rows.get_with_index(i).unwrap();
+        let selected = i as usize == palette.selected;
+        row.class_list().toggle_with_force("palette_result_selected", selected).unwrap();
+        if (selected) { row.scroll_into_view_with_bool(false); }
+    }
+}
+
+
+fn accept(index : Option<usize>) {
+    let item = {
+        let palette = PALETTE.lock().unwrap();
+        let Some(palette) = &*palette else { return; };
+        palette.items.get(index.unwrap_or(palette.selected)).cloned()
+    };
+    close();
+    match (item) {
+        Some(PaletteItem::File { file_id, path }) => { crate::state::open_file(file_id, path, true); },
+        Some(PaletteItem::Command(command))      => { (command.run)(); },
+        None => { }
+    }
+}
+
+
+impl PaletteItem {
+    fn label(&self) -> &str {
+        match (self) {
+            Self::File { path, .. } => path,
+            Self::Command(command)  => command.name
+        }
+    }
+}
+
+
+/// Matches `query` as a case-insensitive subsequence of `text`.
+///
+/// Returns a score, where higher is better, and the char indices of `text` that matched.
+/// Consecutive matches, matches at the start of a word and matches in the file name score higher.
+fn fuzzy_match(query : &str, text : &str) -> Option<(i32, Vec<usize>)> {
+    let query = query.chars().filter(|ch| ! ch.is_whitespace()).flat_map(|ch| ch.to_lowercase()).collect::<Vec<_>>();
+    let chars = text.chars().collect::<Vec<_>>();
+    let name_start = chars.iter().rposition(|ch| *ch == '/').map_or(0, |i| i + 1);
+
+    let mut score   = 0;
+    let mut indices = Vec::with_capacity(query.len());
+    let mut next    = 0;
+    for query_ch in query {
+        let found = (next..chars.len()).find(|&i| chars[i].to_lowercase().eq(query_ch.to_lowercase()))?;
+        let previous = if (found > 0) { Some(chars[found - 1]) } else { None };
+        score += 1;
+        if (indices.last().is_some_and(|last| last + 1 == found)) { score += 5; }
+        if (matches!(previous, None | Some('/' | '_' | '-' | '.' | ' '))
+            || (previous.is_some_and(|previous| previous.is_lowercase()) && chars[found].is_uppercase())
+        ) { score += 8; }
+        if (found >= name_start) { score += 2; }
+        // Gaps count against the match.
+        score -= ((found - next) as i32).min(5);
+        indices.push(found);
+        next = found + 1;
+    }
+    // Shorter texts are more likely to be what was meant.
+    score -= (chars.len() as i32) / 16;
+    Some((score, indices))
+}
+
+
+/// Runs the command with the given name, as if it was picked from the palette.
+pub fn run_command(name : &str) {
+    if let Some(command) = COMMANDS.iter().find(|command| command.name == name) {
+        (command.run)();
+    }
+}
+
+
+fn close_tab() {
+    if let Some((file_id, file_path)) = crate::filetabs::currently_focused() {
+        crate::state::close_file(file_id, Some(file_path));
+    }
+}
+
+fn close_all_tabs() {
+    for (file_id, file_path) in crate::filetabs::list_all() {
+        crate::state::close_file(file_id, Some(file_path));
+    }
+}
+
+fn save() {
+    // Make sure the server has every change before the host saves.
+    crate::code::diffsync::send_patches_to_server();
+    crate::ws::WS.send(RequestActionC2SPacket { action : EditorAction::Save });
+}
+
+fn build() {
+    crate::code::diffsync::send_patches_to_server();
+    crate::ws::WS.send(RequestActionC2SPacket { action : EditorAction::Build });
+}
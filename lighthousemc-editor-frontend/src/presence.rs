@@ -0,0 +1,153 @@
+use lighthousemc_editor_common::packet::s2c::{ PresenceS2CPacket, PresenceLeaveS2CPacket };
+use lighthousemc_editor_common::Uuid;
+use std::cell::LazyCell;
+use std::sync::RwLock;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+use web_sys::MouseEvent;
+use js_sys::Date;
+
+
+static COLLABORATORS : CollaboratorsContainer = CollaboratorsContainer::new();
+struct CollaboratorsContainer {
+    collaborators : LazyCell<RwLock<HashMap<Uuid, Collaborator>>>
+}
+struct Collaborator {
+    client_name  : String,
+    colour       : u8,
+    connected_at : u64,
+    file_id      : Option<u64>,
+    idle         : bool
+}
+impl CollaboratorsContainer { const fn new() -> Self { Self {
+    collaborators : LazyCell::new(|| RwLock::new(HashMap::new()))
+} } }
+unsafe impl Sync for CollaboratorsContainer { }
+
+
+pub fn init() {
+    // Keep the connection times up to date.
+    let interval_callback = Closure::<dyn FnMut() -> ()>::new(move || { update(); });
+    crate::set_interval(interval_callback.as_ref().unchecked_ref(), 30000);
+    interval_callback.forget();
+}
+
+
+pub fn on_presence(presence : PresenceS2CPacket<'static>) {
+    COLLABORATORS.collaborators.write().unwrap().insert(presence.client_uuid, Collaborator {
+        client_name  : presence.client_name.to_string(),
+        colour       : presence.colour,
+        connected_at : presence.connected_at,
+        file_id      : presence.file_id,
+        idle         : presence.idle
+    });
+    update();
+}
+
+pub fn on_leave(leave : PresenceLeaveS2CPacket) {
+    COLLABORATORS.collaborators.write().unwrap().remove(&leave.client_uuid);
+    crate::follow::on_leave(leave.client_uuid);
+    update();
+}
+
+
+/// Redraws the presence list.
+pub fn update() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let list = document.get_element_by_id("editor_presence_list").unwrap();
+    list.set_inner_html("");
+
+    let     collaborators = COLLABORATORS.collaborators.read().unwrap();
+    let mut sorted        = collaborators.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|(client_uuid, collaborator)| (collaborator.connected_at, **client_uuid));
+
+    document.get_element_by_id("editor_presence_count").unwrap().set_text_content(Some(&sorted.len().to_string()));
+    if (sorted.is_empty()) {
+        let empty = document.create_element("div").unwrap();
+        empty.class_list().toggle_with_force("editor_presence_empty", true).unwrap();
+        empty.set_text_content(Some("Nobody else is here."));
+        list.append_child(&empty).unwrap();
+        return;
+    }
+
+    let now       = Date::now() as u64;
+    let following = crate::follow::following();
+    for (&client_uuid, collaborator) in sorted {
+        let row = document.create_element("div").unwrap();
+        row.class_list().toggle_with_force("editor_presence_entry", true).unwrap();
+        row.class_list().toggle_with_force("hbox", true).unwrap();
+        row.class_list().toggle_with_force("editor_presence_idle", collaborator.idle).unwrap();
+
+        let marker = document.create_element("div").unwrap();
+        marker.class_list().toggle_with_force("editor_presence_marker", true).unwrap();
+        marker.class_list().toggle_with_force(&format!("editor_code_remote_selection_{}_single", collaborator.colour), true).unwrap();
+        row.append_child(&marker).unwrap();
+
+        let text = document.create_element("div").unwrap();
+        text.class_list().toggle_with_force("vbox", true).unwrap();
+        let name = document.create_element("div").unwrap();
+        name.class_list().toggle_with_force("editor_presence_name", true).unwrap();
+        name.set_text_content(Some(&collaborator.client_name));
+        text.append_child(&name).unwrap();
+        let file = document.create_element("div").unwrap();
+        file.class_list().toggle_with_force("editor_presence_file", true).unwrap();
+        file.set_text_content(Some(&collaborator.file_id.and_then(crate::state::file_path).unwrap_or_else(|| "No file open".to_string())));
+        text.append_child(&file).unwrap();
+        let status = document.create_element("div").unwrap();
+        status.class_list().toggle_with_force("editor_presence_status", true).unwrap();
+        status.set_text_content(Some(&format!("{} · joined {}", if (collaborator.idle) { "Idle" } else { "Active" }, time_ago(now.saturating_sub(collaborator.connected_at)))));
+        text.append_child(&status).unwrap();
+        row.append_child(&text).unwrap();
+
+        if (collaborator.file_id.is_some()) {
+            row.set_attribute("title", &format!("Jump to {}", collaborator.client_name)).unwrap();
+            let click_callback = Closure::<dyn FnMut() -> ()>::new(move || { jump_to(client_uuid); });
+            row.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+            click_callback.forget();
+        }
+
+        let is_following = following == Some(client_uuid);
+        let follow = document.create_element("div").unwrap();
+        follow.class_list().toggle_with_force("editor_presence_follow", true).unwrap();
+        follow.class_list().toggle_with_force("editor_presence_following", is_following).unwrap();
+        follow.set_text_content(Some(if (is_following) { "Unfollow" } else { "Follow" }));
+        let client_name = collaborator.client_name.clone();
+        let follow_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : MouseEvent| {
+            event.stop_propagation();
+            if (is_following) { crate::follow::stop(); }
+            else { crate::follow::follow(client_uuid, &client_name); }
+        });
+        follow.add_event_listener_with_callback("click", follow_callback.as_ref().unchecked_ref()).unwrap();
+        follow_callback.forget();
+        row.append_child(&follow).unwrap();
+
+        list.append_child(&row).unwrap();
+    }
+}
+
+fn time_ago(ms : u64) -> String {
+    let minutes = ms / 60000;
+    match (minutes) {
+        0       => "just now".to_string(),
+        1       => "1 minute ago".to_string(),
+        2..60   => format!("{} minutes ago", minutes),
+        60..120 => "1 hour ago".to_string(),
+        _       => format!("{} hours ago", minutes / 60)
+    }
+}
+
+
+/// Opens the file that a collaborator is looking at, and selects their cursor if known.
+pub fn jump_to(client_uuid : Uuid) {
+    let Some(file_id) = COLLABORATORS.collaborators.read().unwrap().get(&client_uuid).and_then(|collaborator| collaborator.file_id) else { return; };
+    let Some(path) = crate::state::file_path(file_id) else { return; };
+    crate::state::open_file(file_id, path, true);
+    let selection = crate::code::remote_cursors::REMOTE_SELECTIONS.read().get(&client_uuid)
+        .filter(|remote_selection| remote_selection.file_id == file_id)
+        .and_then(|remote_selection| remote_selection.selections.first().map(|selection| (selection.start, selection.end)));
+    if let Some((start, end)) = selection {
+        crate::code::reveal_offsets(file_id, start, end);
+    }
+}
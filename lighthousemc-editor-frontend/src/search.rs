@@ -0,0 +1,181 @@
+use lighthousemc_editor_common::packet::s2c::{ SearchResultsS2CPacket, SearchDoneS2CPacket };
+use lighthousemc_editor_common::packet::c2s::{ SearchC2SPacket, SearchOptions };
+use std::sync::atomic::{ AtomicU32, Ordering };
+use wasm_bindgen::prelude::*;
+use web_sys::{ Element, HtmlInputElement, KeyboardEvent };
+
+
+static SEARCH_ID : AtomicU32 = AtomicU32::new(0);
+
+
+pub fn init() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    for id in ["editor_search_query", "editor_search_globs"] {
+        let input = document.get_element_by_id(id).unwrap();
+        let keydown_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : KeyboardEvent| {
+            if (event.key() == "Enter") { run(false); }
+        });
+        input.add_event_listener_with_callback("keydown", keydown_callback.as_ref().unchecked_ref()).unwrap();
+        keydown_callback.forget();
+    }
+
+    for id in ["editor_search_case", "editor_search_word", "editor_search_regex"] {
+        let toggle  = document.get_element_by_id(id).unwrap();
+        let toggle1 = toggle.clone();
+        let click_callback = Closure::<dyn FnMut() -> ()>::new(move || {
+            toggle1.class_list().toggle("editor_search_toggle_on").unwrap();
+            run(false);
+        });
+        toggle.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+        click_callback.forget();
+    }
+
+    let replace_all = document.get_element_by_id("editor_search_replace_all").unwrap();
+    let click_callback = Closure::<dyn FnMut() -> ()>::new(move || {
+        let window = web_sys::window().unwrap();
+        if (window.confirm_with_message("Replace every match in all files? This affects everyone editing this server.").unwrap_or(false)) {
+            run(true);
+        }
+    });
+    replace_all.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+    click_callback.forget();
+}
+
+
+/// Opens the search panel and focuses the query input.
+pub fn focus() {
+    crate::sidebar::open("search");
+    let query = input("editor_search_query");
+    query.focus().unwrap();
+    query.select();
+}
+
+
+fn input(id : &str) -> HtmlInputElement {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    document.get_element_by_id(id).unwrap().dyn_into::<HtmlInputElement>().unwrap()
+}
+
+fn toggled(id : &str) -> bool {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    document.get_element_by_id(id).unwrap().class_list().contains("editor_search_toggle_on")
+}
+
+
+fn run(replace : bool) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let query = input("editor_search_query").value();
+    document.get_element_by_id("editor_search_results").unwrap().set_inner_html("");
+    if (query.is_empty()) {
+        set_status("");
+        return;
+    }
+    let search_id = SEARCH_ID.fetch_add(1, Ordering::SeqCst).wrapping_add(1);
+    set_status(if (replace) { "Replacing…" } else { "Searching…" });
+    crate::ws::WS.send(SearchC2SPacket {
+        search_id,
+        query,
+        options   : SearchOptions {
+            regex          : toggled("editor_search_regex"),
+            case_sensitive : toggled("editor_search_case"),
+            whole_word     : toggled("editor_search_word")
+        },
+        globs     : input("editor_search_globs").value(),
+        replace   : replace.then(|| input("editor_search_replace").value())
+    });
+}
+
+fn set_status(status : &str) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    document.get_element_by_id("editor_search_status").unwrap().set_inner_html(status);
+}
+
+
+pub fn on_results(results : SearchResultsS2CPacket<'static>) {
+    if (results.search_id != SEARCH_ID.load(Ordering::SeqCst)) { return; }
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    let Some(path) = crate::state::file_path(results.file_id) else { return; };
+    let file_id = results.file_id;
+
+    let group = document.create_element("div").unwrap();
+    group.class_list().toggle_with_force("editor_search_group", true).unwrap();
+
+    let header = document.create_element("div").unwrap();
+    header.class_list().toggle_with_force("editor_search_group_header", true).unwrap();
+    header.class_list().toggle_with_force("hbox", true).unwrap();
+    let icon = document.create_element("i").unwrap();
+    crate::filetree::set_filename_icon_classes(&path, &icon.class_list());
+    header.append_child(&icon).unwrap();
+    let name = document.create_element("div").unwrap();
+    name.set_text_content(Some(&path));
+    header.append_child(&name).unwrap();
+    let count = document.create_element("div").unwrap();
+    count.class_list().toggle_with_force("editor_search_group_count", true).unwrap();
+    count.set_text_content(Some(&results.matches.len().to_string()));
+    header.append_child(&count).unwrap();
+    group.append_child(&header).unwrap();
+
+    for search_match in &*results.matches {
+        let row = document.create_element("div").unwrap();
+        row.class_list().toggle_with_force("editor_search_match", true).unwrap();
+        let preview = &search_match.preview;
+        let start   = (search_match.preview_start as usize).min(preview.len());
+        let end     = (search_match.preview_end   as usize).clamp(start, preview.len());
+        append_text(&row, "span", &preview[..start]);
+        append_text(&row, "mark", &preview[start..end]);
+        append_text(&row, "span", &preview[end..]);
+
+        let path1 = path.clone();
+        let (line, column_start, line_end, column_end) = (search_match.line, search_match.column_start, search_match.line_end, search_match.column_end);
+        let click_callback = Closure::<dyn FnMut() -> ()>::new(move || {
+            crate::state::open_file(file_id, path1.clone(), true);
+            crate::code::reveal(file_id, line, column_start, line_end, column_end);
+        });
+        row.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+        click_callback.forget();
+        group.append_child(&row).unwrap();
+    }
+
+    document.get_element_by_id("editor_search_results").unwrap().append_child(&group).unwrap();
+}
+
+fn append_text(parent : &Element, tag : &str, text : &str) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    let element  = document.create_element(tag).unwrap();
+    element.set_text_content(Some(text));
+    parent.append_child(&element).unwrap();
+}
+
+
+pub fn on_done(done : SearchDoneS2CPacket<'static>) {
+    if (done.search_id != SEARCH_ID.load(Ordering::SeqCst)) { return; }
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+    if let Some(error) = done.error {
+        set_status(&format!("<span class=\"editor_search_error\">{}</span>", html_escape(&error)));
+        return;
+    }
+    let files = document.get_elements_by_class_name("editor_search_group").length();
+    set_status(&match ((done.replaced, done.total_matches)) {
+        (_, 0)         => "No results.".to_string(),
+        (true, count)  => format!("Replaced {} occurrence{} in {} file{}.", count, plural(count), files, plural(files)),
+        (false, count) => format!("{} result{} in {} file{}.", count, plural(count), files, plural(files))
+    });
+}
+
+fn plural(count : u32) -> &'static str {
+    if (count == 1) { "" } else { "s" }
+}
+
+fn html_escape(s : &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
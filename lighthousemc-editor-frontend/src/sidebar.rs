@@ -0,0 +1,38 @@
+use wasm_bindgen::prelude::*;
+
+
+pub fn init() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let tabs = document.get_elements_by_class_name("editor_sidebar_tab");
+    for i in 0..tabs.length() {
+        let tab   = tabs.get_with_index(i).unwrap();
+        let panel = tab.get_attribute("editor_sidebar_panel").unwrap();
+        let click_callback = Closure::<dyn FnMut() -> ()>::new(move || { open(&panel); });
+        tab.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+        click_callback.forget();
+    }
+}
+
+
+/// Shows the sidebar panel with the given name, hiding the others.
+pub fn open(panel : &str) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    for class in ["editor_sidebar_tab", "editor_sidebar_panel"] {
+        let elements = document.get_elements_by_class_name(class);
+        for i in 0..elements.length() {
+            let element  = elements.get_with_index(i).unwrap();
+            let selected = element.get_attribute("editor_sidebar_panel").as_deref() == Some(panel);
+            element.class_list().toggle_with_force("editor_sidebar_selected", selected).unwrap();
+        }
+    }
+
+    match (panel) {
+        "activity" => { crate::activity::refresh(); },
+        "chat"     => { crate::chat::mark_read(); },
+        _          => { }
+    }
+}
@@ -33,19 +33,27 @@ unsafe impl Sync for FilesContainer { }
 
 static FILE_HISTORY : Mutex<VecDeque<(u64, String)>> = Mutex::new(VecDeque::new());
 
+/// Recently opened files, most recent first.
+static RECENT_FILES     : Mutex<VecDeque<u64>> = Mutex::new(VecDeque::new());
+const  RECENT_FILES_LEN : usize                = 50;
+
 
 #[derive(Debug)]
 pub struct FilesEntry {
-    pub file_id : u64,
-    pub fsname  : String,
-    pub is_open : Option<Option<FilesEntryContents>>
+    pub file_id    : u64,
+    pub fsname     : String,
+    pub parent_dir : Option<u64>,
+    pub is_open    : Option<Option<FilesEntryContents>>
     //            |      |      ^- File data
     //            |      ^- None if opened but no data from server yet
     //            ^- None if file not opened
 }
 #[derive(Debug)]
 pub enum FilesEntryContents {
-    NonText,
+    Binary {
+        size : u64,
+        mime : String
+    },
     Text(String)
 }
 
@@ -58,10 +66,14 @@ pub struct DirectoriesEntry {
 
 pub fn add_tree_entry(entry : FileTreeEntry<'static>) {
     if (! entry.is_dir) {
-        FILES.write_files().insert(entry.entry_id, FilesEntry {
-            file_id : entry.entry_id,
-            fsname  : entry.fsname.to_string(),
-            is_open : None
+        let mut files = FILES.write_files();
+        // The tree is sent again when it changes, which must not close open files.
+        let is_open = files.remove(&entry.entry_id).and_then(|file| file.is_open);
+        files.insert(entry.entry_id, FilesEntry {
+            file_id    : entry.entry_id,
+            fsname     : entry.fsname.to_string(),
+            parent_dir : entry.parent_dir,
+            is_open
         });
     } else {
         FILES.write_directories().insert(entry.entry_id, DirectoriesEntry {
@@ -72,16 +84,44 @@ pub fn add_tree_entry(entry : FileTreeEntry<'static>) {
     crate::filetree::add(entry);
 }
 
+/// The full path of a file, with directories separated by `/`.
+pub fn file_path(file_id : u64) -> Option<String> {
+    let     files       = FILES.read_files();
+    let     directories = FILES.read_directories();
+    let     file        = files.get(&file_id)?;
+    let mut parts       = vec![ file.fsname.as_str() ];
+    let mut parent      = file.parent_dir;
+    while let Some(directory_id) = parent {
+        let directory = directories.get(&directory_id)?;
+        parts.push(&directory.fsname);
+        parent = directory.parent_dir;
+    }
+    parts.reverse();
+    Some(parts.join("/"))
+}
+
+/// Finds a file by its full path, with directories separated by `/`.
+pub fn find_file(path : &str) -> Option<u64> {
+    let file_ids = FILES.read_files().keys().copied().collect::<Vec<_>>();
+    file_ids.into_iter().find(|file_id| file_path(*file_id).as_deref() == Some(path))
+}
+
 pub fn open_file(file_id : u64, path : String, remove_history : bool) -> bool {
     if (remove_history) {
         FILE_HISTORY.lock().unwrap().retain(|(file, _)| *file != file_id);
     }
     let mut files = FILES.write_files();
     let Some(FilesEntry { is_open, .. }) = files.get_mut(&file_id) else { return false; };
+    {
+        let mut recent = RECENT_FILES.lock().unwrap();
+        recent.retain(|recent_file_id| *recent_file_id != file_id);
+        recent.push_front(file_id);
+        recent.truncate(RECENT_FILES_LEN);
+    }
     crate::code::remote_cursors::update();
     let mut should_proper_update_cursor = true;
     match (is_open) {
-        Some(Some(FilesEntryContents::NonText)) => { crate::code::open_nontext(); },
+        Some(Some(FilesEntryContents::Binary { .. })) => { drop(files); crate::code::open_binary(file_id); },
         Some(Some(FilesEntryContents::Text(_))) => { crate::code::open_monaco(file_id); },
         Some(None) => { crate::code::open_load(); },
         None => {
@@ -124,6 +164,11 @@ pub fn close_file(file_id : u64, history_path : Option<String>) {
 }
 
 
+pub fn recent_files() -> Vec<u64> {
+    RECENT_FILES.lock().unwrap().iter().copied().collect()
+}
+
+
 pub fn reopen_history() {
     let mut history = FILE_HISTORY.lock().unwrap();
     loop {
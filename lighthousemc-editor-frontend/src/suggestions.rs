@@ -0,0 +1,149 @@
+use crate::code::suggestions::SUGGESTIONS;
+use lighthousemc_editor_common::packet::s2c::{ SuggestionsS2CPacket, SuggestionModeS2CPacket };
+use lighthousemc_editor_common::packet::c2s::{ SetSuggestingC2SPacket, ReviewSuggestionC2SPacket };
+use std::sync::Mutex;
+use wasm_bindgen::prelude::*;
+use web_sys::MouseEvent;
+
+
+/// Whether edits are sent as suggestions, and whether the client may write directly.
+static MODE : Mutex<SuggestionModeS2CPacket> = Mutex::new(SuggestionModeS2CPacket { suggesting : false, can_write : true });
+
+
+pub fn init() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let indicator = document.get_element_by_id("editor_footer_suggesting").unwrap();
+    let click_callback = Closure::<dyn FnMut() -> ()>::new(move || { toggle(); });
+    indicator.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+    click_callback.forget();
+}
+
+
+pub fn on_mode(mode : SuggestionModeS2CPacket) {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let indicator = document.get_element_by_id("editor_footer_suggesting").unwrap();
+    indicator.class_list().toggle_with_force("editor_footer_suggesting_on", mode.suggesting).unwrap();
+    indicator.set_text_content(Some(match ((mode.suggesting, mode.can_write)) {
+        (true,  true ) => "Suggesting",
+        (true,  false) => "Suggesting (read-only)",
+        (false, _    ) => "Editing"
+    }));
+    indicator.set_attribute("title", if (mode.can_write) { "Click to switch between editing and suggesting" } else { "You can only suggest changes to this plot" }).unwrap();
+    *MODE.lock().unwrap() = mode;
+    update();
+}
+
+/// Switches between editing files directly and suggesting changes, if the client may write.
+pub fn toggle() {
+    let mode = MODE.lock().unwrap().clone();
+    if (mode.can_write) {
+        crate::ws::WS.send(SetSuggestingC2SPacket { suggesting : ! mode.suggesting });
+    }
+}
+
+
+pub fn on_suggestions(suggestions : SuggestionsS2CPacket<'static>) {
+    crate::code::suggestions::set(suggestions.file_id, suggestions.suggestions.into_owned());
+    update();
+}
+
+
+/// Redraws the suggested changes in the comment panel.
+pub fn update() {
+    let window   = web_sys::window().unwrap();
+    let document = window.document().unwrap();
+
+    let list      = document.get_element_by_id("editor_suggestions_list").unwrap();
+    let can_write = MODE.lock().unwrap().can_write;
+    list.set_inner_html("");
+
+    let     files  = SUGGESTIONS.read();
+    let mut sorted = files.iter().filter_map(|(file_id, suggestions)| Some((crate::state::file_path(*file_id)?, *file_id, suggestions))).collect::<Vec<_>>();
+    sorted.sort_by(|(path_a, _, _), (path_b, _, _)| path_a.cmp(path_b));
+
+    let count = sorted.iter().map(|(_, _, suggestions)| suggestions.len()).sum::<usize>();
+    document.get_element_by_id("editor_suggestions_header").unwrap().set_text_content(Some(&format!("Suggested changes ({})", count)));
+    document.get_element_by_id("editor_suggestions").unwrap().class_list().toggle_with_force("editor_suggestions_hidden", count == 0).unwrap();
+
+    for (path, file_id, suggestions) in sorted {
+        for suggestion in suggestions {
+            let suggestion_id = suggestion.suggestion_id;
+            let (start, end)  = (suggestion.start, suggestion.end);
+
+            let element = document.create_element("div").unwrap();
+            element.class_list().toggle_with_force("editor_suggestions_suggestion", true).unwrap();
+
+            let header = document.create_element("div").unwrap();
+            header.class_list().toggle_with_force("hbox", true).unwrap();
+            let name = document.create_element("span").unwrap();
+            name.class_list().toggle_with_force("editor_comments_name", true).unwrap();
+            name.set_attribute("style", &format!("color: hsl({}, 100%, 62.5%);", (suggestion.colour as u32) * 2)).unwrap();
+            name.set_text_content(Some(&suggestion.client_name));
+            header.append_child(&name).unwrap();
+            let location = document.create_element("span").unwrap();
+            location.class_list().toggle_with_force("editor_comments_location", true).unwrap();
+            location.set_text_content(Some(&match (crate::code::offset_to_line(file_id, start)) {
+                Some(line) => format!("{}:{}", path, line),
+                None       => path.clone()
+            }));
+            header.append_child(&location).unwrap();
+            if (suggestion.stale) {
+                let stale = document.create_element("span").unwrap();
+                stale.class_list().toggle_with_force("editor_suggestions_stale", true).unwrap();
+                stale.set_text_content(Some("outdated"));
+                header.append_child(&stale).unwrap();
+            }
+            let click_callback = Closure::<dyn FnMut() -> ()>::new(move || {
+                let Some(path) = crate::state::file_path(file_id) else { return; };
+                if (crate::state::open_file(file_id, path, true)) {
+                    crate::code::reveal_offsets(file_id, start, end);
+                }
+            });
+            element.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+            click_callback.forget();
+            element.append_child(&header).unwrap();
+
+            if (! suggestion.original.is_empty()) {
+                let removed = document.create_element("div").unwrap();
+                removed.class_list().toggle_with_force("editor_suggestions_removed", true).unwrap();
+                removed.set_text_content(Some(suggestion.original.trim_end_matches('\n')));
+                element.append_child(&removed).unwrap();
+            }
+            if (! suggestion.replacement.is_empty()) {
+                let inserted = document.create_element("div").unwrap();
+                inserted.class_list().toggle_with_force("editor_suggestions_inserted", true).unwrap();
+                inserted.set_text_content(Some(suggestion.replacement.trim_end_matches('\n')));
+                element.append_child(&inserted).unwrap();
+            }
+
+            let actions = document.create_element("div").unwrap();
+            actions.class_list().toggle_with_force("hbox", true).unwrap();
+            let mut buttons = Vec::new();
+            if (can_write && ! suggestion.stale) {
+                buttons.push(("Accept", true));
+            }
+            if (can_write || suggestion.applied) {
+                buttons.push((if (suggestion.applied) { "Withdraw" } else { "Reject" }, false));
+            }
+            for (label, accept) in buttons {
+                let button = document.create_element("div").unwrap();
+                button.class_list().toggle_with_force("editor_comments_resolve", true).unwrap();
+                button.set_text_content(Some(label));
+                let click_callback = Closure::<dyn FnMut(_) -> ()>::new(move |event : MouseEvent| {
+                    event.stop_propagation();
+                    crate::ws::WS.send(ReviewSuggestionC2SPacket { suggestion_id, accept });
+                });
+                button.add_event_listener_with_callback("click", click_callback.as_ref().unchecked_ref()).unwrap();
+                click_callback.forget();
+                actions.append_child(&button).unwrap();
+            }
+            element.append_child(&actions).unwrap();
+
+            list.append_child(&element).unwrap();
+        }
+    }
+}
@@ -12,7 +12,7 @@ use std::borrow::Cow;
 use wasm_bindgen::prelude::*;
 use web_sys::{ WebSocket, BinaryType, MessageEvent, ErrorEvent };
 use js_sys::{ ArrayBuffer, Uint8Array };
-use js_sys::Date;
+use js_sys::{ Date, Math };
 use wasm_cookies as cookies;
 
 
@@ -63,8 +63,9 @@ pub(super) fn start() {
     let cookie_expires = Date::new_0();
     let cookie_expires_hours = 12.0;
     cookie_expires.set_time(cookie_expires.get_time() + (cookie_expires_hours*3600000.0));
+    let editor_path = crate::editor_path();
     cookies::set("lighthousemc-editor-session", &session_code, &cookies::CookieOptions {
-        path      : Some("/editor"),
+        path      : Some(&editor_path),
         domain    : None,
         expires   : Some(Cow::Owned(cookie_expires.to_utc_string().into())),
         secure    : true,
@@ -75,11 +76,8 @@ pub(super) fn start() {
         "https:" => "wss:",
         _ => panic!()
     };
-    let hostname = location.hostname().unwrap();
-    let port     = location.port().unwrap();
-    let path     = location.pathname().unwrap();
-    let path     = path.trim_end_matches('/');
-    let ws_host  = format!("{protocol}//{hostname}:{port}{path}/ws");
+    let host     = location.host().unwrap();
+    let ws_host  = format!("{protocol}//{host}{editor_path}/ws");
     let ws       = WebSocket::new_with_str(&ws_host, "lighthousemc-editor").unwrap();
     ws.set_binary_type(BinaryType::Arraybuffer);
 
@@ -138,7 +136,21 @@ fn on_ws_message(e : MessageEvent) {
 
 
         S2CPackets::Disconnect(disconnect) => {
-            crate::cover::open_cover_error(&format!("<b>Server disconnected</b>:<br />{}", disconnect.reason));
+            match (disconnect.retry_after_ms) {
+                Some(retry_after_ms) => {
+                    crate::cover::open_cover_error(&format!("<b>Server restarting</b><br />{}<br />Reconnecting shortly...", disconnect.reason));
+                    // Spread out reconnects so that every client does not arrive at once.
+                    let delay    = retry_after_ms as f64 + (Math::random() * 3000.0);
+                    let callback = Closure::<dyn FnMut() -> ()>::new(move || {
+                        let _ = web_sys::window().unwrap().location().reload();
+                    });
+                    web_sys::window().unwrap().set_timeout_with_callback_and_timeout_and_arguments_0(callback.as_ref().unchecked_ref(), delay as i32).unwrap();
+                    callback.forget();
+                },
+                None => {
+                    crate::cover::open_cover_error(&format!("<b>Server disconnected</b>:<br />{}", disconnect.reason));
+                }
+            }
             let _ = WS.close();
         },
 
@@ -188,12 +200,15 @@ fn on_ws_message(e : MessageEvent) {
 
 
         S2CPackets::OvewriteFile(overwrite_file) => {
-            if let Some(FilesEntry { is_open, fsname, .. }) = crate::state::FILES.write_files().get_mut(&overwrite_file.file_id) {
-                crate::filetabs::overwrite(overwrite_file.file_id, fsname, &overwrite_file.contents);
-                *is_open = Some(Some(match (overwrite_file.contents) {
-                    FileContents::NonText    => FilesEntryContents::NonText,
-                    FileContents::Text(text) => FilesEntryContents::Text(text.into_owned())
+            let mut files = crate::state::FILES.write_files();
+            if let Some(FilesEntry { is_open, fsname, .. }) = files.get_mut(&overwrite_file.file_id) {
+                let fsname = fsname.clone();
+                *is_open = Some(Some(match (&overwrite_file.contents) {
+                    FileContents::Binary { size, mime } => FilesEntryContents::Binary { size : *size, mime : mime.to_string() },
+                    FileContents::Text(text)            => FilesEntryContents::Text(text.to_string())
                 }));
+                drop(files);
+                crate::filetabs::overwrite(overwrite_file.file_id, &fsname, &overwrite_file.contents);
             }
         },
 
@@ -225,6 +240,61 @@ fn on_ws_message(e : MessageEvent) {
 
         S2CPackets::CloseFile(close_file) => {
             crate::state::close_file(close_file.file_id, None);
+        },
+
+
+        S2CPackets::SearchResults(search_results) => {
+            crate::search::on_results(search_results);
+        },
+
+
+        S2CPackets::SearchDone(search_done) => {
+            crate::search::on_done(search_done);
+        },
+
+
+        S2CPackets::AuditLog(audit_log) => {
+            crate::activity::on_log(audit_log);
+        },
+
+
+        S2CPackets::Blame(blame) => {
+            crate::code::blame::on_blame(blame);
+        },
+
+
+        S2CPackets::Presence(presence) => {
+            crate::presence::on_presence(presence);
+        },
+
+
+        S2CPackets::PresenceLeave(presence_leave) => {
+            crate::presence::on_leave(presence_leave);
+        },
+
+
+        S2CPackets::Viewport(viewport) => {
+            crate::follow::on_viewport(viewport);
+        },
+
+
+        S2CPackets::Chat(chat) => {
+            crate::chat::on_chat(chat);
+        },
+
+
+        S2CPackets::CommentThreads(comment_threads) => {
+            crate::comments::on_threads(comment_threads);
+        },
+
+
+        S2CPackets::Suggestions(suggestions) => {
+            crate::suggestions::on_suggestions(suggestions);
+        },
+
+
+        S2CPackets::SuggestionMode(suggestion_mode) => {
+            crate::suggestions::on_mode(suggestion_mode);
         }
 
 
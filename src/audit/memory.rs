@@ -0,0 +1,45 @@
+use super::{ AuditSink, AuditEntry };
+use lighthousemc_database::DBPlotID;
+use std::collections::{ BTreeMap, VecDeque };
+use std::pin::Pin;
+use std::time::SystemTime;
+
+
+/// Keeps the most recent entries of each plot in memory. Entries are lost when the app exits.
+pub struct MemoryAuditSink {
+    entries_per_plot : usize,
+    plots            : BTreeMap<DBPlotID, VecDeque<AuditEntry>>
+}
+
+impl MemoryAuditSink {
+    pub fn new(entries_per_plot : usize) -> Self { Self {
+        entries_per_plot,
+        plots            : BTreeMap::new()
+    } }
+}
+
+impl Default for MemoryAuditSink {
+    fn default() -> Self { Self::new(1000) }
+}
+
+impl AuditSink for MemoryAuditSink {
+
+    fn record(&mut self, entry : AuditEntry) {
+        let entries = self.plots.entry(entry.plot_id).or_default();
+        entries.push_back(entry);
+        while (entries.len() > self.entries_per_plot) {
+            entries.pop_front();
+        }
+    }
+
+    fn query(&self, plot_id : DBPlotID, before : Option<SystemTime>, limit : usize) -> Pin<Box<dyn Future<Output = Vec<AuditEntry>> + Send + 'static>> {
+        let entries = self.plots.get(&plot_id).map_or_else(Vec::new, |entries| entries.iter().rev()
+            .filter(|entry| before.is_none_or(|before| entry.timestamp < before))
+            .take(limit)
+            .cloned()
+            .collect()
+        );
+        Box::pin(async move { entries })
+    }
+
+}
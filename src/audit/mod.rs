@@ -0,0 +1,268 @@
+use crate::peer::OutgoingPeerCommand;
+use crate::instances::EditorInstance;
+use crate::instances::session::{ EditorSession, EditorSessionStep };
+use lighthousemc_editor_common::packet::s2c::{ S2CPackets, AuditLogS2CPacket, AuditLogEntry };
+use lighthousemc_database::{ DBPlotID, DBFSFileID };
+use axecs::prelude::*;
+use std::fmt;
+use std::pin::Pin;
+use std::collections::BTreeMap;
+use std::time::{ SystemTime, Duration, UNIX_EPOCH };
+use uuid::Uuid;
+
+
+mod memory;
+pub use memory::*;
+
+
+/// Edits by the same client to the same file are merged into one entry until they stop for this long.
+const EDIT_SUMMARY_IDLE : Duration = Duration::from_secs(30);
+
+/// The most entries that a client can ask for at once.
+const MAX_QUERY_LIMIT : usize = 200;
+
+
+#[derive(Clone, Debug)]
+pub struct AuditEntry {
+    pub timestamp   : SystemTime,
+    pub plot_id     : DBPlotID,
+    pub client_uuid : Uuid,
+    pub client_name : String,
+    pub event       : AuditEvent
+}
+
+#[derive(Clone, Debug)]
+pub enum AuditEvent {
+    SessionOpened,
+    SessionClosed,
+    FileOpened {
+        file_id : DBFSFileID,
+        path    : String
+    },
+    FileUploaded {
+        file_id : DBFSFileID,
+        path    : String,
+        size    : u64
+    },
+    FileEdited {
+        file_id  : DBFSFileID,
+        path     : String,
+        inserted : usize,
+        deleted  : usize
+    },
+    FileCreated {
+        file_id : DBFSFileID,
+        path    : String
+    },
+    /// The file stayed in the same directory.
+    FileRenamed {
+        file_id : DBFSFileID,
+        from    : String,
+        to      : String
+    },
+    /// The file was moved to another directory.
+    FileMoved {
+        file_id : DBFSFileID,
+        from    : String,
+        to      : String
+    },
+    FileDeleted {
+        file_id : DBFSFileID,
+        path    : String
+    }
+}
+
+impl AuditEvent {
+    pub fn file_id(&self) -> Option<DBFSFileID> {
+        match (self) {
+            Self::SessionOpened | Self::SessionClosed => None,
+            Self::FileOpened   { file_id, .. }
+            | Self::FileUploaded { file_id, .. }
+            | Self::FileEdited   { file_id, .. }
+            | Self::FileCreated  { file_id, .. }
+            | Self::FileRenamed  { file_id, .. }
+            | Self::FileMoved    { file_id, .. }
+            | Self::FileDeleted  { file_id, .. } => Some(*file_id)
+        }
+    }
+}
+
+impl fmt::Display for AuditEvent {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self) {
+            Self::SessionOpened                                => write!(f, "joined"),
+            Self::SessionClosed                                => write!(f, "left"),
+            Self::FileOpened   { path, .. }                    => write!(f, "opened {}", path),
+            Self::FileUploaded { path, size, .. }              => write!(f, "uploaded {} ({} bytes)", path, size),
+            Self::FileEdited   { path, inserted, deleted, .. } => write!(f, "edited {} (+{} −{})", path, inserted, deleted),
+            Self::FileCreated  { path, .. }                    => write!(f, "created {}", path),
+            Self::FileRenamed  { from, to, .. }                => write!(f, "renamed {} to {}", from, to),
+            Self::FileMoved    { from, to, .. }                => write!(f, "moved {} to {}", from, to),
+            Self::FileDeleted  { path, .. }                    => write!(f, "deleted {}", path)
+        }
+    }
+}
+
+
+/// Stores audit entries. The default [`MemoryAuditSink`] only keeps recent entries,
+/// so hosts wanting a permanent record should provide their own.
+pub trait AuditSink : Send + Sync + 'static {
+
+    fn record(&mut self, entry : AuditEntry);
+
+    /// Entries on a plot older than `before`, newest first.
+    ///
+    /// The returned future must not borrow the sink, so that slow queries do not hold up the editor.
+    fn query(&self, plot_id : DBPlotID, before : Option<SystemTime>, limit : usize) -> Pin<Box<dyn Future<Output = Vec<AuditEntry>> + Send + 'static>>;
+
+}
+
+
+#[derive(Component)]
+pub struct EditorAuditLog {
+    sink          : Box<dyn AuditSink>,
+    pending_edits : BTreeMap<(DBPlotID, Uuid, DBFSFileID), AuditEntry>
+}
+
+impl EditorAuditLog {
+
+    pub(crate) fn new(sink : Box<dyn AuditSink>) -> Self { Self {
+        sink,
+        pending_edits : BTreeMap::new()
+    } }
+
+    fn record(&mut self, entry : AuditEntry) {
+        match (&entry.event) {
+            AuditEvent::FileEdited { file_id, inserted, deleted, .. } => {
+                let key = (entry.plot_id, entry.client_uuid, *file_id);
+                if let Some(pending) = self.pending_edits.get_mut(&key) {
+                    if let AuditEvent::FileEdited { inserted : pending_inserted, deleted : pending_deleted, .. } = &mut pending.event {
+                        *pending_inserted += inserted;
+                        *pending_deleted  += deleted;
+                    }
+                    pending.timestamp = entry.timestamp;
+                } else {
+                    self.pending_edits.insert(key, entry);
+                }
+            },
+            AuditEvent::SessionClosed => {
+                self.flush_edits(|pending| pending.plot_id == entry.plot_id && pending.client_uuid == entry.client_uuid);
+                self.sink.record(entry);
+            },
+            _ => { self.sink.record(entry); }
+        }
+    }
+
+    fn flush_edits<F : Fn(&AuditEntry) -> bool>(&mut self, f : F) {
+        let keys = self.pending_edits.iter().filter(|(_, pending)| f(pending)).map(|(key, _)| *key).collect::<Vec<_>>();
+        for key in keys {
+            if let Some(pending) = self.pending_edits.remove(&key) {
+                self.sink.record(pending);
+            }
+        }
+    }
+
+    /// Entries on a plot older than `before`, newest first. Includes edits that are still being merged.
+    pub fn query(&self, plot_id : DBPlotID, before : Option<SystemTime>, limit : usize) -> impl Future<Output = Vec<AuditEntry>> + Send + 'static {
+        let recorded = self.sink.query(plot_id, before, limit);
+        let pending  = self.pending_edits.values()
+            .filter(|pending| pending.plot_id == plot_id && before.is_none_or(|before| pending.timestamp < before))
+            .cloned()
+            .collect::<Vec<_>>();
+        async move {
+            let mut entries = recorded.await;
+            entries.extend(pending);
+            entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+            entries.truncate(limit);
+            entries
+        }
+    }
+
+}
+
+
+/// Records every edit that is still being merged, eg. before the app exits.
+pub(crate) async fn flush_audit_log(
+    mut audit_logs : Entities<(&mut EditorAuditLog)>
+) {
+    for audit_log in &mut audit_logs {
+        audit_log.flush_edits(|_| true);
+    }
+}
+
+
+pub(crate) async fn update_audit_log(
+    mut instances  : Entities<(&mut EditorInstance)>,
+    mut sessions   : Entities<(&mut EditorSession)>,
+    mut audit_logs : Entities<(&mut EditorAuditLog)>
+) {
+    let Some(audit_log) = audit_logs.iter_mut().next() else { return; };
+
+    for instance in &mut instances {
+        for entry in instance.drain_audit_entries() {
+            audit_log.record(entry);
+        }
+    }
+
+    let now = SystemTime::now();
+    audit_log.flush_edits(|pending| now.duration_since(pending.timestamp).is_ok_and(|idle| idle >= EDIT_SUMMARY_IDLE));
+
+    for session in &mut sessions {
+        let plot_id = session.plot_id();
+        if let EditorSessionStep::Active { outgoing_commands_tx, state, .. } = session.session_step_mut() {
+            while let Some((before, limit)) = state.pop_audit_query() {
+                let before               = before.map(|before| UNIX_EPOCH + Duration::from_millis(before));
+                let entries              = audit_log.query(plot_id, before, (limit as usize).min(MAX_QUERY_LIMIT));
+                let outgoing_commands_tx = outgoing_commands_tx.clone();
+                // Answered once the sink has the entries, without holding up the editor.
+                tokio::spawn(async move {
+                    let entries = entries.await;
+                    let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::AuditLog(AuditLogS2CPacket {
+                        is_continuation : before.is_some(),
+                        entries         : entries.into_iter().map(|entry| AuditLogEntry {
+                            timestamp   : entry.timestamp.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64),
+                            client_name : entry.client_name.into(),
+                            colour      : (entry.client_uuid.as_u128() % 180) as u8,
+                            file_id     : entry.event.file_id(),
+                            description : entry.event.to_string().into()
+                        }).collect::<Vec<_>>().into()
+                    })));
+                });
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instances::EditorInstanceState;
+
+    const CLIENT : Uuid = Uuid::from_u128(1);
+
+    #[tokio::test]
+    async fn file_tree_changes_are_queried() {
+        let mut instance = EditorInstance::with_state(6, EditorInstanceState::empty(6));
+        assert!(instance.create_file(CLIENT, "Client", 1, None, "a.txt".to_string(), b"one".to_vec()));
+        assert!(! instance.create_file(CLIENT, "Client", 1, None, "a.txt".to_string(), Vec::new()));
+        assert!(! instance.create_file(CLIENT, "Client", 2, Some(3), "b.txt".to_string(), Vec::new()));
+        assert!(instance.move_file(CLIENT, "Client", 1, None, "b.txt".to_string()));
+        assert!(instance.delete_file(CLIENT, "Client", 1));
+        assert!(! instance.delete_file(CLIENT, "Client", 1));
+
+        let mut audit_log = EditorAuditLog::new(Box::new(MemoryAuditSink::default()));
+        for entry in instance.drain_audit_entries() {
+            audit_log.record(entry);
+        }
+        let entries = audit_log.query(6, None, 10).await;
+        let entries = entries.iter().map(|entry| (entry.event.file_id(), entry.event.to_string())).collect::<Vec<_>>();
+        assert_eq!(entries, [
+            (Some(1), "deleted b.txt".to_string()),
+            (Some(1), "renamed a.txt to b.txt".to_string()),
+            (Some(1), "created a.txt".to_string())
+        ]);
+        assert!(audit_log.query(7, None, 10).await.is_empty());
+    }
+
+}
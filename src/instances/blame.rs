@@ -0,0 +1,357 @@
+use crate::metrics::METRICS;
+use lighthousemc_editor_common::packet::s2c::{ BlameS2CPacket, BlameRange, BlameAuthor };
+use lighthousemc_editor_common::dmp;
+use lighthousemc_database::DBFSFileID;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use uuid::Uuid;
+
+
+/// The parts of a text that changed between two versions, one region for each diff hunk.
+pub(crate) struct TextEdit {
+    /// In order, without overlapping.
+    regions : Vec<EditRegion>
+}
+
+/// One changed part of a text, as byte ranges in the old and in the new version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct EditRegion {
+    old : Range<usize>,
+    new : Range<usize>
+}
+
+impl TextEdit {
+
+    /// Diffs the two versions, so that unchanged text between changes is kept.
+    pub(crate) fn between(old : &str, new : &str) -> Self {
+        let dmp = dmp::DiffMatchPatch::new();
+        let Ok(diffs) = METRICS.time_diff(|| dmp.diff_main::<dmp::Efficient>(old, new)) else {
+            return Self { regions : EditRegion::around(old, new).into_iter().collect() };
+        };
+        let mut regions = Vec::<EditRegion>::new();
+        let (mut old_pos, mut new_pos) = (0, 0);
+        for diff in &diffs {
+            let len = diff.data().len();
+            let (old_end, new_end) = match (diff.op()) {
+                dmp::Ops::Equal  => { old_pos += len; new_pos += len; continue; },
+                dmp::Ops::Delete => (old_pos + len, new_pos),
+                dmp::Ops::Insert => (old_pos, new_pos + len)
+            };
+            match (regions.last_mut()) {
+                Some(last) if (last.old.end == old_pos && last.new.end == new_pos) => {
+                    last.old.end = old_end;
+                    last.new.end = new_end;
+                },
+                _ => { regions.push(EditRegion { old : old_pos..old_end, new : new_pos..new_end }); }
+            }
+            (old_pos, new_pos) = (old_end, new_end);
+        }
+        // An insertion or deletion in repeated text could be anywhere in it. Move it as late as possible,
+        // so that eg. a removed line is the line itself rather than the line break before it and the rest of the line.
+        for index in 0..regions.len() {
+            let (old_limit, new_limit) = regions.get(index + 1).map_or((old.len(), new.len()), |next| (next.old.start, next.new.start));
+            let region = &mut regions[index];
+            while (region.can_slide(old, new, old_limit, new_limit)) {
+                region.old = (region.old.start + 1)..(region.old.end + 1);
+                region.new = (region.new.start + 1)..(region.new.end + 1);
+            }
+        }
+        // Diffs are made of bytes, so grow them to whole characters. The text around them is the same in both versions.
+        for region in &mut regions {
+            while (! (old.is_char_boundary(region.old.start) && new.is_char_boundary(region.new.start))) {
+                region.old.start -= 1;
+                region.new.start -= 1;
+            }
+            while (! (old.is_char_boundary(region.old.end) && new.is_char_boundary(region.new.end))) {
+                region.old.end += 1;
+                region.new.end += 1;
+            }
+        }
+        regions.dedup_by(|next, last| {
+            if (next.old.start > last.old.end) { return false; }
+            last.old.end = next.old.end;
+            last.new.end = next.new.end;
+            true
+        });
+        Self { regions }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.regions.is_empty()
+    }
+
+    pub(crate) fn inserted_chars(&self, new : &str) -> usize {
+        self.regions.iter().map(|region| new[region.new.clone()].chars().count()).sum()
+    }
+
+    pub(crate) fn deleted_chars(&self, old : &str) -> usize {
+        self.regions.iter().map(|region| old[region.old.clone()].chars().count()).sum()
+    }
+
+    /// Moves a byte offset in the old text to the same place in the new text.
+    /// Offsets inside a changed region move to its start, or to its end if `is_end`.
+    /// Text inserted exactly at an offset ends up outside the range that the offset starts or ends.
+    pub(crate) fn shift(&self, offset : usize, is_end : bool) -> usize {
+        for region in &self.regions {
+            if (offset < region.old.start || (offset == region.old.start && (! region.old.is_empty() || is_end))) {
+                return region.new.start - (region.old.start - offset);
+            }
+            if (offset < region.old.end) {
+                return if (is_end) { region.new.end } else { region.new.start };
+            }
+        }
+        match (self.regions.last()) {
+            Some(last) => offset - last.old.end + last.new.end,
+            None       => offset
+        }
+    }
+
+    /// Whether the edit changes anything strictly inside a byte range of the old text.
+    pub(crate) fn overlaps(&self, range : Range<usize>) -> bool {
+        self.regions.iter().any(|region| region.old.start < range.end && range.start < region.old.end)
+    }
+
+}
+
+impl EditRegion {
+
+    /// The changed region found by ignoring the unchanged start and end, or `None` if nothing changed.
+    fn around(old : &str, new : &str) -> Option<Self> {
+        let (old_bytes, new_bytes) = (old.as_bytes(), new.as_bytes());
+        let mut start = old_bytes.iter().zip(new_bytes).take_while(|(a, b)| a == b).count();
+        while (! (old.is_char_boundary(start) && new.is_char_boundary(start))) { start -= 1; }
+        let mut suffix = old_bytes[start..].iter().rev().zip(new_bytes[start..].iter().rev()).take_while(|(a, b)| a == b).count();
+        while (! (old.is_char_boundary(old.len() - suffix) && new.is_char_boundary(new.len() - suffix))) { suffix -= 1; }
+        let region = Self { old : start..(old.len() - suffix), new : start..(new.len() - suffix) };
+        (! (region.old.is_empty() && region.new.is_empty())).then_some(region)
+    }
+
+    /// Whether this is an insertion or deletion that could be one byte later, as the text after it is the same as its start.
+    fn can_slide(&self, old : &str, new : &str, old_limit : usize, new_limit : usize) -> bool {
+        if (self.new.is_empty()) { self.old.end < old_limit && old.as_bytes()[self.old.start] == old.as_bytes()[self.old.end] }
+        else if (self.old.is_empty()) { self.new.end < new_limit && new.as_bytes()[self.new.start] == new.as_bytes()[self.new.end] }
+        else { false }
+    }
+}
+
+
+/// Who last changed each line of a text file.
+#[derive(Clone, Default)]
+pub struct FileBlame {
+    authors : BTreeMap<Uuid, String>,
+    /// The author of each line. `None` if the line has not been changed since the file was loaded.
+    lines   : Vec<Option<Uuid>>
+}
+
+impl FileBlame {
+
+    /// Attributes every line of `text` to `author`, or to nobody.
+    pub fn new(text : &str, author : Option<(Uuid, &str)>) -> Self {
+        let mut blame = Self::default();
+        if let Some((uuid, name)) = author {
+            blame.authors.insert(uuid, name.to_string());
+        }
+        blame.lines = vec![ author.map(|(uuid, _)| uuid); line_count(text) ];
+        blame
+    }
+
+    /// Attributes the lines changed between `old` and `new` to `author`.
+    pub(crate) fn apply_edit(&mut self, old : &str, new : &str, edit : &TextEdit, author_uuid : Uuid, author_name : &str) {
+        if (edit.is_empty()) { return; }
+        if (self.lines.len() != line_count(old)) {
+            *self = Self::new(old, None);
+        }
+        // From the end, so that the lines before each region are still numbered as in `old`.
+        for region in edit.regions.iter().rev() {
+            let     start_line   = old[..region.old.start].matches('\n').count();
+            let mut old_end_line = start_line + old[region.old.clone()].matches('\n').count() + 1;
+            let mut new_end_line = start_line + new[region.new.clone()].matches('\n').count() + 1;
+            // Whole lines were inserted or removed, so the line after them is untouched.
+            if (is_line_start(old, region.old.start) && is_line_start(old, region.old.end) && is_line_start(new, region.new.end)) {
+                old_end_line -= 1;
+                new_end_line -= 1;
+            }
+            self.lines.splice(start_line..old_end_line, (start_line..new_end_line).map(|_| Some(author_uuid)));
+        }
+        self.authors.insert(author_uuid, author_name.to_string());
+        self.authors.retain(|uuid, _| self.lines.contains(&Some(*uuid)));
+    }
+
+    /// Whether this blame has a line for each line of `text`.
+    pub(crate) fn fits(&self, text : &str) -> bool {
+        self.lines.len() == line_count(text)
+    }
+
+    /// Runs of consecutive lines with the same author, from the top of the file.
+    pub fn ranges(&self) -> Vec<(u32, Option<(Uuid, &str)>)> {
+        let mut ranges = Vec::<(u32, Option<Uuid>)>::new();
+        for &author in &self.lines {
+            match (ranges.last_mut()) {
+                Some((lines, last_author)) if (*last_author == author) => { *lines += 1; },
+                _                                                         => { ranges.push((1, author)); }
+            }
+        }
+        ranges.into_iter().map(|(lines, author)| (lines, author.and_then(|uuid| self.authors.get(&uuid).map(|name| (uuid, name.as_str()))))).collect()
+    }
+
+    pub(crate) fn to_packet(&self, file_id : DBFSFileID) -> BlameS2CPacket<'static> {
+        BlameS2CPacket {
+            file_id,
+            ranges  : self.ranges().into_iter().map(|(lines, author)| BlameRange {
+                lines,
+                author : author.map(|(uuid, name)| BlameAuthor {
+                    client_name : name.to_string().into(),
+                    colour      : (uuid.as_u128() % 180) as u8
+                })
+            }).collect::<Vec<_>>().into()
+        }
+    }
+
+
+    /// Encodes this blame so that it can be stored alongside the file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend((self.authors.len() as u32).to_be_bytes());
+        for (uuid, name) in &self.authors {
+            buf.extend(uuid.as_bytes());
+            buf.extend((name.len() as u32).to_be_bytes());
+            buf.extend(name.as_bytes());
+        }
+        let ranges = self.ranges();
+        buf.extend((ranges.len() as u32).to_be_bytes());
+        for (lines, author) in ranges {
+            buf.extend(lines.to_be_bytes());
+            buf.extend(author.map_or(Uuid::nil(), |(uuid, _)| uuid).as_bytes());
+        }
+        buf
+    }
+
+    /// Decodes a blame created by [`FileBlame::to_bytes`]. Returns `None` if the data is invalid.
+    pub fn from_bytes(mut data : &[u8]) -> Option<Self> {
+        fn take<'l>(data : &mut &'l [u8], len : usize) -> Option<&'l [u8]> {
+            let (taken, rest) = data.split_at_checked(len)?;
+            *data = rest;
+            Some(taken)
+        }
+        fn take_u32(data : &mut &[u8]) -> Option<u32> {
+            Some(u32::from_be_bytes(take(data, 4)?.try_into().ok()?))
+        }
+        fn take_uuid(data : &mut &[u8]) -> Option<Uuid> {
+            Uuid::from_slice(take(data, 16)?).ok()
+        }
+        let mut blame = Self::default();
+        for _ in 0..take_u32(&mut data)? {
+            let uuid = take_uuid(&mut data)?;
+            let len  = take_u32(&mut data)? as usize;
+            blame.authors.insert(uuid, String::from_utf8(take(&mut data, len)?.to_vec()).ok()?);
+        }
+        for _ in 0..take_u32(&mut data)? {
+            let lines  = take_u32(&mut data)? as usize;
+            let author = Some(take_uuid(&mut data)?).filter(|uuid| ! uuid.is_nil());
+            blame.lines.extend((0..lines).map(|_| author));
+        }
+        Some(blame)
+    }
+
+}
+
+
+fn line_count(text : &str) -> usize {
+    text.matches('\n').count() + 1
+}
+
+fn is_line_start(text : &str, offset : usize) -> bool {
+    offset == 0 || text.as_bytes()[offset - 1] == b'\n'
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_hunk_is_a_region() {
+        let edit = TextEdit::between("one two three", "1 two 3");
+        assert_eq!(edit.regions, vec![
+            EditRegion { old : 0..3,  new : 0..1 },
+            EditRegion { old : 8..13, new : 6..7 }
+        ]);
+        assert_eq!(edit.inserted_chars("1 two 3"), 2);
+        assert_eq!(edit.deleted_chars("one two three"), 8);
+        assert!(TextEdit::between("same", "same").is_empty());
+    }
+
+    #[test]
+    fn regions_are_whole_characters() {
+        let (old, new) = ("a é b", "a è b");
+        let edit = TextEdit::between(old, new);
+        for region in &edit.regions {
+            assert!(old.is_char_boundary(region.old.start) && old.is_char_boundary(region.old.end));
+            assert!(new.is_char_boundary(region.new.start) && new.is_char_boundary(region.new.end));
+        }
+        assert_eq!(edit.inserted_chars(new), 1);
+        assert_eq!(edit.deleted_chars(old), 1);
+    }
+
+    #[test]
+    fn shift_keeps_text_between_hunks() {
+        let edit = TextEdit::between("one two three", "1 two 3");
+        // `two` is untouched, so it only moves.
+        assert_eq!((edit.shift(4, false), edit.shift(7, true)), (2, 5));
+        // `three` was replaced as a whole.
+        assert_eq!((edit.shift(8, false), edit.shift(13, true)), (6, 7));
+        // Offsets inside a replaced region move to its edges.
+        assert_eq!((edit.shift(10, false), edit.shift(10, true)), (6, 7));
+    }
+
+    #[test]
+    fn shift_excludes_insertions_at_edges() {
+        let edit = TextEdit::between("ab", "aXb");
+        assert_eq!(edit.shift(1, true), 1);
+        assert_eq!(edit.shift(1, false), 2);
+    }
+
+    #[test]
+    fn blame_only_changed_lines() {
+        let author = Uuid::from_u128(1);
+        let (old, new) = ("a\nb\nc\n", "A\nb\nC\n");
+        let mut blame = FileBlame::new(old, None);
+        blame.apply_edit(old, new, &TextEdit::between(old, new), author, "Author");
+        let authors = blame.ranges().into_iter().map(|(lines, author)| (lines, author.map(|(_, name)| name))).collect::<Vec<_>>();
+        assert_eq!(authors, vec![ (1, Some("Author")), (1, None), (1, Some("Author")), (1, None) ]);
+        assert!(blame.fits(new));
+    }
+
+    #[test]
+    fn blame_inserted_and_removed_lines() {
+        let author = Uuid::from_u128(1);
+        let (old, new) = ("a\nb\nc\n", "a\nc\nd\n");
+        let mut blame = FileBlame::new(old, None);
+        blame.apply_edit(old, new, &TextEdit::between(old, new), author, "Author");
+        assert!(blame.fits(new));
+        let authors = blame.ranges().into_iter().map(|(lines, author)| (lines, author.is_some())).collect::<Vec<_>>();
+        assert_eq!(authors, vec![ (2, false), (1, true), (1, false) ]);
+    }
+
+    #[test]
+    fn removed_line_is_not_blamed_on_the_line_before() {
+        let (old, new) = ("a\nb\nc\n", "a\nc\n");
+        let edit = TextEdit::between(old, new);
+        assert_eq!(edit.regions, vec![ EditRegion { old : 2..4, new : 2..2 } ]);
+        let mut blame = FileBlame::new(old, None);
+        blame.apply_edit(old, new, &edit, Uuid::from_u128(1), "Author");
+        assert_eq!(blame.ranges().into_iter().map(|(lines, author)| (lines, author.is_some())).collect::<Vec<_>>(), vec![ (3, false) ]);
+    }
+
+    #[test]
+    fn blame_bytes_round_trip() {
+        let (old, new) = ("a\nb\n", "a\nB\n");
+        let mut blame = FileBlame::new(old, Some((Uuid::from_u128(1), "First")));
+        blame.apply_edit(old, new, &TextEdit::between(old, new), Uuid::from_u128(2), "Second");
+        let decoded = FileBlame::from_bytes(&blame.to_bytes()).unwrap();
+        assert_eq!(decoded.ranges(), blame.ranges());
+        assert!(decoded.fits(new));
+        assert!(FileBlame::from_bytes(&[0, 0, 0, 1]).is_none());
+    }
+
+}
@@ -0,0 +1,68 @@
+use super::{ EditorInstance, EditorInstanceEvent, EditorHostEvent };
+use lighthousemc_editor_common::packet::s2c::{ ChatS2CPacket, ChatMessage, ChatSource };
+use std::time::{ SystemTime, UNIX_EPOCH };
+use uuid::Uuid;
+
+
+/// How many messages are kept in the chat history of an instance. Older messages are forgotten.
+pub const CHAT_HISTORY_LEN : usize = 1000;
+
+/// The longest message that can be sent, in characters. Matches the in-game chat limit so that messages can be bridged as-is.
+pub const MAX_CHAT_MESSAGE_LEN : usize = 256;
+
+
+impl EditorInstance {
+
+    /// Sends a message from a player in game to the editor chat.
+    pub fn send_game_chat(&mut self, player_uuid : Uuid, player_name : &str, message : &str) {
+        self.push_chat(ChatSource::Game, player_uuid, player_name, message);
+    }
+
+    /// Sends a message from the host server to the editor chat, eg. an announcement.
+    pub fn send_server_chat(&mut self, message : &str) {
+        self.push_chat(ChatSource::Server, Uuid::nil(), "", message);
+    }
+
+    /// The chat messages sent while this instance has been running, oldest first.
+    pub fn chat_history(&self) -> impl Iterator<Item = &ChatMessage<'static>> {
+        self.chat_history.iter()
+    }
+
+    /// Sends a message from a collaborator in the editor to the editor chat, and passes it on to the host server.
+    pub(crate) fn send_editor_chat(&mut self, client_uuid : Uuid, client_name : &str, message : &str) {
+        let Some(message) = self.push_chat(ChatSource::Editor, client_uuid, client_name, message) else { return; };
+        self.host_events.push_back(EditorHostEvent::ChatMessage {
+            client_uuid,
+            client_name : client_name.to_string(),
+            message
+        });
+    }
+
+    /// Returns the message as it was sent, or `None` if it was empty.
+    fn push_chat(&mut self, source : ChatSource, sender_uuid : Uuid, sender_name : &str, message : &str) -> Option<String> {
+        let message = message.trim().chars().take(MAX_CHAT_MESSAGE_LEN).collect::<String>();
+        if (message.is_empty()) { return None; }
+        let message = ChatMessage {
+            timestamp   : SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64),
+            source,
+            sender_name : sender_name.to_string().into(),
+            colour      : (sender_uuid.as_u128() % 180) as u8,
+            message     : message.into()
+        };
+        if (self.chat_history.len() >= CHAT_HISTORY_LEN) {
+            self.chat_history.pop_front();
+        }
+        self.chat_history.push_back(message.clone());
+        let text = message.message.to_string();
+        self.events.push_back(EditorInstanceEvent::Chat { message });
+        Some(text)
+    }
+
+    pub(crate) fn chat_history_packet(&self) -> ChatS2CPacket<'static> {
+        ChatS2CPacket {
+            is_history : true,
+            messages   : self.chat_history.iter().cloned().collect::<Vec<_>>().into()
+        }
+    }
+
+}
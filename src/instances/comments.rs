@@ -0,0 +1,338 @@
+use super::{ EditorInstance, EditorInstanceState, TextEdit };
+use lighthousemc_editor_common::packet::s2c::{ self, CommentThreadsS2CPacket, FileContents };
+use lighthousemc_database::DBFSFileID;
+use std::collections::BTreeMap;
+use std::time::{ SystemTime, UNIX_EPOCH };
+use uuid::Uuid;
+
+
+/// The longest comment that can be posted, in characters.
+pub const MAX_COMMENT_LEN : usize = 2000;
+
+/// The most comment threads that a plot can have, resolved or not.
+pub const MAX_COMMENT_THREADS : usize = 1000;
+
+
+/// The comment threads of a plot, each anchored to a range of a text file.
+#[derive(Clone, Default)]
+pub struct CommentThreads {
+    next_id : u64,
+    threads : BTreeMap<u64, StoredCommentThread>,
+    /// Whether the threads were changed since they were last saved.
+    unsaved : bool
+}
+
+#[derive(Clone)]
+pub struct StoredCommentThread {
+    pub file_id  : DBFSFileID,
+    /// Byte offsets of the commented range.
+    pub start    : usize,
+    pub end      : usize,
+    pub resolved : bool,
+    /// Oldest first. The first comment starts the thread.
+    pub comments : Vec<StoredComment>
+}
+
+#[derive(Clone)]
+pub struct StoredComment {
+    pub author_uuid : Uuid,
+    pub author_name : String,
+    /// Unix timestamp in milliseconds.
+    pub timestamp   : u64,
+    pub text        : String
+}
+
+impl StoredComment {
+
+    /// Returns `None` if the text is empty.
+    pub(crate) fn new(author_uuid : Uuid, author_name : &str, text : &str) -> Option<Self> {
+        let text = text.trim().chars().take(MAX_COMMENT_LEN).collect::<String>();
+        if (text.is_empty()) { return None; }
+        Some(Self {
+            author_uuid,
+            author_name : author_name.to_string(),
+            timestamp   : SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64),
+            text
+        })
+    }
+
+}
+
+impl CommentThreads {
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &StoredCommentThread)> {
+        self.threads.iter().map(|(thread_id, thread)| (*thread_id, thread))
+    }
+
+    pub fn get(&self, thread_id : u64) -> Option<&StoredCommentThread> {
+        self.threads.get(&thread_id)
+    }
+
+    /// Starts a thread on a range of `file_text`, given in UTF-16 code units. Returns the id of the new thread,
+    /// or `None` if the plot already has [`MAX_COMMENT_THREADS`].
+    pub(crate) fn create(&mut self, file_id : DBFSFileID, file_text : &str, start : usize, end : usize, comment : StoredComment) -> Option<u64> {
+        if (self.threads.len() >= MAX_COMMENT_THREADS) { return None; }
+        let (start, end) = (start.min(end), start.max(end));
+        let thread_id = self.next_id;
+        self.next_id += 1;
+        self.threads.insert(thread_id, StoredCommentThread {
+            file_id,
+            start    : utf16_to_byte(file_text, start),
+            end      : utf16_to_byte(file_text, end),
+            resolved : false,
+            comments : vec![ comment ]
+        });
+        self.unsaved = true;
+        Some(thread_id)
+    }
+
+    /// Returns `false` if the thread does not exist.
+    pub(crate) fn reply(&mut self, thread_id : u64, comment : StoredComment) -> bool {
+        let Some(thread) = self.threads.get_mut(&thread_id) else { return false; };
+        thread.comments.push(comment);
+        self.unsaved = true;
+        true
+    }
+
+    /// Returns `false` if the thread does not exist or was already in that state.
+    pub(crate) fn set_resolved(&mut self, thread_id : u64, resolved : bool) -> bool {
+        let Some(thread) = self.threads.get_mut(&thread_id) else { return false; };
+        if (thread.resolved == resolved) { return false; }
+        thread.resolved = resolved;
+        self.unsaved = true;
+        true
+    }
+
+    /// Moves the anchors of the threads on a file to follow an edit.
+    pub(crate) fn apply_edit(&mut self, file_id : DBFSFileID, edit : &TextEdit) {
+        for thread in self.threads.values_mut() { if (thread.file_id == file_id) {
+            let start = edit.shift(thread.start, false);
+            let end   = edit.shift(thread.end, true).max(start);
+            if ((start, end) != (thread.start, thread.end)) {
+                (thread.start, thread.end) = (start, end);
+                self.unsaved = true;
+            }
+        } }
+    }
+
+    pub(crate) fn to_packet(&self, thread_ids : impl IntoIterator<Item = u64>, state : &EditorInstanceState, is_initial : bool) -> CommentThreadsS2CPacket<'static> {
+        let mut threads = Vec::new();
+        for thread_id in thread_ids {
+            let Some(thread) = self.threads.get(&thread_id) else { continue; };
+            let Some(FileContents::Text(text)) = state.files().get(&thread.file_id).map(|file| file.contents()) else { continue; };
+            threads.push(s2c::CommentThread {
+                thread_id,
+                file_id   : thread.file_id,
+                start     : byte_to_utf16(text, thread.start),
+                end       : byte_to_utf16(text, thread.end),
+                resolved  : thread.resolved,
+                comments  : thread.comments.iter().map(|comment| s2c::Comment {
+                    timestamp   : comment.timestamp,
+                    client_name : comment.author_name.clone().into(),
+                    colour      : (comment.author_uuid.as_u128() % 180) as u8,
+                    text        : comment.text.clone().into()
+                }).collect::<Vec<_>>().into()
+            });
+        }
+        CommentThreadsS2CPacket { is_initial, threads : threads.into() }
+    }
+
+
+    /// Encodes these threads so that they can be stored alongside the plot.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        fn put_str(buf : &mut Vec<u8>, s : &str) {
+            buf.extend((s.len() as u32).to_be_bytes());
+            buf.extend(s.as_bytes());
+        }
+        let mut buf = Vec::new();
+        buf.extend(self.next_id.to_be_bytes());
+        buf.extend((self.threads.len() as u32).to_be_bytes());
+        for (thread_id, thread) in &self.threads {
+            buf.extend(thread_id.to_be_bytes());
+            buf.extend(thread.file_id.to_be_bytes());
+            buf.extend((thread.start as u32).to_be_bytes());
+            buf.extend((thread.end as u32).to_be_bytes());
+            buf.push(thread.resolved as u8);
+            buf.extend((thread.comments.len() as u32).to_be_bytes());
+            for comment in &thread.comments {
+                buf.extend(comment.author_uuid.as_bytes());
+                put_str(&mut buf, &comment.author_name);
+                buf.extend(comment.timestamp.to_be_bytes());
+                put_str(&mut buf, &comment.text);
+            }
+        }
+        buf
+    }
+
+    /// Decodes threads created by [`CommentThreads::to_bytes`]. Returns `None` if the data is invalid.
+    pub fn from_bytes(mut data : &[u8]) -> Option<Self> {
+        fn take<'l>(data : &mut &'l [u8], len : usize) -> Option<&'l [u8]> {
+            let (taken, rest) = data.split_at_checked(len)?;
+            *data = rest;
+            Some(taken)
+        }
+        fn take_u32(data : &mut &[u8]) -> Option<u32> {
+            Some(u32::from_be_bytes(take(data, 4)?.try_into().ok()?))
+        }
+        fn take_u64(data : &mut &[u8]) -> Option<u64> {
+            Some(u64::from_be_bytes(take(data, 8)?.try_into().ok()?))
+        }
+        fn take_str(data : &mut &[u8]) -> Option<String> {
+            let len = take_u32(data)? as usize;
+            String::from_utf8(take(data, len)?.to_vec()).ok()
+        }
+        let mut comments = Self { next_id : take_u64(&mut data)?, threads : BTreeMap::new(), unsaved : false };
+        for _ in 0..take_u32(&mut data)? {
+            let thread_id = take_u64(&mut data)?;
+            let mut thread = StoredCommentThread {
+                file_id  : take_u64(&mut data)?,
+                start    : take_u32(&mut data)? as usize,
+                end      : take_u32(&mut data)? as usize,
+                resolved : take(&mut data, 1)?[0] != 0,
+                comments : Vec::new()
+            };
+            for _ in 0..take_u32(&mut data)? {
+                thread.comments.push(StoredComment {
+                    author_uuid : Uuid::from_slice(take(&mut data, 16)?).ok()?,
+                    author_name : take_str(&mut data)?,
+                    timestamp   : take_u64(&mut data)?,
+                    text        : take_str(&mut data)?
+                });
+            }
+            comments.next_id = comments.next_id.max(thread_id + 1);
+            comments.threads.insert(thread_id, thread);
+        }
+        Some(comments)
+    }
+
+}
+
+
+impl EditorInstance {
+
+    pub fn comments(&self) -> &CommentThreads { &self.comments }
+
+    /// Replaces the comment threads of the plot, eg. with ones that were stored alongside it.
+    pub fn set_comments(&mut self, comments : CommentThreads) {
+        self.comments = comments;
+    }
+
+    pub(crate) fn comments_packet(&self) -> CommentThreadsS2CPacket<'static> {
+        self.comments.to_packet(self.comments.threads.keys().copied(), &self.state, true)
+    }
+
+    /// The encoded comment threads if they were changed since they were last saved, marking them as saved.
+    pub(crate) fn take_unsaved_comments(&mut self) -> Option<Vec<u8>> {
+        if (! self.comments.unsaved) { return None; }
+        self.comments.unsaved = false;
+        Some(self.comments.to_bytes())
+    }
+
+    /// Marks the comment threads as changed again, eg. after saving them failed.
+    pub(crate) fn mark_comments_unsaved(&mut self) {
+        self.comments.unsaved = true;
+    }
+
+    pub(crate) fn has_unsaved_comments(&self) -> bool {
+        self.comments.unsaved
+    }
+
+}
+
+
+pub(crate) fn utf16_to_byte(text : &str, offset : usize) -> usize {
+    let mut utf16 = 0;
+    for (byte, ch) in text.char_indices() {
+        if (utf16 >= offset) { return byte; }
+        utf16 += ch.len_utf16();
+    }
+    text.len()
+}
+
+pub(crate) fn byte_to_utf16(text : &str, offset : usize) -> usize {
+    let mut offset = offset.min(text.len());
+    while (! text.is_char_boundary(offset)) { offset -= 1; }
+    text[..offset].encode_utf16().count()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(text : &str) -> StoredComment {
+        StoredComment::new(Uuid::from_u128(1), "Author", text).unwrap()
+    }
+
+    #[test]
+    fn anchors_follow_each_hunk() {
+        let (old, new) = ("one two three four", "1 two 3 four");
+        let mut comments = CommentThreads::default();
+        let two  = comments.create(0, old, 4, 7, comment("two")).unwrap();
+        let four = comments.create(0, old, 14, 18, comment("four")).unwrap();
+        let other_file = comments.create(1, old, 4, 7, comment("other")).unwrap();
+        comments.apply_edit(0, &TextEdit::between(old, new));
+        let range = |thread_id| comments.get(thread_id).map(|thread| &new[thread.start..thread.end]);
+        assert_eq!(range(two), Some("two"));
+        assert_eq!(range(four), Some("four"));
+        assert_eq!(comments.get(other_file).map(|thread| (thread.start, thread.end)), Some((4, 7)));
+    }
+
+    #[test]
+    fn anchor_on_removed_text_collapses() {
+        let (old, new) = ("keep drop keep", "keep  keep");
+        let mut comments = CommentThreads::default();
+        let thread_id = comments.create(0, old, 5, 9, comment("drop")).unwrap();
+        comments.apply_edit(0, &TextEdit::between(old, new));
+        let thread = comments.get(thread_id).unwrap();
+        assert_eq!((thread.start, thread.end), (5, 5));
+    }
+
+    #[test]
+    fn threads_are_capped() {
+        let mut comments = CommentThreads::default();
+        for _ in 0..MAX_COMMENT_THREADS {
+            assert!(comments.create(0, "text", 0, 4, comment("comment")).is_some());
+        }
+        assert!(comments.create(0, "text", 0, 4, comment("comment")).is_none());
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut comments = CommentThreads::default();
+        let thread_id = comments.create(3, "héllo", 1, 5, comment("first")).unwrap();
+        comments.reply(thread_id, comment("second"));
+        comments.set_resolved(thread_id, true);
+        let decoded = CommentThreads::from_bytes(&comments.to_bytes()).unwrap();
+        let thread  = decoded.get(thread_id).unwrap();
+        assert_eq!((thread.file_id, thread.start, thread.end, thread.resolved), (3, 1, 6, true));
+        assert_eq!(thread.comments.iter().map(|comment| comment.text.as_str()).collect::<Vec<_>>(), vec![ "first", "second" ]);
+        assert!(! decoded.unsaved);
+        assert!(CommentThreads::from_bytes(&[0; 4]).is_none());
+    }
+
+    #[test]
+    fn changes_are_unsaved() {
+        let mut comments = CommentThreads::default();
+        assert!(! comments.unsaved);
+        let thread_id = comments.create(0, "text", 0, 4, comment("comment")).unwrap();
+        assert!(comments.unsaved);
+        comments.unsaved = false;
+        comments.apply_edit(0, &TextEdit::between("text", "text!"));
+        assert!(! comments.unsaved);
+        comments.apply_edit(0, &TextEdit::between("text!", "more text!"));
+        assert!(comments.unsaved);
+        comments.unsaved = false;
+        assert!(! comments.set_resolved(thread_id, false));
+        assert!(! comments.unsaved);
+    }
+
+    #[test]
+    fn utf16_offsets() {
+        let text = "a😀b";
+        assert_eq!(utf16_to_byte(text, 3), 5);
+        assert_eq!(byte_to_utf16(text, 5), 3);
+        assert_eq!(byte_to_utf16(text, 2), 1);
+    }
+
+}
@@ -1,10 +1,15 @@
 use crate::peer::OutgoingPeerCommand;
+use crate::audit::{ AuditEntry, AuditEvent };
+use crate::metrics::METRICS;
 use lighthousemc_editor_common::packet::s2c::*;
+use lighthousemc_editor_common::packet::c2s::{ SearchC2SPacket, EditorAction, CommentAction };
 use lighthousemc_editor_common::dmp;
-use lighthousemc_database::{ LighthouseDB, DBPlotID, DBFSFileID, DBError };
+use lighthousemc_database::{ LighthouseDB, DBPlotID, DBFSDirectoryID, DBFSFileID, DBError };
 use axecs::prelude::*;
 use std::sync::Arc;
+use std::borrow::Cow;
 use std::collections::VecDeque;
+use std::time::SystemTime;
 use uuid::Uuid;
 
 
@@ -14,30 +19,161 @@ use session::*;
 mod state;
 pub use state::*;
 
+mod search;
+
+mod blame;
+pub use blame::*;
+
+mod chat;
+pub use chat::{ CHAT_HISTORY_LEN, MAX_CHAT_MESSAGE_LEN };
+
+mod comments;
+pub use comments::*;
+
+mod suggestions;
+pub use suggestions::*;
+
 
 #[derive(Component)]
 pub struct EditorInstance {
-                plot_id : DBPlotID,
-    pub(crate)  state   : EditorInstanceState,
-                events  : VecDeque<EditorInstanceEvent>
+                plot_id       : DBPlotID,
+    pub(crate)  state         : EditorInstanceState,
+                events        : VecDeque<EditorInstanceEvent>,
+                host_events   : VecDeque<EditorHostEvent>,
+                audit_entries : VecDeque<AuditEntry>,
+                chat_history  : VecDeque<ChatMessage<'static>>,
+                comments      : CommentThreads,
+                suggestions   : Suggestions
 }
 
 impl EditorInstance {
 
+    /// Prefer [`EditorRegistry::open_instance`](crate::registry::EditorRegistry::open_instance), which checks the first invariant.
+    ///
     /// # Safety:
     /// The plot must not be managed by any other editor instance.
     /// The plot must be locked and unlocked properly, preventing management conflicts with other nodes.
+    /// The [lifecycle manager](crate::lifecycle) does this with [leases](crate::lease), if it is given them.
     pub async unsafe fn create(plot_id : DBPlotID, database : Arc<LighthouseDB>) -> Result<Option<Self>, DBError> {
         Ok(Some(Self {
             plot_id,
-            state   : { let Some(state) = EditorInstanceState::load(&database, plot_id).await? else { return Ok(None); }; state },
-            events  : VecDeque::new()
+            state         : { let Some(state) = EditorInstanceState::load(&database, plot_id).await? else { return Ok(None); }; state },
+            events        : VecDeque::new(),
+            host_events   : VecDeque::new(),
+            audit_entries : VecDeque::new(),
+            chat_history  : VecDeque::new(),
+            comments      : CommentThreads::default(),
+            suggestions   : Suggestions::default()
         }))
     }
 
 
+    #[cfg(test)]
+    pub(crate) fn with_state(plot_id : DBPlotID, state : EditorInstanceState) -> Self { Self {
+        plot_id,
+        state,
+        events        : VecDeque::new(),
+        host_events   : VecDeque::new(),
+        audit_entries : VecDeque::new(),
+        chat_history  : VecDeque::new(),
+        comments      : CommentThreads::default(),
+        suggestions   : Suggestions::default()
+    } }
+
+
     pub fn plot_id(&self) -> DBPlotID { self.plot_id }
 
+    pub fn state(&self) -> &EditorInstanceState { &self.state }
+
+    /// Whether any file or comment thread was changed since it was last saved.
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.state.has_unsaved_files() || self.has_unsaved_comments()
+    }
+
+    /// Replaces who last changed each line of a text file, eg. with one that was stored alongside the file.
+    ///
+    /// Returns `false` if the file does not exist.
+    pub fn set_file_blame(&mut self, file_id : DBFSFileID, blame : FileBlame) -> bool {
+        let Some(file) = self.state.files_mut().get_mut(&file_id) else { return false; };
+        file.set_blame(blame);
+        true
+    }
+
+    /// Takes the events that the host server should react to, oldest first.
+    pub fn drain_host_events(&mut self) -> impl Iterator<Item = EditorHostEvent> + '_ {
+        self.host_events.drain(..)
+    }
+
+    pub(crate) fn push_event(&mut self, event : EditorInstanceEvent) {
+        self.events.push_back(event);
+    }
+
+    /// Adds a file that the host created for a client. Returns `false` if it already exists or its directory does not.
+    pub fn create_file(&mut self, client_uuid : Uuid, client_name : &str, file_id : DBFSFileID, parent_dir : Option<DBFSDirectoryID>, fsname : String, data : Vec<u8>) -> bool {
+        if (! self.state.insert_file(file_id, parent_dir, fsname, data, (client_uuid, client_name))) { return false; }
+        let path = self.state.file_path(file_id).unwrap_or_default();
+        self.audit(client_uuid, client_name, AuditEvent::FileCreated { file_id, path });
+        self.push_event(EditorInstanceEvent::UpdateFileTree { deleted : None });
+        true
+    }
+
+    /// Renames a file or moves it to another directory for a client. Returns `false` if the file or directory does not exist.
+    pub fn move_file(&mut self, client_uuid : Uuid, client_name : &str, file_id : DBFSFileID, parent_dir : Option<DBFSDirectoryID>, fsname : String) -> bool {
+        let Some(from)       = self.state.file_path(file_id) else { return false; };
+        let Some(old_parent) = self.state.set_file_location(file_id, parent_dir, fsname) else { return false; };
+        let to = self.state.file_path(file_id).unwrap_or_default();
+        self.audit(client_uuid, client_name, if (old_parent == parent_dir) {
+            AuditEvent::FileRenamed { file_id, from, to }
+        } else {
+            AuditEvent::FileMoved { file_id, from, to }
+        });
+        self.push_event(EditorInstanceEvent::UpdateFileTree { deleted : None });
+        true
+    }
+
+    /// Removes a file that the host deleted for a client. Returns `false` if it does not exist.
+    pub fn delete_file(&mut self, client_uuid : Uuid, client_name : &str, file_id : DBFSFileID) -> bool {
+        let path = self.state.file_path(file_id);
+        if (self.state.remove_file(file_id).is_none()) { return false; }
+        self.audit(client_uuid, client_name, AuditEvent::FileDeleted { file_id, path : path.unwrap_or_default() });
+        self.push_event(EditorInstanceEvent::UpdateFileTree { deleted : Some(file_id) });
+        true
+    }
+
+    /// Records an entry in the audit log.
+    pub fn audit(&mut self, client_uuid : Uuid, client_name : &str, event : AuditEvent) {
+        self.audit_entries.push_back(AuditEntry {
+            timestamp   : SystemTime::now(),
+            plot_id     : self.plot_id,
+            client_uuid,
+            client_name : client_name.to_string(),
+            event
+        });
+    }
+
+    pub(crate) fn drain_audit_entries(&mut self) -> impl Iterator<Item = AuditEntry> + '_ {
+        self.audit_entries.drain(..)
+    }
+
+}
+
+
+pub enum EditorHostEvent {
+
+    /// A client asked for the plot to be saved or built.
+    ActionRequested {
+        client_uuid : Uuid,
+        client_name : String,
+        action      : EditorAction
+    },
+
+    /// A collaborator sent a message in the editor chat, which can be bridged to in-game chat.
+    ChatMessage {
+        client_uuid : Uuid,
+        client_name : String,
+        message     : String
+    }
+
 }
 
 
@@ -48,9 +184,67 @@ pub enum EditorInstanceEvent {
     },
 
     PatchFile {
+        /// The session that sent the patches, or nil if they were made by the server.
         client_uuid : Uuid,
+        /// Who the change is attributed to.
+        author_uuid : Uuid,
         file_id     : DBFSFileID,
         patches     : dmp::Patches<dmp::Efficient>
+    },
+
+    /// Patches from a session in suggestion mode, which change its suggestions rather than the file.
+    SuggestPatch {
+        client_uuid : Uuid,
+        client_name : String,
+        file_id     : DBFSFileID,
+        patches     : dmp::Patches<dmp::Efficient>
+    },
+
+    ReviewSuggestion {
+        client_uuid   : Uuid,
+        can_write     : bool,
+        suggestion_id : u64,
+        accept        : bool
+    },
+
+    UpdateViewport {
+        packet : ViewportS2CPacket
+    },
+
+    Chat {
+        message : ChatMessage<'static>
+    },
+
+    Comment {
+        client_uuid : Uuid,
+        client_name : String,
+        action      : CommentAction
+    },
+
+    /// A client joined, or changed file or idle state.
+    UpdatePresence {
+        packet : PresenceS2CPacket<'static>,
+        /// Whether the client just joined, and needs to be sent everyone else.
+        is_new : bool
+    },
+
+    RemovePresence {
+        client_uuid : Uuid
+    },
+
+    /// The contents of a file were replaced entirely, eg. by an upload.
+    OverwriteFile {
+        file_id : DBFSFileID
+    },
+
+    /// Files were created, moved or deleted.
+    UpdateFileTree {
+        deleted : Option<DBFSFileID>
+    },
+
+    Search {
+        client_uuid : Uuid,
+        search      : SearchC2SPacket
     }
 
 }
@@ -73,53 +267,334 @@ pub(super) async fn read_instance_events(
                 } }
             },
 
-            EditorInstanceEvent::PatchFile { client_uuid, file_id, patches } => {
-                if let Some(file) = instance.state.files_mut().get_mut(&file_id) {
-                    if let FileContents::Text(central_text) = file.contents_mut() {
-                        let dmp = dmp::DiffMatchPatch::new();
-                        // Apply patch to server text on a best-effort basis.
-                        if let Ok((new_central_text, _)) = dmp.patch_apply(&patches, &central_text) {
-                            *central_text = new_central_text.into();
-                            'iter_sessions : for session in &mut sessions { if (session.plot_id() == instance.plot_id) {
-                                let session_client_uuid = session.client_uuid();
-                                if let EditorSessionStep::Active { outgoing_commands_tx, state, .. } = session.session_step_mut() {
-                                    if let Some(shadow) = state.file_shadows_mut().get_mut(&file_id) {
-                                        if let FileShadowStep::Open = shadow.step() {
-                                            if let FileShadowContent::Text { text : shadow_text, .. } = shadow.content_mut() {
-                                                // Apply patches directly if this client is the source of the change.
-                                                if (session_client_uuid == client_uuid) {
-                                                    let dmp = dmp::DiffMatchPatch::new();
-                                                    if let Ok((new_shadow_text, _)) = dmp.patch_apply(&patches, shadow_text) {
-                                                        *shadow_text = new_shadow_text;
-                                                    }
-                                                }
-                                                // Server text is diffed against the server shadow.
-                                                if let Ok(diffs) = dmp.diff_main(shadow_text, &central_text) {
-                                                    if let Ok(patches_to_client) = dmp.patch_make(dmp::PatchInput::new_diffs(&diffs)) {
-                                                        *shadow_text = central_text.to_string();
-                                                        let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::PatchFile(PatchFileS2CPacket {
-                                                            file_id,
-                                                            patches : patches_to_client
-                                                        })));
-                                                        continue 'iter_sessions;
-                                                    }
-                                                }
-                                                *shadow_text = central_text.to_string();
-                                            }
-                                            // Failed to merge changes, resend file.
-                                            let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::OvewriteFile(OverwriteFileS2CPacket {
-                                                file_id,
-                                                contents : FileContents::Text(central_text.to_string().into())
-                                            })));
-                                        }
-                                    }
+            EditorInstanceEvent::Chat { message } => {
+                let packet = ChatS2CPacket { is_history : false, messages : vec![ message ].into() };
+                for session in &sessions { if (session.plot_id() == instance.plot_id) {
+                    if let EditorSessionStep::Active { outgoing_commands_tx, .. } = session.session_step() {
+                        let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Chat(packet.clone())));
+                    }
+                } }
+            },
+
+            EditorInstanceEvent::Comment { client_uuid, client_name, action } => {
+                let thread_id = match (action) {
+                    CommentAction::Create { file_id, start, end, text } => {
+                        let Some(comment) = StoredComment::new(client_uuid, &client_name, &text) else { continue; };
+                        let Some(FileContents::Text(file_text)) = instance.state.files().get(&file_id).map(|file| file.contents().clone()) else { continue; };
+                        let Some(thread_id) = instance.comments.create(file_id, &file_text, start, end, comment) else { continue; };
+                        thread_id
+                    },
+                    CommentAction::Reply { thread_id, text } => {
+                        let Some(comment) = StoredComment::new(client_uuid, &client_name, &text) else { continue; };
+                        if (! instance.comments.reply(thread_id, comment)) { continue; }
+                        thread_id
+                    },
+                    CommentAction::SetResolved { thread_id, resolved } => {
+                        if (! instance.comments.set_resolved(thread_id, resolved)) { continue; }
+                        thread_id
+                    }
+                };
+                let packet = instance.comments.to_packet([thread_id], &instance.state, false);
+                for session in &sessions { if (session.plot_id() == instance.plot_id) {
+                    if let EditorSessionStep::Active { outgoing_commands_tx, .. } = session.session_step() {
+                        let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::CommentThreads(packet.clone())));
+                    }
+                } }
+            },
+
+            EditorInstanceEvent::UpdateViewport { packet } => {
+                for session in &sessions { if (session.plot_id() == instance.plot_id && session.client_uuid() != packet.client_uuid) {
+                    if let EditorSessionStep::Active { outgoing_commands_tx, .. } = session.session_step() {
+                        let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Viewport(packet.clone())));
+                    }
+                } }
+            },
+
+            EditorInstanceEvent::UpdatePresence { packet, is_new } => {
+                // Everyone else's presence and viewport, so that the new client can follow them straight away.
+                let others = if (is_new) {
+                    sessions.iter().filter(|session| session.plot_id() == instance.plot_id && session.client_uuid() != packet.client_uuid).filter_map(|session| {
+                        let EditorSessionStep::Active { state, .. } = session.session_step() else { return None; };
+                        Some((session.presence_packet()?, ViewportS2CPacket { client_uuid : session.client_uuid(), viewport : state.viewport() }))
+                    }).collect::<Vec<_>>()
+                } else { Vec::new() };
+                for session in &sessions { if (session.plot_id() == instance.plot_id) {
+                    if let EditorSessionStep::Active { outgoing_commands_tx, .. } = session.session_step() {
+                        if (session.client_uuid() != packet.client_uuid) {
+                            let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Presence(packet.clone())));
+                        } else {
+                            for (presence, viewport) in &others {
+                                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Presence(presence.clone())));
+                                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Viewport(viewport.clone())));
+                            }
+                        }
+                    }
+                } }
+            },
+
+            EditorInstanceEvent::RemovePresence { client_uuid } => {
+                for session in &sessions { if (session.plot_id() == instance.plot_id && session.client_uuid() != client_uuid) {
+                    if let EditorSessionStep::Active { outgoing_commands_tx, .. } = session.session_step() {
+                        let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::PresenceLeave(PresenceLeaveS2CPacket { client_uuid })));
+                    }
+                } }
+            },
+
+            EditorInstanceEvent::PatchFile { client_uuid, author_uuid, file_id, patches } => {
+                let path        = instance.state.file_path(file_id).unwrap_or_default();
+                let author_name = sessions.iter().find(|session| session.plot_id() == instance.plot_id && session.client_uuid() == author_uuid).map(|session| session.client_name().to_string()).unwrap_or_default();
+                let mut audit_entry = None;
+                let mut text_edit   = None;
+                let had_suggestions = instance.suggestions.has_file(file_id);
+                let Some((central_text, blame)) = instance.state.files_mut().get_mut(&file_id).and_then(|file| file.text_and_blame_mut()) else { continue; };
+                let dmp = dmp::DiffMatchPatch::new();
+                // Apply patch to server text on a best-effort basis.
+                let Ok((new_central_text, applied)) = dmp.patch_apply(&patches, &central_text) else { METRICS.patch_failure(); continue; };
+                if (applied.iter().any(|applied| ! applied)) { METRICS.patch_failure(); }
+                let edit = TextEdit::between(central_text, &new_central_text);
+                if (! edit.is_empty()) {
+                    let inserted = edit.inserted_chars(&new_central_text);
+                    let deleted  = edit.deleted_chars(central_text);
+                    blame.apply_edit(central_text, &new_central_text, &edit, author_uuid, &author_name);
+                    audit_entry = Some(AuditEvent::FileEdited { file_id, path, inserted, deleted });
+                    text_edit   = Some(edit);
+                }
+                *central_text = new_central_text.into();
+                if let Some(edit) = text_edit {
+                    if let Some(file) = instance.state.files_mut().get_mut(&file_id) { file.mark_unsaved(); }
+                    instance.comments.apply_edit(file_id, &edit);
+                    if (had_suggestions) {
+                        if let Some(FileContents::Text(new_central_text)) = instance.state.files().get(&file_id).map(|file| file.contents().clone()) {
+                            instance.suggestions.apply_edit(file_id, &edit, &new_central_text);
+                        }
+                    }
+                }
+                sync_text_file(&mut sessions, &*instance, file_id, Some((client_uuid, &patches)));
+                if (had_suggestions) {
+                    send_suggestions(&mut sessions, &*instance, file_id);
+                }
+                if let Some(event) = audit_entry {
+                    instance.audit(author_uuid, &author_name, event);
+                    if let Some(file) = instance.state.files().get(&file_id) {
+                        send_blame(&mut sessions, instance.plot_id, file_id, file);
+                    }
+                }
+            },
+
+            EditorInstanceEvent::SuggestPatch { client_uuid, client_name, file_id, patches } => {
+                let Some(FileContents::Text(central_text)) = instance.state.files().get(&file_id).map(|file| file.contents().clone()) else { continue; };
+                // The client's shadow is its view of the file, so the suggestions are whatever it differs by once patched.
+                let mut proposed_text = None;
+                for session in &mut sessions { if (session.plot_id() == instance.plot_id && session.client_uuid() == client_uuid) {
+                    if let EditorSessionStep::Active { state, .. } = session.session_step_mut() {
+                        if let Some(FileShadowContent::Text { text : shadow_text, .. }) = state.file_shadows_mut().get_mut(&file_id).map(|shadow| shadow.content_mut()) {
+                            let dmp = dmp::DiffMatchPatch::new();
+                            if let Ok((new_shadow_text, _)) = dmp.patch_apply(&patches, shadow_text) {
+                                *shadow_text = new_shadow_text;
+                            }
+                            proposed_text = Some(shadow_text.clone());
+                        }
+                    }
+                    break;
+                } }
+                let Some(proposed_text) = proposed_text else { continue; };
+                instance.suggestions.update(file_id, client_uuid, &client_name, &central_text, &proposed_text);
+                sync_text_file(&mut sessions, &*instance, file_id, None);
+                send_suggestions(&mut sessions, &*instance, file_id);
+            },
+
+            EditorInstanceEvent::ReviewSuggestion { client_uuid, can_write, suggestion_id, accept } => {
+                let Some(suggestion) = instance.suggestions.get(suggestion_id) else { continue; };
+                if (! (can_write || (suggestion.author_uuid == client_uuid && ! accept))) { continue; }
+                if (accept && suggestion.stale) { continue; }
+                let Some(suggestion) = instance.suggestions.remove(suggestion_id) else { continue; };
+                if (accept) {
+                    // Accepted suggestions go through the normal merge path, attributed to whoever suggested them.
+                    let Some(FileContents::Text(central_text)) = instance.state.files().get(&suggestion.file_id).map(|file| file.contents()) else { continue; };
+                    if let Some(patches) = Suggestions::patches_for(&suggestion, central_text) {
+                        instance.events.push_front(EditorInstanceEvent::PatchFile {
+                            client_uuid : Uuid::nil(),
+                            author_uuid : suggestion.author_uuid,
+                            file_id     : suggestion.file_id,
+                            patches
+                        });
+                    }
+                } else {
+                    // The suggester no longer sees the change.
+                    sync_text_file(&mut sessions, &*instance, suggestion.file_id, None);
+                }
+                send_suggestions(&mut sessions, &*instance, suggestion.file_id);
+            },
+
+            EditorInstanceEvent::OverwriteFile { file_id } => {
+                // Suggestions can not be carried over to entirely new contents.
+                if (instance.suggestions.clear_file(file_id)) {
+                    send_suggestions(&mut sessions, &*instance, file_id);
+                }
+                let Some(file) = instance.state.files().get(&file_id) else { continue; };
+                for session in &mut sessions { if (session.plot_id() == instance.plot_id) {
+                    if let EditorSessionStep::Active { outgoing_commands_tx, state, .. } = session.session_step_mut() {
+                        if let Some(shadow) = state.file_shadows_mut().get_mut(&file_id) {
+                            if let FileShadowStep::Open = shadow.step() {
+                                *shadow.content_mut() = FileShadowContent::from(file.contents());
+                                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::OvewriteFile(OverwriteFileS2CPacket {
+                                    file_id,
+                                    contents : file.contents().clone()
+                                })));
+                            }
+                        }
+                    }
+                } }
+                send_blame(&mut sessions, instance.plot_id, file_id, file);
+            },
+
+            EditorInstanceEvent::UpdateFileTree { deleted } => {
+                if let Some(file_id) = deleted {
+                    instance.suggestions.clear_file(file_id);
+                }
+                for session in &mut sessions { if (session.plot_id() == instance.plot_id) {
+                    if let EditorSessionStep::Active { outgoing_commands_tx, state, .. } = session.session_step_mut() {
+                        if let Some(file_id) = deleted {
+                            if (state.file_shadows_mut().remove(&file_id).is_some()) {
+                                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::CloseFile(CloseFileS2CPacket { file_id })));
+                            }
+                        }
+                        let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::InitialState(instance.state.to_initial_state_packet())));
+                    }
+                } }
+            },
+
+            EditorInstanceEvent::Search { client_uuid, search } => {
+                let Some(outgoing_commands_tx) = sessions.iter().find_map(|session| {
+                    if (session.plot_id() == instance.plot_id && session.client_uuid() == client_uuid) {
+                        if let EditorSessionStep::Active { outgoing_commands_tx, .. } = session.session_step() {
+                            return Some(outgoing_commands_tx.clone());
+                        }
+                    }
+                    None
+                }) else { continue; };
+                let results = match (search::SearchQuery::new(&search.query, search.options, &search.globs)) {
+                    Ok(query) => query.run(&instance.state, search.replace.as_deref()),
+                    Err(err)  => {
+                        let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::SearchDone(SearchDoneS2CPacket {
+                            search_id     : search.search_id,
+                            total_matches : 0,
+                            replaced      : false,
+                            error         : Some(err.into())
+                        })));
+                        continue;
+                    }
+                };
+                let mut total_matches = 0;
+                for result in results {
+                    total_matches += result.match_count;
+                    let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::SearchResults(SearchResultsS2CPacket {
+                        search_id : search.search_id,
+                        file_id   : result.file_id,
+                        matches   : result.matches.into()
+                    })));
+                    // Replacements are applied as patches from nobody, so that every session is sent the change.
+                    // They are still attributed to the client that made them.
+                    if let Some(replaced_text) = result.replaced_text {
+                        if let Some(FileContents::Text(central_text)) = instance.state.files().get(&result.file_id).map(|file| file.contents()) {
+                            let dmp = dmp::DiffMatchPatch::new();
+                            if let Ok(diffs) = METRICS.time_diff(|| dmp.diff_main::<dmp::Efficient>(central_text, &replaced_text)) {
+                                if let Ok(patches) = dmp.patch_make(dmp::PatchInput::new_diffs(&diffs)) {
+                                    instance.events.push_front(EditorInstanceEvent::PatchFile {
+                                        client_uuid : Uuid::nil(),
+                                        author_uuid : client_uuid,
+                                        file_id     : result.file_id,
+                                        patches
+                                    });
                                 }
-                            } }
+                            }
                         }
                     }
                 }
+                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::SearchDone(SearchDoneS2CPacket {
+                    search_id     : search.search_id,
+                    total_matches : total_matches as u32,
+                    replaced      : search.replace.is_some(),
+                    error         : None
+                })));
             }
 
         } }
     }
 }
+
+
+
+/// Sends every session that has a text file open the changes between what it was last sent and what it should now see.
+/// If `source` is given, its patches are applied to that session's shadow first, as the client already made them.
+fn sync_text_file(sessions : &mut Entities<(&mut EditorSession)>, instance : &EditorInstance, file_id : DBFSFileID, source : Option<(Uuid, &dmp::Patches<dmp::Efficient>)>) {
+    let Some(FileContents::Text(central_text)) = instance.state.files().get(&file_id).map(|file| file.contents()) else { return; };
+    let dmp = dmp::DiffMatchPatch::new();
+    'iter_sessions : for session in sessions { if (session.plot_id() == instance.plot_id) {
+        let session_client_uuid = session.client_uuid();
+        if let EditorSessionStep::Active { outgoing_commands_tx, state, .. } = session.session_step_mut() {
+            // Clients that are suggesting see their own suggestions applied.
+            let target_text = if (state.suggesting()) { instance.suggestions.view(file_id, session_client_uuid, central_text) } else { Cow::Borrowed(&**central_text) };
+            if let Some(shadow) = state.file_shadows_mut().get_mut(&file_id) {
+                if let FileShadowStep::Open = shadow.step() {
+                    if let FileShadowContent::Text { text : shadow_text, .. } = shadow.content_mut() {
+                        // Apply patches directly if this client is the source of the change.
+                        if let Some((client_uuid, patches)) = source {
+                            if (session_client_uuid == client_uuid) {
+                                if let Ok((new_shadow_text, _)) = dmp.patch_apply(patches, shadow_text) {
+                                    *shadow_text = new_shadow_text;
+                                }
+                            }
+                        }
+                        if (*shadow_text == *target_text) { continue 'iter_sessions; }
+                        // Server text is diffed against the server shadow.
+                        if let Ok(diffs) = METRICS.time_diff(|| dmp.diff_main::<dmp::Efficient>(shadow_text, &target_text)) {
+                            if let Ok(patches_to_client) = dmp.patch_make(dmp::PatchInput::new_diffs(&diffs)) {
+                                *shadow_text = target_text.to_string();
+                                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::PatchFile(PatchFileS2CPacket {
+                                    file_id,
+                                    patches : patches_to_client
+                                })));
+                                continue 'iter_sessions;
+                            }
+                        }
+                        *shadow_text = target_text.to_string();
+                    }
+                    // Failed to merge changes, resend file.
+                    METRICS.overwrite_fallback();
+                    let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::OvewriteFile(OverwriteFileS2CPacket {
+                        file_id,
+                        contents : FileContents::Text(target_text.into_owned().into())
+                    })));
+                }
+            }
+        }
+    } }
+}
+
+/// Sends the suggestions on a file to every session that has it open.
+fn send_suggestions(sessions : &mut Entities<(&mut EditorSession)>, instance : &EditorInstance, file_id : DBFSFileID) {
+    let Some(FileContents::Text(central_text)) = instance.state.files().get(&file_id).map(|file| file.contents()) else { return; };
+    for session in sessions { if (session.plot_id() == instance.plot_id) {
+        let session_client_uuid = session.client_uuid();
+        if let EditorSessionStep::Active { outgoing_commands_tx, state, .. } = session.session_step_mut() {
+            if (state.file_shadows_mut().get(&file_id).is_some_and(|shadow| matches!(shadow.step(), FileShadowStep::Open))) {
+                let packet = instance.suggestions.to_packet(file_id, state.suggesting().then_some(session_client_uuid), central_text);
+                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Suggestions(packet)));
+            }
+        }
+    } }
+}
+
+/// Sends the blame of a file to every session that has it open and wants to see it.
+fn send_blame(sessions : &mut Entities<(&mut EditorSession)>, plot_id : DBPlotID, file_id : DBFSFileID, file : &StateFile) {
+    let mut packet = None;
+    for session in sessions { if (session.plot_id() == plot_id) {
+        if let EditorSessionStep::Active { outgoing_commands_tx, state, .. } = session.session_step_mut() {
+            if (state.blame_visible() && state.file_shadows_mut().get(&file_id).is_some_and(|shadow| matches!(shadow.step(), FileShadowStep::Open))) {
+                let packet = packet.get_or_insert_with(|| file.blame().to_packet(file_id)).clone();
+                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Blame(packet)));
+            }
+        }
+    } }
+}
@@ -0,0 +1,253 @@
+use super::EditorInstanceState;
+use lighthousemc_editor_common::packet::s2c::{ SearchMatch, FileContents };
+use lighthousemc_editor_common::packet::c2s::SearchOptions;
+use lighthousemc_database::DBFSFileID;
+use regex::{ Regex, RegexBuilder };
+
+
+/// Matches beyond this are still counted and replaced, but not sent to the client.
+const MAX_REPORTED_MATCHES : usize = 2500;
+
+/// Longer lines are shortened around the match in previews.
+const MAX_PREVIEW_LEN : usize = 160;
+
+
+pub(crate) struct SearchQuery {
+    pattern : Regex,
+    regex   : bool,
+    include : Vec<Regex>,
+    exclude : Vec<Regex>
+}
+
+pub(crate) struct FileSearchResult {
+    pub file_id       : DBFSFileID,
+    pub match_count   : usize,
+    pub matches       : Vec<SearchMatch<'static>>,
+    /// The new contents of the file, if matches were replaced.
+    pub replaced_text : Option<String>
+}
+
+
+impl SearchQuery {
+
+    pub(crate) fn new(query : &str, options : SearchOptions, globs : &str) -> Result<Self, String> {
+        if (query.is_empty()) {
+            return Err("Empty search query".into());
+        }
+        let mut pattern = if (options.regex) { query.to_string() } else { regex::escape(query) };
+        if (options.whole_word) {
+            pattern = format!(r"\b(?:{})\b", pattern);
+        }
+        let pattern = RegexBuilder::new(&pattern)
+            .case_insensitive(! options.case_sensitive)
+            .multi_line(true)
+            .size_limit(1 << 20)
+            .build()
+            .map_err(|err| err.to_string())?;
+        let mut include = Vec::new();
+        let mut exclude = Vec::new();
+        for glob in globs.split(',').map(|glob| glob.trim()).filter(|glob| ! glob.is_empty()) {
+            if let Some(glob) = glob.strip_prefix('!') {
+                exclude.push(glob_to_regex(glob)?);
+            } else {
+                include.push(glob_to_regex(glob)?);
+            }
+        }
+        Ok(Self { pattern, regex : options.regex, include, exclude })
+    }
+
+    fn matches_path(&self, path : &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|glob| glob.is_match(path)))
+            && (! self.exclude.iter().any(|glob| glob.is_match(path)))
+    }
+
+    /// Searches every text file in the plot, returning the files with at least one match.
+    pub(crate) fn run(&self, state : &EditorInstanceState, replace : Option<&str>) -> Vec<FileSearchResult> {
+        let mut results  = Vec::new();
+        let mut reported = 0;
+        for (&file_id, file) in state.files() {
+            let FileContents::Text(text) = file.contents() else { continue; };
+            let Some(path) = state.file_path(file_id) else { continue; };
+            if (! self.matches_path(&path)) { continue; }
+            results.extend(self.search_text(file_id, text, replace, &mut reported));
+        }
+        results
+    }
+
+    /// Searches one text, or returns `None` if nothing matched. Matches are only reported while `reported` is under the limit.
+    fn search_text(&self, file_id : DBFSFileID, text : &str, replace : Option<&str>, reported : &mut usize) -> Option<FileSearchResult> {
+        let mut match_count   = 0;
+        let mut matches       = Vec::new();
+        let mut line          = 0;
+        let mut line_from     = 0;
+        let mut replaced_text = replace.map(|_| String::with_capacity(text.len()));
+        let mut replaced_to   = 0;
+        for captures in self.pattern.captures_iter(text) {
+            let found = captures.get(0).unwrap();
+            // Empty matches are neither reported nor replaced.
+            if (found.is_empty()) { continue; }
+            match_count += 1;
+            if (*reported < MAX_REPORTED_MATCHES) {
+                line     += text[line_from..found.start()].matches('\n').count();
+                line_from = found.start();
+                matches.push(make_match(text, line, found.start(), found.end()));
+                *reported += 1;
+            }
+            if let (Some(replaced_text), Some(replace)) = (&mut replaced_text, replace) {
+                replaced_text.push_str(&text[replaced_to..found.start()]);
+                if (self.regex) { captures.expand(replace, replaced_text); }
+                else { replaced_text.push_str(replace); }
+                replaced_to = found.end();
+            }
+        }
+        if (match_count == 0) { return None; }
+        if let Some(replaced_text) = &mut replaced_text {
+            replaced_text.push_str(&text[replaced_to..]);
+        }
+        Some(FileSearchResult { file_id, match_count, matches, replaced_text })
+    }
+
+}
+
+
+fn make_match(text : &str, line : usize, start : usize, end : usize) -> SearchMatch<'static> {
+    let line_start_byte     = text[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end_byte       = text[start..].find('\n').map_or(text.len(), |i| start + i);
+    let end_line_start_byte = text[..end].rfind('\n').map_or(0, |i| i + 1);
+
+    // Shorten long lines, keeping the start of the match in view.
+    let mut preview_from = line_start_byte;
+    if (start - line_start_byte > MAX_PREVIEW_LEN / 2) {
+        preview_from = floor_char_boundary(text, start - MAX_PREVIEW_LEN / 2);
+    }
+    let preview_to  = floor_char_boundary(text, line_end_byte.min(preview_from + MAX_PREVIEW_LEN));
+    let preview_end = end.min(line_end_byte).min(preview_to).max(start);
+
+    SearchMatch {
+        line          : line as u32,
+        column_start  : text[line_start_byte..start].encode_utf16().count() as u32,
+        line_end      : (line + text[start..end].matches('\n').count()) as u32,
+        column_end    : text[end_line_start_byte..end].encode_utf16().count() as u32,
+        preview       : text[preview_from..preview_to].to_string().into(),
+        preview_start : (start - preview_from) as u32,
+        preview_end   : (preview_end - preview_from) as u32
+    }
+}
+
+fn floor_char_boundary(text : &str, mut index : usize) -> usize {
+    while (! text.is_char_boundary(index)) { index -= 1; }
+    index
+}
+
+
+/// Converts a path glob to an anchored regex.
+///
+/// `**` matches across directories, `*` and `?` do not. Globs without a `/` match the file name in any directory.
+fn glob_to_regex(glob : &str) -> Result<Regex, String> {
+    let mut pattern = String::from("^");
+    if (! glob.contains('/')) {
+        pattern += "(?:.*/)?";
+    }
+    let mut chars = glob.trim_start_matches('/').chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch) {
+            '*' if (chars.peek() == Some(&'*')) => {
+                chars.next();
+                if (chars.peek() == Some(&'/')) {
+                    chars.next();
+                    pattern += "(?:.*/)?";
+                } else {
+                    pattern += ".*";
+                }
+            },
+            '*' => { pattern += "[^/]*"; },
+            '?' => { pattern += "[^/]"; },
+            ch  => { pattern += &regex::escape(&ch.to_string()); }
+        }
+    }
+    pattern += "$";
+    Regex::new(&pattern).map_err(|err| err.to_string())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(query : &str, regex : bool, case_sensitive : bool, whole_word : bool) -> SearchQuery {
+        SearchQuery::new(query, SearchOptions { regex, case_sensitive, whole_word }, "").unwrap()
+    }
+
+    fn search(query : &SearchQuery, text : &str, replace : Option<&str>) -> Option<FileSearchResult> {
+        query.search_text(0, text, replace, &mut 0)
+    }
+
+    #[test]
+    fn literal_queries_are_escaped() {
+        let query = query("a.b", false, false, false);
+        assert!(search(&query, "axb", None).is_none());
+        assert_eq!(search(&query, "a.b A.B", None).map(|result| result.match_count), Some(2));
+    }
+
+    #[test]
+    fn case_and_whole_word_options() {
+        assert_eq!(search(&query("Foo", false, true, false), "foo Foo food", None).map(|result| result.match_count), Some(1));
+        assert_eq!(search(&query("foo", false, false, true), "foo Foo food", None).map(|result| result.match_count), Some(2));
+    }
+
+    #[test]
+    fn only_regex_replacements_expand_groups() {
+        let regex = search(&query(r"(\w+)=(\w+)", true, true, false), "a=b c=d", Some("$2=$1")).unwrap();
+        assert_eq!(regex.replaced_text.as_deref(), Some("b=a d=c"));
+        let literal = search(&query("a=b", false, true, false), "a=b", Some("$1")).unwrap();
+        assert_eq!(literal.replaced_text.as_deref(), Some("$1"));
+    }
+
+    #[test]
+    fn empty_matches_are_not_replaced() {
+        let result = search(&query("a*", true, true, false), "baab", Some("x")).unwrap();
+        assert_eq!(result.match_count, 1);
+        assert_eq!(result.replaced_text.as_deref(), Some("bxb"));
+        assert!(search(&query("^", true, true, false), "ab", Some("x")).is_none());
+    }
+
+    #[test]
+    fn matches_have_lines_and_utf16_columns() {
+        let result = search(&query("bar", false, true, false), "é\nfoo bar\nbar", None).unwrap();
+        let found  = result.matches.iter().map(|found| (found.line, found.column_start, found.column_end, &*found.preview)).collect::<Vec<_>>();
+        assert_eq!(found, [(1, 4, 7, "foo bar"), (2, 0, 3, "bar")]);
+        let multi_line = search(&query("o\\nb", true, true, false), "foo\nbar", None).unwrap();
+        assert_eq!((multi_line.matches[0].line, multi_line.matches[0].line_end, multi_line.matches[0].column_end), (0, 1, 1));
+    }
+
+    #[test]
+    fn reported_matches_are_limited() {
+        let     text     = "x".repeat(MAX_REPORTED_MATCHES + 10);
+        let mut reported = 0;
+        let     result   = query("x", false, true, false).search_text(0, &text, None, &mut reported).unwrap();
+        assert_eq!(result.match_count, MAX_REPORTED_MATCHES + 10);
+        assert_eq!(result.matches.len(), MAX_REPORTED_MATCHES);
+        assert!(query("x", false, true, false).search_text(0, "x", None, &mut reported).is_some_and(|result| result.matches.is_empty()));
+    }
+
+    #[test]
+    fn path_globs() {
+        let options = SearchOptions { regex : false, case_sensitive : false, whole_word : false };
+        let query   = SearchQuery::new("x", options, "*.js, src/**/*.ts, !test/**").unwrap();
+        assert!(query.matches_path("main.js"));
+        assert!(query.matches_path("lib/main.js"));
+        assert!(! query.matches_path("main.jsx"));
+        assert!(query.matches_path("src/a.ts"));
+        assert!(query.matches_path("src/deep/er/a.ts"));
+        assert!(! query.matches_path("other/a.ts"));
+        assert!(! query.matches_path("test/main.js"));
+    }
+
+    #[test]
+    fn bad_queries_are_refused() {
+        let options = SearchOptions { regex : true, case_sensitive : false, whole_word : false };
+        assert!(SearchQuery::new("", options, "").is_err());
+        assert!(SearchQuery::new("(", options, "").is_err());
+    }
+
+}
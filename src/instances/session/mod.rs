@@ -1,11 +1,16 @@
 use crate::peer::{ OutgoingPeerCommand, IncomingPeerEvent };
-use super::{ EditorInstance, EditorInstanceEvent };
+use crate::peer::guard::MIN_SESSION_CODE_BITS;
+use super::{ EditorInstance, EditorInstanceEvent, EditorHostEvent };
+use crate::audit::AuditEvent;
+use crate::metrics::METRICS;
 use lighthousemc_editor_common::packet::s2c::*;
 use lighthousemc_editor_common::packet::c2s::*;
 use lighthousemc_database::DBPlotID;
 use voxidian_logger::debug;
 use axecs::prelude::*;
-use std::time::{ Instant, Duration };
+use std::time::{ Instant, SystemTime, Duration };
+use std::net::SocketAddr;
+use std::borrow::Cow;
 use tokio::sync::mpsc;
 use openssl::rand::rand_priv_bytes;
 use uuid::Uuid;
@@ -24,10 +29,21 @@ pub struct EditorSession {
 
     session_code : String,
     session_step : EditorSessionStep,
+    permission   : EditorPermission,
 
     closed       : u8
 }
 
+/// What a session may do to the files of its plot.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum EditorPermission {
+    /// Edit files directly, and accept or reject suggestions.
+    #[default]
+    Write,
+    /// Only propose edits, which are held as suggestions until someone with write access accepts them.
+    Suggest
+}
+
 pub(crate) enum EditorSessionStep {
     Pending {
         expires_at : Instant
@@ -42,30 +58,36 @@ pub(crate) enum EditorSessionStep {
 
 impl EditorSession {
 
+    /// Prefer [`EditorRegistry::open_session`](crate::registry::EditorRegistry::open_session), which checks these invariants.
+    ///
     /// # SAFETY:
     /// Client uuid and the plot together must be unique.
     /// The plot must be managed by an editor instance.
+    ///
+    /// Fails if a random code of `SESSION_CODE_LEN` characters would have less than [`MIN_SESSION_CODE_BITS`] of entropy.
     pub unsafe fn create<const SESSION_CODE_LEN : usize>(
         plot_id     : DBPlotID,
         client_uuid : Uuid,
         client_name : String,
         expires_in  : Duration
     ) -> Result<Self, ()> {
-        let mut sesssion_code = [0; SESSION_CODE_LEN];
-        let Ok(_) = rand_priv_bytes(&mut sesssion_code) else { return Err(()); };
+        // Each random character has 6 bits.
+        if ((SESSION_CODE_LEN as u32) * 6 < MIN_SESSION_CODE_BITS) { return Err(()); }
         Ok(unsafe{ Self::create_with(
             plot_id,
             client_uuid,
             client_name,
             expires_in,
-            sesssion_code.map(|b| Self::rand_byte_to_char(b)).into_iter().collect::<String>()
+            Self::random_code::<SESSION_CODE_LEN>()?
         ) })
     }
 
+    /// Prefer [`EditorRegistry::open_session`](crate::registry::EditorRegistry::open_session), which checks these invariants.
+    ///
     /// # SAFETY:
     /// Client uuid and the plot together must be unique.
     /// The plot must be managed by an editor instance.
-    /// The session code must be unique and should be sufficiently unguessable.
+    /// The session code must be unique and have at least [`MIN_SESSION_CODE_BITS`] of estimated entropy.
     pub unsafe fn create_with(
         plot_id      : DBPlotID,
         client_uuid  : Uuid,
@@ -81,10 +103,17 @@ impl EditorSession {
             session_step : EditorSessionStep::Pending {
                 expires_at : Instant::now() + expires_in
             },
+            permission   : EditorPermission::Write,
             closed       : 0
         }
     }
 
+    pub(crate) fn random_code<const SESSION_CODE_LEN : usize>() -> Result<String, ()> {
+        let mut sesssion_code = [0; SESSION_CODE_LEN];
+        let Ok(_) = rand_priv_bytes(&mut sesssion_code) else { return Err(()); };
+        Ok(sesssion_code.map(|b| Self::rand_byte_to_char(b)).into_iter().collect::<String>())
+    }
+
     fn rand_byte_to_char(byte : u8) -> char {
         let byte = byte % 64;
         let ascii = if ((0..26).contains(&byte)) {
@@ -117,6 +146,23 @@ impl EditorSession {
         &self.session_code
     }
 
+    /// Sets what this session may do to files. Sessions can write by default.
+    pub fn with_permission(mut self, permission : EditorPermission) -> Self {
+        self.permission = permission;
+        self
+    }
+
+    pub fn permission(&self) -> EditorPermission {
+        self.permission
+    }
+
+    /// This session's entry in the presence list, if it is active.
+    pub(crate) fn presence_packet(&self) -> Option<PresenceS2CPacket<'static>> {
+        if (self.closed != 0) { return None; }
+        let EditorSessionStep::Active { state, .. } = &self.session_step else { return None; };
+        Some(state.to_presence_packet(self.client_uuid, &self.client_name))
+    }
+
     pub(crate) fn session_step(&self) -> &EditorSessionStep {
         &self.session_step
     }
@@ -126,31 +172,138 @@ impl EditorSession {
     pub(crate) fn activate(
         &mut self,
         outgoing_commands_tx : mpsc::UnboundedSender<OutgoingPeerCommand>,
-        incoming_events_rx   : mpsc::UnboundedReceiver<IncomingPeerEvent>
+        incoming_events_rx   : mpsc::UnboundedReceiver<IncomingPeerEvent>,
+        address              : SocketAddr
     )  {
         let EditorSessionStep::Pending { .. } = self.session_step else {
             panic!("`EditorSession::activate` called on already activated `EditorSession`");
         };
 
         debug!("Opened editor session for {:?} on plot {}.", self.client_name, self.plot_id);
+        METRICS.session_activated();
         self.session_step = EditorSessionStep::Active {
             outgoing_commands_tx,
             incoming_events_rx,
-            state                : EditorSessionState::new()
+            state                : EditorSessionState::new(self.permission == EditorPermission::Suggest, address)
         };
     }
 
 
     pub fn close(&mut self) {
+        self.kick("Session closed");
+    }
+
+    /// Closes this session, showing the client the reason if it is connected.
+    pub fn kick<S : Into<Cow<'static, str>>>(&mut self, reason : S) {
         if (self.closed == 0) {
             self.closed = 1;
             if let EditorSessionStep::Active { outgoing_commands_tx, .. } = &mut self.session_step {
-                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Close);
+                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Close(reason.into()));
+                METRICS.session_kicked();
             }
             debug!("Closed editor session of {:?} on plot {}.", self.client_name, self.plot_id);
         }
     }
 
+    pub fn is_closed(&self) -> bool {
+        self.closed != 0
+    }
+
+    /// Restarts the expiry of a session that nobody has logged in with yet, and changes its permission and optionally its code.
+    ///
+    /// Returns `false` if it is already active or closed.
+    pub(crate) fn reuse_pending(&mut self, expires_in : Duration, permission : EditorPermission, session_code : Option<String>) -> bool {
+        if (self.closed != 0) { return false; }
+        let EditorSessionStep::Pending { expires_at } = &mut self.session_step else { return false; };
+        *expires_at     = Instant::now() + expires_in;
+        self.permission = permission;
+        if let Some(session_code) = session_code {
+            self.session_code = session_code;
+        }
+        true
+    }
+
+    /// Closes this session if nobody has logged in with its code yet.
+    ///
+    /// Returns `false` if it is already active or closed.
+    pub fn revoke(&mut self) -> bool {
+        if (self.closed != 0) { return false; }
+        let EditorSessionStep::Pending { .. } = self.session_step else { return false; };
+        self.close();
+        true
+    }
+
+    /// Gives the client longer to log in with the session code.
+    ///
+    /// Returns `false` if it is already active or closed.
+    pub fn extend(&mut self, by : Duration) -> bool {
+        if (self.closed != 0) { return false; }
+        let EditorSessionStep::Pending { expires_at } = &mut self.session_step else { return false; };
+        *expires_at += by;
+        true
+    }
+
+    /// Replaces the session code, which must be unique.
+    /// [`EditorRegistry::regenerate_code`](crate::registry::EditorRegistry::regenerate_code) checks that.
+    ///
+    /// Returns `false` if it is already active or closed, as the client is already using the old code.
+    pub(crate) fn replace_code(&mut self, session_code : String) -> bool {
+        if (self.closed != 0) { return false; }
+        let EditorSessionStep::Pending { .. } = self.session_step else { return false; };
+        self.session_code = session_code;
+        true
+    }
+
+
+    pub fn status(&self) -> EditorSessionStatus {
+        if (self.closed != 0) { return EditorSessionStatus::Closed; }
+        match (&self.session_step) {
+            EditorSessionStep::Pending { expires_at } => EditorSessionStatus::Pending { expires_at : *expires_at },
+            EditorSessionStep::Active { state, .. }   => EditorSessionStatus::Active {
+                connected_at : state.connected_at(),
+                address      : state.address()
+            }
+        }
+    }
+
+    pub fn info(&self) -> EditorSessionInfo {
+        EditorSessionInfo {
+            plot_id     : self.plot_id,
+            client_uuid : self.client_uuid,
+            client_name : self.client_name.clone(),
+            permission  : self.permission,
+            status      : self.status()
+        }
+    }
+
+    /// Describes every session on a plot that has not been closed, oldest first.
+    pub fn list<'l, I : IntoIterator<Item = &'l EditorSession>>(sessions : I, plot_id : DBPlotID) -> Vec<EditorSessionInfo> {
+        sessions.into_iter().filter(|session| session.plot_id == plot_id && session.closed == 0).map(|session| session.info()).collect()
+    }
+
+}
+
+
+#[derive(Clone, Debug)]
+pub struct EditorSessionInfo {
+    pub plot_id     : DBPlotID,
+    pub client_uuid : Uuid,
+    pub client_name : String,
+    pub permission  : EditorPermission,
+    pub status      : EditorSessionStatus
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum EditorSessionStatus {
+    /// Waiting for the client to log in with the session code.
+    Pending {
+        expires_at : Instant
+    },
+    Active {
+        connected_at : SystemTime,
+        address      : SocketAddr
+    },
+    Closed
 }
 
 
@@ -168,6 +321,10 @@ pub(crate) async fn read_session_events(
                 session.closed = 2;
 
                 for instance in &mut instances { if (instance.plot_id == session.plot_id) {
+                    if let EditorSessionStep::Active { .. } = session.session_step {
+                        instance.audit(session.client_uuid, &session.client_name, AuditEvent::SessionClosed);
+                        instance.events.push_back(EditorInstanceEvent::RemovePresence { client_uuid : session.client_uuid });
+                    }
                     // Clear selections.
                     instance.events.push_back(EditorInstanceEvent::UpdateSelections { packet : SelectionsS2CPacket {
                         client_uuid : session.client_uuid,
@@ -195,19 +352,85 @@ pub(crate) async fn read_session_events(
                 match (incoming_events_rx.try_recv()) {
                     Ok(event) => { match (event) {
 
-                        IncomingPeerEvent::Recieve(packet) => { match (packet) {
-
-                            C2SPackets::Keepalive(KeepaliveC2SPacket { .. }) => { }, // TODO: Keepalive
-
-                            C2SPackets::OpenFile(OpenFileC2SPacket { file_id }) => { state.open_file(file_id); },
-
-                            C2SPackets::CloseFile(CloseFileC2SPacket { file_id }) => { state.close_file(file_id); },
-
-                            C2SPackets::PatchFile(PatchFileC2SPacket { file_id, patches }) => { state.patch_file(file_id, patches); },
-
-                            C2SPackets::Selections(SelectionsC2SPacket { selections }) => { state.update_selections(selections); }
-
-                        } },
+                        IncomingPeerEvent::Recieve(packet) => {
+                            if (! matches!(packet, C2SPackets::Keepalive(_))) {
+                                state.mark_active();
+                            }
+                            match (packet) {
+
+                                C2SPackets::Keepalive(KeepaliveC2SPacket { .. }) => { }, // Checked by the peer.
+
+                                C2SPackets::OpenFile(OpenFileC2SPacket { file_id }) => { state.open_file(file_id); },
+
+                                C2SPackets::CloseFile(CloseFileC2SPacket { file_id }) => { state.close_file(file_id); },
+
+                                C2SPackets::PatchFile(PatchFileC2SPacket { file_id, patches }) => { state.patch_file(file_id, patches); },
+
+                                C2SPackets::Selections(SelectionsC2SPacket { selections }) => { state.update_selections(selections); },
+
+                                C2SPackets::Viewport(ViewportC2SPacket { viewport }) => { state.update_viewport(viewport); },
+
+                                C2SPackets::Search(mut search) => {
+                                    // Replacements change files directly.
+                                    if (session.permission != EditorPermission::Write) {
+                                        search.replace = None;
+                                    }
+                                    for instance in &mut instances { if (instance.plot_id == session.plot_id) {
+                                        instance.events.push_back(EditorInstanceEvent::Search { client_uuid : session.client_uuid, search });
+                                        break;
+                                    } }
+                                },
+
+                                C2SPackets::QueryAuditLog(QueryAuditLogC2SPacket { before, limit }) => { state.query_audit_log(before, limit); },
+
+                                C2SPackets::SetBlameVisible(SetBlameVisibleC2SPacket { visible }) => { state.set_blame_visible(visible); },
+
+                                C2SPackets::Chat(ChatC2SPacket { message }) => {
+                                    for instance in &mut instances { if (instance.plot_id == session.plot_id) {
+                                        instance.send_editor_chat(session.client_uuid, &session.client_name, &message);
+                                        break;
+                                    } }
+                                },
+
+                                C2SPackets::Comment(CommentC2SPacket { action }) => {
+                                    for instance in &mut instances { if (instance.plot_id == session.plot_id) {
+                                        instance.events.push_back(EditorInstanceEvent::Comment {
+                                            client_uuid : session.client_uuid,
+                                            client_name : session.client_name.clone(),
+                                            action
+                                        });
+                                        break;
+                                    } }
+                                },
+
+                                C2SPackets::SetSuggesting(SetSuggestingC2SPacket { suggesting }) => { state.set_suggesting(suggesting); },
+
+                                C2SPackets::ReviewSuggestion(ReviewSuggestionC2SPacket { suggestion_id, accept }) => {
+                                    for instance in &mut instances { if (instance.plot_id == session.plot_id) {
+                                        instance.events.push_back(EditorInstanceEvent::ReviewSuggestion {
+                                            client_uuid : session.client_uuid,
+                                            can_write   : session.permission == EditorPermission::Write,
+                                            suggestion_id,
+                                            accept
+                                        });
+                                        break;
+                                    } }
+                                },
+
+                                C2SPackets::RequestAction(RequestActionC2SPacket { action }) => {
+                                    debug!("{:?} requested {:?} on plot {}.", session.client_name, action, session.plot_id);
+                                    for instance in &mut instances { if (instance.plot_id == session.plot_id) {
+                                        instance.host_events.push_back(EditorHostEvent::ActionRequested {
+                                            client_uuid : session.client_uuid,
+                                            client_name : session.client_name.clone(),
+                                            action
+                                        });
+                                        break;
+                                    } }
+                                }
+
+                            }
+                        },
 
                         IncomingPeerEvent::Close => { session.close(); }
 
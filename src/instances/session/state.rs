@@ -1,18 +1,40 @@
 use crate::peer::OutgoingPeerCommand;
 use crate::instances::{ EditorInstance, EditorInstanceEvent };
+use crate::audit::AuditEvent;
 use crate::util::Dirty;
 use super::{ EditorSession, EditorSessionStep };
 use lighthousemc_editor_common::packet::s2c::*;
-use lighthousemc_editor_common::packet::c2s::SelectionRange;
+use lighthousemc_editor_common::packet::c2s::{ SelectionRange, Viewport };
 use lighthousemc_editor_common::dmp;
 use lighthousemc_database::DBFSFileID;
 use axecs::prelude::*;
+use std::borrow::Cow;
+use std::net::SocketAddr;
 use std::collections::{ BTreeMap, VecDeque };
+use std::time::{ Instant, SystemTime, Duration, UNIX_EPOCH };
+use uuid::Uuid;
+
+
+/// How long a client can go without doing anything before being shown as idle.
+const PRESENCE_IDLE_AFTER : Duration = Duration::from_secs(120);
 
 
 pub struct EditorSessionState {
-    file_shadows : BTreeMap<DBFSFileID, FileShadow>,
-    selections   : Dirty<Option<(DBFSFileID, Vec<SelectionRange>)>>
+    file_shadows       : BTreeMap<DBFSFileID, FileShadow>,
+    selections         : Dirty<Option<(DBFSFileID, Vec<SelectionRange>)>>,
+    viewport           : Dirty<Option<Viewport>>,
+    audit_queries      : VecDeque<(Option<u64>, u32)>,
+    blame_visible      : Dirty<bool>,
+    can_write          : bool,
+    /// Whether edits are held as suggestions. Always set if the session can not write.
+    suggesting         : Dirty<bool>,
+
+    connected_at       : SystemTime,
+    address            : SocketAddr,
+    last_active        : Instant,
+    /// The current file and whether the client is idle.
+    presence           : Dirty<(Option<DBFSFileID>, bool)>,
+    presence_announced : bool
 }
 
 pub struct FileShadow {
@@ -29,7 +51,7 @@ pub enum FileShadowStep {
 
 pub enum FileShadowContent {
     Loading,
-    NonText,
+    Binary,
     Text {
         text           : String,
         queued_patches : VecDeque<dmp::Patches<dmp::Efficient>>
@@ -39,11 +61,26 @@ pub enum FileShadowContent {
 
 impl EditorSessionState {
 
-    pub(super) fn new() -> Self { Self {
-        file_shadows : BTreeMap::new(),
-        selections   : Dirty::new_clean(None)
+    pub(super) fn new(suggest_only : bool, address : SocketAddr) -> Self { Self {
+        file_shadows       : BTreeMap::new(),
+        selections         : Dirty::new_clean(None),
+        viewport           : Dirty::new_clean(None),
+        audit_queries      : VecDeque::new(),
+        blame_visible      : Dirty::new_clean(false),
+        can_write          : ! suggest_only,
+        suggesting         : Dirty::new_clean(suggest_only),
+
+        connected_at       : SystemTime::now(),
+        address,
+        last_active        : Instant::now(),
+        presence           : Dirty::new_clean((None, false)),
+        presence_announced : false
     } }
 
+    pub fn file_shadows(&self) -> &BTreeMap<DBFSFileID, FileShadow> {
+        &self.file_shadows
+    }
+
     pub fn file_shadows_mut(&mut self) -> &mut BTreeMap<DBFSFileID, FileShadow> {
         &mut self.file_shadows
     }
@@ -83,6 +120,89 @@ impl EditorSessionState {
         }
     }
 
+    /// Takes the patches that the client sent to its files as instance events, oldest first for each file.
+    pub(crate) fn take_queued_edits(&mut self, client_uuid : Uuid, client_name : &str) -> Vec<EditorInstanceEvent> {
+        let     suggesting = *self.suggesting;
+        let mut events     = Vec::new();
+        for (&file_id, shadow) in &mut self.file_shadows {
+            let FileShadowContent::Text { queued_patches, .. } = &mut shadow.content else { continue; };
+            for patches in queued_patches.drain(..) {
+                events.push(if (suggesting) {
+                    EditorInstanceEvent::SuggestPatch {
+                        client_uuid,
+                        client_name : client_name.to_string(),
+                        file_id,
+                        patches
+                    }
+                } else {
+                    EditorInstanceEvent::PatchFile {
+                        client_uuid,
+                        author_uuid : client_uuid,
+                        file_id,
+                        patches
+                    }
+                });
+            }
+        }
+        events
+    }
+
+    pub(crate) fn to_presence_packet(&self, client_uuid : Uuid, client_name : &str) -> PresenceS2CPacket<'static> {
+        let (file_id, idle) = *self.presence;
+        PresenceS2CPacket {
+            client_uuid,
+            client_name  : client_name.to_string().into(),
+            colour       : (client_uuid.as_u128() % 180) as u8,
+            connected_at : self.connected_at.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64),
+            file_id,
+            idle
+        }
+    }
+
+    pub fn connected_at(&self) -> SystemTime {
+        self.connected_at
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub(super) fn mark_active(&mut self) {
+        self.last_active = Instant::now();
+    }
+
+    pub fn blame_visible(&self) -> bool {
+        *self.blame_visible
+    }
+
+    pub(super) fn set_blame_visible(&mut self, visible : bool) {
+        Dirty::set(&mut self.blame_visible, visible);
+    }
+
+    pub fn suggesting(&self) -> bool {
+        *self.suggesting
+    }
+
+    pub(super) fn set_suggesting(&mut self, suggesting : bool) {
+        Dirty::set(&mut self.suggesting, suggesting || ! self.can_write);
+    }
+
+    pub(super) fn query_audit_log(&mut self, before : Option<u64>, limit : u32) {
+        self.audit_queries.push_back((before, limit));
+    }
+
+    pub(crate) fn pop_audit_query(&mut self) -> Option<(Option<u64>, u32)> {
+        self.audit_queries.pop_front()
+    }
+
+    pub fn viewport(&self) -> Option<Viewport> {
+        *self.viewport
+    }
+
+    pub(super) fn update_viewport(&mut self, viewport : Option<Viewport>) {
+        Dirty::set(&mut self.viewport, viewport);
+    }
+
     pub(super) fn update_selections(&mut self, selections : Option<(DBFSFileID, Vec<SelectionRange>)>) {
         Dirty::set(&mut self.selections, selections);
     }
@@ -90,12 +210,29 @@ impl EditorSessionState {
 }
 
 
+impl From<&FileContents<'_>> for FileShadowContent {
+    fn from(contents : &FileContents<'_>) -> Self {
+        match (contents) {
+            FileContents::Binary { .. } => Self::Binary,
+            FileContents::Text(text)    => Self::Text {
+                text           : text.to_string(),
+                queued_patches : VecDeque::new()
+            }
+        }
+    }
+}
+
+
 impl FileShadow {
 
     pub fn step(&self) -> FileShadowStep {
         self.step
     }
 
+    pub fn content(&self) -> &FileShadowContent {
+        &self.content
+    }
+
     pub fn content_mut(&mut self) -> &mut FileShadowContent {
         &mut self.content
     }
@@ -113,6 +250,9 @@ pub(crate) async fn update_state(
         if let EditorSessionStep::Active { outgoing_commands_tx, state, .. } = &mut session.session_step {
             let Some(instance) = instances.iter_mut().find(|instance| instance.plot_id == session.plot_id) else { continue; };
 
+            // Edits, including the last ones to files that are being closed.
+            instance.events.extend(state.take_queued_edits(session.client_uuid, &session.client_name));
+
             // File shadows.
             {
                 let mut remove = Vec::new();
@@ -120,35 +260,31 @@ pub(crate) async fn update_state(
                     match (shadow.step) {
                         FileShadowStep::Opening => {
                             if let Some(file) = instance.state.files().get(&file_id) {
+                                let contents = match (file.contents()) {
+                                    FileContents::Text(text) if (*state.suggesting) => FileContents::Text(instance.suggestions.view(file_id, session.client_uuid, text).into_owned().into()),
+                                    contents                                       => contents.clone()
+                                };
+                                shadow.step    = FileShadowStep::Open;
+                                shadow.content = FileShadowContent::from(&contents);
                                 let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::OvewriteFile(OverwriteFileS2CPacket {
                                     file_id,
-                                    contents : file.contents().clone()
+                                    contents
                                 })));
-                                shadow.step = FileShadowStep::Open;
-                                shadow.content = match (&file.contents()) {
-                                    FileContents::NonText => FileShadowContent::NonText,
-                                    FileContents::Text(text) => FileShadowContent::Text {
-                                        text           : text.to_string(),
-                                        queued_patches : VecDeque::new()
-                                    }
-                                };
+                                if (*state.blame_visible) {
+                                    let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Blame(file.blame().to_packet(file_id))));
+                                }
+                                if let Some(packet) = instance.suggestions_packet(file_id, session.client_uuid, *state.suggesting) {
+                                    let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Suggestions(packet)));
+                                }
+                                let path = instance.state.file_path(file_id).unwrap_or_default();
+                                instance.audit(session.client_uuid, &session.client_name, AuditEvent::FileOpened { file_id, path });
                             } else {
                                 let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::CloseFile(CloseFileS2CPacket { file_id })));
                                 remove.push(file_id);
                                 shadow.step = FileShadowStep::Closing;
                             }
                         },
-                        FileShadowStep::Open => {
-                            if let FileShadowContent::Text { queued_patches, .. } = &mut shadow.content {
-                                for patches in queued_patches.drain(..) {
-                                    instance.events.push_back(EditorInstanceEvent::PatchFile {
-                                        client_uuid : session.client_uuid,
-                                        file_id,
-                                        patches
-                                    })
-                                }
-                            }
-                        },
+                        FileShadowStep::Open    => { },
                         FileShadowStep::Closing => { remove.push(file_id); }
                     }
                 }
@@ -157,6 +293,63 @@ pub(crate) async fn update_state(
                 }
             }
 
+            // Blame of files that were already open.
+            if (Dirty::take_dirty(&mut state.blame_visible) && *state.blame_visible) {
+                for (&file_id, shadow) in &state.file_shadows {
+                    if let FileShadowStep::Open = shadow.step {
+                        if let Some(file) = instance.state.files().get(&file_id) {
+                            let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Blame(file.blame().to_packet(file_id))));
+                        }
+                    }
+                }
+            }
+
+            // Suggestion mode. Open files are resent, as the client's own suggestions are only applied to them while suggesting.
+            if (Dirty::take_dirty(&mut state.suggesting)) {
+                let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::SuggestionMode(SuggestionModeS2CPacket {
+                    suggesting : *state.suggesting,
+                    can_write  : state.can_write
+                })));
+                for (&file_id, shadow) in &mut state.file_shadows {
+                    let FileShadowStep::Open = shadow.step else { continue; };
+                    let FileShadowContent::Text { text : shadow_text, .. } = &mut shadow.content else { continue; };
+                    let Some(FileContents::Text(central_text)) = instance.state.files().get(&file_id).map(|file| file.contents()) else { continue; };
+                    let target_text = if (*state.suggesting) { instance.suggestions.view(file_id, session.client_uuid, central_text) } else { Cow::Borrowed(&**central_text) };
+                    if (*shadow_text != *target_text) {
+                        *shadow_text = target_text.to_string();
+                        let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::OvewriteFile(OverwriteFileS2CPacket {
+                            file_id,
+                            contents : FileContents::Text(target_text.into_owned().into())
+                        })));
+                    }
+                    if let Some(packet) = instance.suggestions_packet(file_id, session.client_uuid, *state.suggesting) {
+                        let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Suggestions(packet)));
+                    }
+                }
+            }
+
+            // Presence.
+            {
+                let file_id = state.viewport.map(|viewport| viewport.file_id).or_else(|| state.selections.as_ref().map(|(file_id, _)| *file_id));
+                let idle    = state.last_active.elapsed() >= PRESENCE_IDLE_AFTER;
+                Dirty::set(&mut state.presence, (file_id, idle));
+                if (Dirty::take_dirty(&mut state.presence) || ! state.presence_announced) {
+                    instance.events.push_back(EditorInstanceEvent::UpdatePresence {
+                        packet : state.to_presence_packet(session.client_uuid, &session.client_name),
+                        is_new : ! state.presence_announced
+                    });
+                    state.presence_announced = true;
+                }
+            }
+
+            // Viewport.
+            if (Dirty::take_dirty(&mut state.viewport)) {
+                instance.events.push_back(EditorInstanceEvent::UpdateViewport { packet : ViewportS2CPacket {
+                    client_uuid : session.client_uuid,
+                    viewport    : *state.viewport
+                } });
+            }
+
             // Selections.
             if (Dirty::take_dirty(&mut state.selections)) {
                 instance.events.push_back(EditorInstanceEvent::UpdateSelections { packet : SelectionsS2CPacket {
@@ -171,3 +364,63 @@ pub(crate) async fn update_state(
     }
 
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLIENT : Uuid = Uuid::from_u128(1);
+
+    fn open_text_file(state : &mut EditorSessionState, file_id : DBFSFileID, text : &str) {
+        state.open_file(file_id);
+        let shadow = state.file_shadows.get_mut(&file_id).unwrap();
+        shadow.step    = FileShadowStep::Open;
+        shadow.content = FileShadowContent::from(&FileContents::Text(text.to_string().into()));
+    }
+
+    fn patches(old : &str, new : &str) -> dmp::Patches<dmp::Efficient> {
+        let dmp   = dmp::DiffMatchPatch::new();
+        let diffs = dmp.diff_main::<dmp::Efficient>(old, new).unwrap();
+        dmp.patch_make(dmp::PatchInput::new_diffs(&diffs)).unwrap()
+    }
+
+    fn new_state(suggest_only : bool) -> EditorSessionState {
+        EditorSessionState::new(suggest_only, SocketAddr::from(([127, 0, 0, 1], 0)))
+    }
+
+    #[test]
+    fn queued_edits_are_taken_once() {
+        let mut state = new_state(false);
+        open_text_file(&mut state, 1, "one");
+        state.patch_file(1, patches("one", "one two"));
+        state.patch_file(1, patches("one two", "one two three"));
+        let events = state.take_queued_edits(CLIENT, "Client");
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| matches!(event, EditorInstanceEvent::PatchFile { file_id : 1, author_uuid : CLIENT, .. })));
+        assert!(state.take_queued_edits(CLIENT, "Client").is_empty());
+    }
+
+    #[test]
+    fn suggesting_clients_queue_suggestions() {
+        let mut state = new_state(true);
+        open_text_file(&mut state, 1, "one");
+        state.patch_file(1, patches("one", "two"));
+        let events = state.take_queued_edits(CLIENT, "Client");
+        assert!(matches!(events.as_slice(), [EditorInstanceEvent::SuggestPatch { file_id : 1, .. }]));
+    }
+
+    #[test]
+    fn closing_files_keep_their_last_edits() {
+        let mut state = new_state(false);
+        open_text_file(&mut state, 1, "one");
+        open_text_file(&mut state, 2, "two");
+        state.patch_file(1, patches("one", "one!"));
+        state.close_file(1);
+        // Closed files take no new patches, but the ones sent before are still applied.
+        state.patch_file(1, patches("one!", "one!!"));
+        let events = state.take_queued_edits(CLIENT, "Client");
+        assert!(matches!(events.as_slice(), [EditorInstanceEvent::PatchFile { file_id : 1, .. }]));
+    }
+
+}
@@ -1,6 +1,10 @@
+use super::blame::FileBlame;
+use crate::store::UnsavedFile;
 use lighthousemc_editor_common::packet::s2c::{ InitialStateS2CPacket, FileTreeEntry, FileContents };
 use lighthousemc_database::{ LighthouseDB, DBPlotID, DBFSDirectoryID, DBFSDirectory, DBFSFileID, DBError };
 use std::collections::BTreeMap;
+use std::borrow::Cow;
+use uuid::Uuid;
 
 
 pub struct EditorInstanceState {
@@ -28,17 +32,30 @@ impl EditorInstanceState {
             files           : {
                 let mut map = BTreeMap::new();
                 for file in database.get_plot_files(plot_id).await? {
-                    map.insert(file.id, StateFile {
+                    let mut state_file = StateFile {
                         parent_dir : file.parent_dir,
                         fsname     : file.fsname,
-                        contents   : String::from_utf8(file.blob).map_or(FileContents::NonText, |text| FileContents::Text(text.into()))
-                    });
+                        contents   : FileContents::Text("".into()),
+                        blob       : Vec::new(),
+                        blame      : FileBlame::default(),
+                        unsaved    : false
+                    };
+                    state_file.set_bytes(file.blob, None);
+                    map.insert(file.id, state_file);
                 }
                 map
             }
         }))
     }
 
+    #[cfg(test)]
+    pub(crate) fn empty(plot_id : DBPlotID) -> Self { Self {
+        plot_id,
+        plot_owner_name : String::new(),
+        directories     : BTreeMap::new(),
+        files           : BTreeMap::new()
+    } }
+
     pub(crate) fn to_initial_state_packet(&self) -> InitialStateS2CPacket<'static> {
         InitialStateS2CPacket {
             plot_id         : self.plot_id,
@@ -67,24 +84,103 @@ impl EditorInstanceState {
     }
 
 
-    pub(crate) fn files(&self) -> &BTreeMap<DBFSFileID, StateFile> {
+    /// The full path of a file, with directories separated by `/`.
+    pub fn file_path(&self, file_id : DBFSFileID) -> Option<String> {
+        let     file   = self.files.get(&file_id)?;
+        let mut parts  = vec![ file.fsname.as_str() ];
+        let mut parent = file.parent_dir;
+        while let Some(directory_id) = parent {
+            let directory = self.directories.get(&directory_id)?;
+            parts.push(&directory.fsname);
+            parent = directory.parent_dir;
+        }
+        parts.reverse();
+        Some(parts.join("/"))
+    }
+
+
+    pub fn files(&self) -> &BTreeMap<DBFSFileID, StateFile> {
         &self.files
     }
     pub(crate) fn files_mut(&mut self) -> &mut BTreeMap<DBFSFileID, StateFile> {
         &mut self.files
     }
 
+    /// Adds a file to the tree. Returns `false` if it already exists or its directory does not.
+    pub(crate) fn insert_file(&mut self, file_id : DBFSFileID, parent_dir : Option<DBFSDirectoryID>, fsname : String, data : Vec<u8>, author : (Uuid, &str)) -> bool {
+        if (self.files.contains_key(&file_id) || parent_dir.is_some_and(|parent_dir| ! self.directories.contains_key(&parent_dir))) { return false; }
+        let mut file = StateFile {
+            parent_dir,
+            fsname,
+            contents   : FileContents::Text("".into()),
+            blob       : Vec::new(),
+            blame      : FileBlame::default(),
+            unsaved    : false
+        };
+        file.set_bytes(data, Some(author));
+        self.files.insert(file_id, file);
+        true
+    }
+
+    /// Moves a file to another directory or name, returning the directory it was in.
+    pub(crate) fn set_file_location(&mut self, file_id : DBFSFileID, parent_dir : Option<DBFSDirectoryID>, fsname : String) -> Option<Option<DBFSDirectoryID>> {
+        if (parent_dir.is_some_and(|parent_dir| ! self.directories.contains_key(&parent_dir))) { return None; }
+        let file = self.files.get_mut(&file_id)?;
+        file.fsname = fsname;
+        Some(std::mem::replace(&mut file.parent_dir, parent_dir))
+    }
+
+    pub(crate) fn remove_file(&mut self, file_id : DBFSFileID) -> Option<StateFile> {
+        self.files.remove(&file_id)
+    }
+
+    /// Whether any file was changed since it was last saved.
+    pub fn has_unsaved_files(&self) -> bool {
+        self.files.values().any(|file| file.unsaved)
+    }
+
+    /// The contents of every file that was changed since it was last saved, marking them as saved.
+    pub(crate) fn take_unsaved_files(&mut self) -> Vec<UnsavedFile> {
+        let ids = self.files.iter().filter(|(_, file)| file.unsaved).map(|(file_id, _)| *file_id).collect::<Vec<_>>();
+        ids.into_iter().filter_map(|file_id| {
+            let path = self.file_path(file_id)?;
+            let file = self.files.get_mut(&file_id)?;
+            file.unsaved = false;
+            let blame = matches!(file.contents, FileContents::Text(_)).then(|| file.blame.to_bytes());
+            Some(UnsavedFile { file_id, path, data : file.bytes().to_vec(), blame })
+        }).collect()
+    }
+
+    /// Marks files as changed again, eg. after saving them failed.
+    pub(crate) fn mark_unsaved<I : IntoIterator<Item = DBFSFileID>>(&mut self, file_ids : I) {
+        for file_id in file_ids {
+            if let Some(file) = self.files.get_mut(&file_id) {
+                file.unsaved = true;
+            }
+        }
+    }
+
 }
 
 
 pub struct StateFile {
     parent_dir : Option<DBFSDirectoryID>,
     fsname     : String,
-    contents   : FileContents<'static>
+    contents   : FileContents<'static>,
+    /// The raw data of a binary file. Empty if the file is text.
+    blob       : Vec<u8>,
+    /// Who last changed each line. Empty if the file is binary.
+    blame      : FileBlame,
+    /// Whether the file was changed since it was last saved.
+    unsaved    : bool
 }
 
 impl StateFile {
 
+    pub fn fsname(&self) -> &str {
+        &self.fsname
+    }
+
     pub fn contents(&self) -> &FileContents<'static> {
         &self.contents
     }
@@ -92,4 +188,81 @@ impl StateFile {
         &mut self.contents
     }
 
+    pub fn blame(&self) -> &FileBlame {
+        &self.blame
+    }
+    /// Replaces who last changed each line, eg. with one that was stored alongside the file.
+    pub fn set_blame(&mut self, blame : FileBlame) {
+        self.blame = blame;
+    }
+
+    pub fn is_unsaved(&self) -> bool {
+        self.unsaved
+    }
+    pub(crate) fn mark_unsaved(&mut self) {
+        self.unsaved = true;
+    }
+
+    /// The text and blame of this file, if it is text.
+    pub(crate) fn text_and_blame_mut(&mut self) -> Option<(&mut Cow<'static, str>, &mut FileBlame)> {
+        match (&mut self.contents) {
+            FileContents::Binary { .. } => None,
+            FileContents::Text(text)    => Some((text, &mut self.blame))
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        match (&self.contents) {
+            FileContents::Binary { .. } => &self.blob,
+            FileContents::Text(text)    => text.as_bytes()
+        }
+    }
+
+    pub fn mime(&self) -> &str {
+        match (&self.contents) {
+            FileContents::Binary { mime, .. } => mime,
+            FileContents::Text(_)             => guess_mime(&self.fsname, &[])
+        }
+    }
+
+    /// Replaces the contents of this file, classifying it as text or binary.
+    /// Every line is attributed to `author`, or to nobody.
+    pub fn set_bytes(&mut self, data : Vec<u8>, author : Option<(Uuid, &str)>) {
+        match (String::from_utf8(data)) {
+            Ok(text) => {
+                self.blame    = FileBlame::new(&text, author);
+                self.contents = FileContents::Text(text.into());
+                self.blob     = Vec::new();
+            },
+            Err(err) => {
+                let data = err.into_bytes();
+                self.contents = FileContents::Binary {
+                    size : data.len() as u64,
+                    mime : guess_mime(&self.fsname, &data).into()
+                };
+                self.blob  = data;
+                self.blame = FileBlame::default();
+            }
+        }
+    }
+
+}
+
+
+fn guess_mime(fsname : &str, data : &[u8]) -> &'static str {
+    if (data.starts_with(b"\x89PNG\r\n\x1A\n")) { return "image/png"; }
+    if (data.starts_with(b"\xFF\xD8\xFF")) { return "image/jpeg"; }
+    if (data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")) { return "image/gif"; }
+    if (data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP") { return "image/webp"; }
+    match (fsname.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).as_deref()) {
+        Some("png")             => "image/png",
+        Some("jpg" | "jpeg")    => "image/jpeg",
+        Some("gif")             => "image/gif",
+        Some("webp")            => "image/webp",
+        Some("ogg")             => "audio/ogg",
+        Some("wasm")            => "application/wasm",
+        Some("json" | "mcmeta") => "application/json",
+        _ if (data.is_empty())  => "text/plain",
+        _                       => "application/octet-stream"
+    }
 }
@@ -0,0 +1,285 @@
+use super::{ EditorInstance, TextEdit, byte_to_utf16 };
+use crate::metrics::METRICS;
+use lighthousemc_editor_common::packet::s2c::{ self, SuggestionsS2CPacket, FileContents };
+use lighthousemc_editor_common::dmp;
+use lighthousemc_database::DBFSFileID;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use uuid::Uuid;
+
+
+/// Edits that were proposed in suggestion mode, waiting for someone with write access to accept or reject them.
+#[derive(Clone, Default)]
+pub struct Suggestions {
+    next_id     : u64,
+    suggestions : BTreeMap<u64, StoredSuggestion>
+}
+
+/// A change to whole lines of a text file.
+/// The live suggestions of one author on one file never overlap.
+#[derive(Clone)]
+pub struct StoredSuggestion {
+    pub file_id     : DBFSFileID,
+    pub author_uuid : Uuid,
+    pub author_name : String,
+    /// Byte offsets of the replaced range, in the current text of the file.
+    pub start       : usize,
+    pub end         : usize,
+    pub replacement : String,
+    /// Whether the text that the suggestion replaces was edited since it was made.
+    /// Stale suggestions are no longer shown to their author and can only be rejected.
+    pub stale       : bool
+}
+
+impl Suggestions {
+
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &StoredSuggestion)> {
+        self.suggestions.iter().map(|(suggestion_id, suggestion)| (*suggestion_id, suggestion))
+    }
+
+    pub fn get(&self, suggestion_id : u64) -> Option<&StoredSuggestion> {
+        self.suggestions.get(&suggestion_id)
+    }
+
+    pub(crate) fn has_file(&self, file_id : DBFSFileID) -> bool {
+        self.suggestions.values().any(|suggestion| suggestion.file_id == file_id)
+    }
+
+    pub(crate) fn remove(&mut self, suggestion_id : u64) -> Option<StoredSuggestion> {
+        self.suggestions.remove(&suggestion_id)
+    }
+
+    /// Removes every suggestion on a file, eg. after its contents were replaced entirely. Returns `false` if there were none.
+    pub(crate) fn clear_file(&mut self, file_id : DBFSFileID) -> bool {
+        let count = self.suggestions.len();
+        self.suggestions.retain(|_, suggestion| suggestion.file_id != file_id);
+        self.suggestions.len() != count
+    }
+
+    /// The live suggestions of an author on a file, in order.
+    fn of_author(&self, file_id : DBFSFileID, author_uuid : Uuid) -> Vec<&StoredSuggestion> {
+        let mut suggestions = self.suggestions.values().filter(|suggestion| suggestion.file_id == file_id && suggestion.author_uuid == author_uuid && ! suggestion.stale).collect::<Vec<_>>();
+        suggestions.sort_by_key(|suggestion| suggestion.start);
+        suggestions
+    }
+
+    /// The text of a file as its author sees it while suggesting: the central text with their suggestions applied.
+    pub(crate) fn view<'l>(&self, file_id : DBFSFileID, author_uuid : Uuid, central_text : &'l str) -> Cow<'l, str> {
+        let suggestions = self.of_author(file_id, author_uuid);
+        if (suggestions.is_empty()) { return Cow::Borrowed(central_text); }
+        let mut view = String::with_capacity(central_text.len());
+        let mut pos  = 0;
+        for suggestion in suggestions {
+            if (suggestion.start < pos) { continue; }
+            view.push_str(&central_text[pos..suggestion.start]);
+            view.push_str(&suggestion.replacement);
+            pos = suggestion.end;
+        }
+        view.push_str(&central_text[pos..]);
+        Cow::Owned(view)
+    }
+
+    /// Moves a byte offset in the central text to the same place in the view of an author.
+    /// Offsets inside one of their suggestions move to its start.
+    fn view_offset(&self, file_id : DBFSFileID, author_uuid : Uuid, offset : usize) -> usize {
+        let mut delta = 0isize;
+        for suggestion in self.of_author(file_id, author_uuid) {
+            if (suggestion.end <= offset) {
+                delta += suggestion.replacement.len() as isize - (suggestion.end - suggestion.start) as isize;
+            } else {
+                if (suggestion.start < offset) { return (suggestion.start as isize + delta) as usize; }
+                break;
+            }
+        }
+        (offset as isize + delta) as usize
+    }
+
+    /// Replaces the suggestions of an author on a file with whatever turns `central_text` into `proposed_text`.
+    /// Suggestions that overlap a previous one keep its id, so that reviewers do not lose track of them.
+    /// Stale suggestions are left alone, as they are not part of the text that the author sees.
+    pub(crate) fn update(&mut self, file_id : DBFSFileID, author_uuid : Uuid, author_name : &str, central_text : &str, proposed_text : &str) {
+        let mut previous = Vec::new();
+        self.suggestions.retain(|suggestion_id, suggestion| {
+            if (suggestion.file_id == file_id && suggestion.author_uuid == author_uuid && ! suggestion.stale) {
+                previous.push((*suggestion_id, suggestion.start..suggestion.end));
+                false
+            } else { true }
+        });
+        for (central, proposed) in line_hunks(central_text, proposed_text) {
+            let suggestion_id = match (previous.iter().position(|(_, range)| range.start <= central.end && central.start <= range.end)) {
+                Some(index) => previous.swap_remove(index).0,
+                None        => { let suggestion_id = self.next_id; self.next_id += 1; suggestion_id }
+            };
+            self.suggestions.insert(suggestion_id, StoredSuggestion {
+                file_id,
+                author_uuid,
+                author_name : author_name.to_string(),
+                start       : central.start,
+                end         : central.end,
+                replacement : proposed_text[proposed].to_string(),
+                stale       : false
+            });
+        }
+    }
+
+    /// Moves the suggestions on a file to follow an edit of its central text.
+    /// Suggestions whose replaced text was edited become stale, and suggestions that no longer change anything are dropped.
+    pub(crate) fn apply_edit(&mut self, file_id : DBFSFileID, edit : &TextEdit, new_central_text : &str) {
+        self.suggestions.retain(|_, suggestion| {
+            if (suggestion.file_id != file_id) { return true; }
+            if (edit.overlaps(suggestion.start..suggestion.end)) { suggestion.stale = true; }
+            suggestion.start = edit.shift(suggestion.start, false);
+            suggestion.end   = edit.shift(suggestion.end, true).max(suggestion.start);
+            new_central_text.get(suggestion.start..suggestion.end) != Some(suggestion.replacement.as_str())
+        });
+    }
+
+    /// The patches that apply a suggestion to the central text.
+    pub(crate) fn patches_for(suggestion : &StoredSuggestion, central_text : &str) -> Option<dmp::Patches<dmp::Efficient>> {
+        let accepted_text = format!("{}{}{}", central_text.get(..suggestion.start)?, suggestion.replacement, central_text.get(suggestion.end..)?);
+        let dmp   = dmp::DiffMatchPatch::new();
+        let diffs = METRICS.time_diff(|| dmp.diff_main::<dmp::Efficient>(central_text, &accepted_text)).ok()?;
+        dmp.patch_make(dmp::PatchInput::new_diffs(&diffs)).ok()
+    }
+
+    /// The suggestions on a file, with offsets in the text that a client sees.
+    /// `viewer` is the client, if they are suggesting and so see their own suggestions applied.
+    pub(crate) fn to_packet(&self, file_id : DBFSFileID, viewer : Option<Uuid>, central_text : &str) -> SuggestionsS2CPacket<'static> {
+        let view = match (viewer) {
+            Some(viewer) => self.view(file_id, viewer, central_text),
+            None         => Cow::Borrowed(central_text)
+        };
+        let mut suggestions = self.suggestions.iter().filter(|(_, suggestion)| suggestion.file_id == file_id).collect::<Vec<_>>();
+        suggestions.sort_by_key(|(_, suggestion)| suggestion.start);
+        SuggestionsS2CPacket { file_id, suggestions : suggestions.into_iter().map(|(&suggestion_id, suggestion)| {
+            let applied = viewer == Some(suggestion.author_uuid) && ! suggestion.stale;
+            let (start, end) = match (viewer) {
+                Some(viewer) if (applied) => {
+                    let start = self.view_offset(file_id, viewer, suggestion.start);
+                    (start, start + suggestion.replacement.len())
+                },
+                Some(viewer) => (self.view_offset(file_id, viewer, suggestion.start), self.view_offset(file_id, viewer, suggestion.end)),
+                None         => (suggestion.start, suggestion.end)
+            };
+            s2c::Suggestion {
+                suggestion_id,
+                client_name : suggestion.author_name.clone().into(),
+                colour      : (suggestion.author_uuid.as_u128() % 180) as u8,
+                applied,
+                stale       : suggestion.stale,
+                start       : byte_to_utf16(&view, start),
+                end         : byte_to_utf16(&view, end),
+                original    : central_text.get(suggestion.start..suggestion.end).unwrap_or_default().to_string().into(),
+                replacement : suggestion.replacement.clone().into()
+            }
+        }).collect::<Vec<_>>().into() }
+    }
+
+}
+
+
+/// The changed ranges between two texts, grown to whole lines, as byte ranges in `old` and in `new`.
+fn line_hunks(old : &str, new : &str) -> Vec<(Range<usize>, Range<usize>)> {
+    let dmp = dmp::DiffMatchPatch::new();
+    let Ok(diffs) = METRICS.time_diff(|| dmp.diff_main::<dmp::Efficient>(old, new)) else { return Vec::new(); };
+    let mut hunks = Vec::<(Range<usize>, Range<usize>)>::new();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for diff in &diffs {
+        let len = diff.data().len();
+        let (old_end, new_end) = match (diff.op()) {
+            dmp::Ops::Equal  => { old_pos += len; new_pos += len; continue; },
+            dmp::Ops::Delete => (old_pos + len, new_pos),
+            dmp::Ops::Insert => (old_pos, new_pos + len)
+        };
+        // Text around the change up to the line breaks is unchanged, unless another change on the same line merges with this one.
+        let before = old.as_bytes()[..old_pos].iter().rev().take_while(|byte| **byte != b'\n').count();
+        let after  = old.as_bytes()[old_end..].iter().take_while(|byte| **byte != b'\n').count();
+        match (hunks.last_mut()) {
+            // The start of the line is only the same distance back in `new` if nothing before it on the line changed.
+            Some(last) if (old_pos - before <= last.0.end) => {
+                last.0.end = old_end + after;
+                last.1.end = new_end + after;
+            },
+            _ => { hunks.push(((old_pos - before)..(old_end + after), (new_pos - before)..(new_end + after))); }
+        }
+        (old_pos, new_pos) = (old_end, new_end);
+    }
+    hunks
+}
+
+
+impl EditorInstance {
+
+    pub fn suggestions(&self) -> &Suggestions { &self.suggestions }
+
+    /// The suggestions on a file as a client sees them, or `None` if there are none.
+    pub(crate) fn suggestions_packet(&self, file_id : DBFSFileID, client_uuid : Uuid, suggesting : bool) -> Option<SuggestionsS2CPacket<'static>> {
+        if (! self.suggestions.has_file(file_id)) { return None; }
+        let Some(FileContents::Text(central_text)) = self.state.files().get(&file_id).map(|file| file.contents()) else { return None; };
+        Some(self.suggestions.to_packet(file_id, suggesting.then_some(client_uuid), central_text))
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AUTHOR : Uuid = Uuid::from_u128(1);
+
+    fn suggest(suggestions : &mut Suggestions, central_text : &str, proposed_text : &str) -> u64 {
+        suggestions.update(0, AUTHOR, "Author", central_text, proposed_text);
+        suggestions.iter().find(|(_, suggestion)| suggestion.author_uuid == AUTHOR && ! suggestion.stale).map(|(suggestion_id, _)| suggestion_id).unwrap()
+    }
+
+    #[test]
+    fn suggestions_replace_whole_lines() {
+        let central_text = "one\ntwo\nthree\n";
+        let mut suggestions = Suggestions::default();
+        let suggestion_id = suggest(&mut suggestions, central_text, "one\n2\nthree\n");
+        let suggestion = suggestions.get(suggestion_id).unwrap();
+        assert_eq!(&central_text[suggestion.start..suggestion.end], "two");
+        assert_eq!(suggestion.replacement, "2");
+        assert_eq!(suggestions.view(0, AUTHOR, central_text), "one\n2\nthree\n");
+    }
+
+    #[test]
+    fn edits_elsewhere_move_suggestions() {
+        let (old, new) = ("one\ntwo\nthree\n", "zero\none\ntwo\nthree!\n");
+        let mut suggestions = Suggestions::default();
+        let suggestion_id = suggest(&mut suggestions, old, "one\n2\nthree\n");
+        suggestions.apply_edit(0, &TextEdit::between(old, new), new);
+        let suggestion = suggestions.get(suggestion_id).unwrap();
+        assert!(! suggestion.stale);
+        assert_eq!(&new[suggestion.start..suggestion.end], "two");
+        assert_eq!(suggestions.view(0, AUTHOR, new), "zero\none\n2\nthree!\n");
+    }
+
+    #[test]
+    fn overlapping_edits_make_suggestions_stale() {
+        let (old, new) = ("one\ntwo\nthree\n", "one\ntwenty\nthree\n");
+        let mut suggestions = Suggestions::default();
+        let suggestion_id = suggest(&mut suggestions, old, "one\n2\nthree\n");
+        suggestions.apply_edit(0, &TextEdit::between(old, new), new);
+        let suggestion = suggestions.get(suggestion_id).unwrap();
+        assert!(suggestion.stale);
+        assert_eq!(&new[suggestion.start..suggestion.end], "twenty");
+        // The author no longer sees a stale suggestion, and new suggestions leave it alone.
+        assert_eq!(suggestions.view(0, AUTHOR, new), new);
+        let other_id = suggest(&mut suggestions, new, "1\ntwenty\nthree\n");
+        assert_ne!(other_id, suggestion_id);
+        assert!(suggestions.get(suggestion_id).is_some_and(|suggestion| suggestion.stale));
+    }
+
+    #[test]
+    fn applied_suggestions_are_dropped() {
+        let (old, new) = ("one\ntwo\nthree\n", "one\n2\nthree\n");
+        let mut suggestions = Suggestions::default();
+        suggest(&mut suggestions, old, new);
+        suggestions.apply_edit(0, &TextEdit::between(old, new), new);
+        assert!(! suggestions.has_file(0));
+    }
+
+}
@@ -0,0 +1,38 @@
+use super::LeaseStore;
+use lighthousemc_database::{ LighthouseDB, DBPlotID };
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+
+/// Keeps leases in the Lighthouse database, shared by every node that uses it.
+///
+/// Each lease is taken with a single conditional write, so two nodes can not both succeed.
+/// Expiry is compared against the database's clock, so node clocks do not need to agree.
+///
+/// [`LighthouseDB::try_acquire_plot_lease`] writes the lease only where it is held by the same node or has expired,
+/// and [`LighthouseDB::release_plot_lease`] deletes it only where it is held by the same node.
+pub struct DatabaseLeaseStore {
+    database : Arc<LighthouseDB>
+}
+
+impl DatabaseLeaseStore {
+    pub fn new(database : Arc<LighthouseDB>) -> Self { Self { database } }
+}
+
+impl LeaseStore for DatabaseLeaseStore {
+
+    fn acquire(&self, plot_id : DBPlotID, node_id : Uuid, ttl : Duration) -> Pin<Box<dyn Future<Output = Result<bool, String>> + Send + '_>> {
+        Box::pin(async move {
+            self.database.try_acquire_plot_lease(plot_id, node_id, ttl).await.map_err(|err| format!("database error: {:?}", err))
+        })
+    }
+
+    fn release(&self, plot_id : DBPlotID, node_id : Uuid) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send + '_>> {
+        Box::pin(async move {
+            self.database.release_plot_lease(plot_id, node_id).await.map_err(|err| format!("database error: {:?}", err))
+        })
+    }
+
+}
@@ -0,0 +1,105 @@
+use super::LeaseStore;
+use lighthousemc_database::DBPlotID;
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::{ Arc, Mutex };
+use std::time::{ Instant, Duration };
+use uuid::Uuid;
+
+
+/// Keeps leases in memory, so only nodes in the same process see them. Clones share the same leases,
+/// so that several nodes can be simulated in tests.
+#[derive(Clone, Default)]
+pub struct MemoryLeaseStore {
+    leases : Arc<Mutex<BTreeMap<DBPlotID, (Uuid, Instant)>>>
+}
+
+impl MemoryLeaseStore {
+
+    pub fn new() -> Self { Self::default() }
+
+    /// The node holding the lease of a plot, if it has not expired.
+    pub fn holder(&self, plot_id : DBPlotID) -> Option<Uuid> {
+        let leases = self.leases.lock().unwrap();
+        leases.get(&plot_id).filter(|(_, expires_at)| Instant::now() < *expires_at).map(|(node_id, _)| *node_id)
+    }
+
+}
+
+impl LeaseStore for MemoryLeaseStore {
+
+    fn acquire(&self, plot_id : DBPlotID, node_id : Uuid, ttl : Duration) -> Pin<Box<dyn Future<Output = Result<bool, String>> + Send + '_>> {
+        let mut leases = self.leases.lock().unwrap();
+        let     now    = Instant::now();
+        let acquired = match (leases.get(&plot_id)) {
+            Some((holder, expires_at)) if (*holder != node_id && now < *expires_at) => false,
+            _ => {
+                leases.insert(plot_id, (node_id, now + ttl));
+                true
+            }
+        };
+        Box::pin(async move { Ok(acquired) })
+    }
+
+    fn release(&self, plot_id : DBPlotID, node_id : Uuid) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send + '_>> {
+        let mut leases = self.leases.lock().unwrap();
+        if (leases.get(&plot_id).is_some_and(|(holder, _)| *holder == node_id)) {
+            leases.remove(&plot_id);
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODE_A : Uuid = Uuid::from_u128(1);
+    const NODE_B : Uuid = Uuid::from_u128(2);
+    const TTL    : Duration = Duration::from_secs(60);
+
+    #[tokio::test]
+    async fn one_node_holds_a_plot() {
+        let store = MemoryLeaseStore::new();
+        assert_eq!(store.acquire(1, NODE_A, TTL).await, Ok(true));
+        assert_eq!(store.acquire(1, NODE_B, TTL).await, Ok(false));
+        assert_eq!(store.acquire(2, NODE_B, TTL).await, Ok(true));
+        assert_eq!(store.holder(1), Some(NODE_A));
+        assert_eq!(store.holder(2), Some(NODE_B));
+    }
+
+    #[tokio::test]
+    async fn holder_renews_its_lease() {
+        let store = MemoryLeaseStore::new();
+        assert_eq!(store.acquire(1, NODE_A, Duration::from_millis(50)).await, Ok(true));
+        assert_eq!(store.acquire(1, NODE_A, TTL).await, Ok(true));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        // The renewal outlasts the first TTL.
+        assert_eq!(store.holder(1), Some(NODE_A));
+        assert_eq!(store.acquire(1, NODE_B, TTL).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn expired_leases_are_taken_over() {
+        let store = MemoryLeaseStore::new();
+        assert_eq!(store.acquire(1, NODE_A, Duration::from_millis(50)).await, Ok(true));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(store.holder(1), None);
+        assert_eq!(store.acquire(1, NODE_B, TTL).await, Ok(true));
+        assert_eq!(store.acquire(1, NODE_A, TTL).await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn only_the_holder_releases() {
+        let store = MemoryLeaseStore::new();
+        let other = store.clone();
+        assert_eq!(store.acquire(1, NODE_A, TTL).await, Ok(true));
+        assert_eq!(other.release(1, NODE_B).await, Ok(()));
+        assert_eq!(other.holder(1), Some(NODE_A));
+        assert_eq!(store.release(1, NODE_A).await, Ok(()));
+        assert_eq!(other.acquire(1, NODE_B, TTL).await, Ok(true));
+    }
+
+}
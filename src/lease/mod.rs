@@ -0,0 +1,64 @@
+use lighthousemc_database::DBPlotID;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+
+mod memory;
+pub use memory::*;
+
+mod database;
+pub use database::*;
+
+
+/// Records which node may manage each plot, so that two nodes never edit the same plot at once.
+///
+/// Leases expire unless they are renewed, so that the plots of a node that crashed can be taken over.
+pub trait LeaseStore : Send + Sync + 'static {
+
+    /// Takes the lease of a plot for a node until `ttl` from now, or extends it if the node already holds it.
+    ///
+    /// Returns `Ok(false)` if another node holds a lease that has not expired.
+    fn acquire(&self, plot_id : DBPlotID, node_id : Uuid, ttl : Duration) -> Pin<Box<dyn Future<Output = Result<bool, String>> + Send + '_>>;
+
+    /// Gives up the lease of a plot, if the node holds it.
+    fn release(&self, plot_id : DBPlotID, node_id : Uuid) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send + '_>>;
+
+}
+
+
+/// How instances hold leases on their plots. See [`crate::lifecycle::LifecycleConfig::with_leases`].
+#[derive(Clone)]
+pub struct LeaseConfig {
+    pub(crate) store          : Arc<dyn LeaseStore>,
+    pub(crate) node_id        : Uuid,
+    pub(crate) ttl            : Duration,
+    pub(crate) renew_interval : Duration
+}
+
+impl LeaseConfig {
+
+    /// `node_id` must be different on every node, and should stay the same across restarts so that a node can take back its own leases.
+    pub fn new<S : LeaseStore>(store : S, node_id : Uuid) -> Self { Self {
+        store          : Arc::new(store),
+        node_id,
+        ttl            : Duration::from_secs(30),
+        renew_interval : Duration::from_secs(10)
+    } }
+
+    /// Sets how long a lease lasts without being renewed. Defaults to 30 seconds.
+    pub fn with_ttl(mut self, ttl : Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets how often leases are renewed. Defaults to 10 seconds, and should be well under the TTL.
+    pub fn with_renew_interval(mut self, renew_interval : Duration) -> Self {
+        self.renew_interval = renew_interval;
+        self
+    }
+
+    pub fn node_id(&self) -> Uuid { self.node_id }
+
+}
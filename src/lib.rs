@@ -11,6 +11,9 @@ use axecs::prelude::*;
 use axecs::future::UntilExitFuture;
 use std::io;
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::net::{ self, ToSocketAddrs };
 
 
@@ -20,12 +23,39 @@ pub mod peer;
 
 pub mod instances;
 
+pub mod registry;
+
+pub mod audit;
+
+pub mod metrics;
+
+pub mod store;
+
+pub mod lifecycle;
+
+pub mod lease;
+
+mod shutdown;
+
 mod util;
 
 
 pub struct EditorPlugin {
     bind_addrs        : Vec<SocketAddr>,
-    display_game_addr : String
+    bind_policy       : webserver::BindPolicy,
+    display_game_addr : String,
+    asset_dir         : PathBuf,
+    audit_sink        : Box<dyn audit::AuditSink>,
+    login_limits      : peer::guard::LoginLimits,
+    connection_limits : peer::limits::ConnectionLimits,
+    tls               : Option<webserver::tls::TlsConfig>,
+    proxy             : webserver::proxy::ProxyConfig,
+    security          : webserver::security::SecurityConfig,
+    metrics           : metrics::MetricsEndpoint,
+    admin_secret      : Option<String>,
+    store             : Option<Arc<dyn store::EditorStore>>,
+    shutdown_timeout  : Duration,
+    lifecycle         : Option<lifecycle::LifecycleConfig>
 }
 
 impl EditorPlugin {
@@ -35,41 +65,210 @@ impl EditorPlugin {
         display_game_addr : String
     ) -> io::Result<Self> { Ok(Self {
         bind_addrs        : net::lookup_host(bind_addrs).await?.collect(),
-        display_game_addr
+        bind_policy       : webserver::BindPolicy::default(),
+        display_game_addr,
+        asset_dir         : PathBuf::from("editor_assets"),
+        audit_sink        : Box::new(audit::MemoryAuditSink::default()),
+        login_limits      : peer::guard::LoginLimits::default(),
+        connection_limits : peer::limits::ConnectionLimits::default(),
+        tls               : None,
+        proxy             : webserver::proxy::ProxyConfig::default(),
+        security          : webserver::security::SecurityConfig::default(),
+        metrics           : metrics::MetricsEndpoint::default(),
+        admin_secret      : None,
+        store             : None,
+        shutdown_timeout  : Duration::from_secs(10),
+        lifecycle         : None
     }) }
 
+    /// Sets whether the server starts when only some of the bind addresses can be listened on.
+    /// Defaults to [`webserver::BindPolicy::RequireAny`].
+    pub fn with_bind_policy(mut self, bind_policy : webserver::BindPolicy) -> Self {
+        self.bind_policy = bind_policy;
+        self
+    }
+
+    /// Sets the directory that third-party editor assets (Monaco, icons, fonts) are served from.
+    /// Defaults to `editor_assets`. Populate it with `tools/fetch_editor_assets.sh`.
+    pub fn with_asset_dir<P : Into<PathBuf>>(mut self, asset_dir : P) -> Self {
+        self.asset_dir = asset_dir.into();
+        self
+    }
+
+    /// Replaces where audit log entries are stored. Defaults to [`audit::MemoryAuditSink`].
+    pub fn with_audit_sink<S : audit::AuditSink>(mut self, sink : S) -> Self {
+        self.audit_sink = Box::new(sink);
+        self
+    }
+
+    /// Sets how failed logins are slowed down and banned. See [`peer::guard::LoginGuard`].
+    pub fn with_login_limits(mut self, limits : peer::guard::LoginLimits) -> Self {
+        self.login_limits = limits;
+        self
+    }
+
+    /// Sets timeouts and how many websocket connections may be open. See [`peer::limits::ConnectionLimits`].
+    pub fn with_connection_limits(mut self, limits : peer::limits::ConnectionLimits) -> Self {
+        self.connection_limits = limits;
+        self
+    }
+
+    /// Serves the editor over HTTPS, so that session codes are not sent in cleartext without a reverse proxy.
+    pub fn with_tls(mut self, tls : webserver::tls::TlsConfig) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
+    /// Sets the path prefix and public URL of the editor, and which reverse proxies to trust, when it is served behind one.
+    pub fn with_proxy(mut self, proxy : webserver::proxy::ProxyConfig) -> Self {
+        self.proxy = proxy;
+        self
+    }
+
+    /// Sets which other sites may connect to or frame the editor.
+    pub fn with_security(mut self, security : webserver::security::SecurityConfig) -> Self {
+        self.security = security;
+        self
+    }
+
+    /// Serves Prometheus metrics at `/metrics`. Disabled by default.
+    pub fn with_metrics(mut self, metrics : metrics::MetricsEndpoint) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Serves the admin dashboard at `/admin`, and its JSON API at `/admin/api`, to anyone with this secret.
+    /// It is given as a bearer token, or as the password of basic authentication. Disabled by default.
+    pub fn with_admin_secret<S : Into<String>>(mut self, secret : S) -> Self {
+        self.admin_secret = Some(secret.into());
+        self
+    }
+
+    /// Sets where changed files are saved when the app exits. See [`store::EditorStore`].
+    pub fn with_store<S : store::EditorStore>(mut self, store : S) -> Self {
+        self.store = Some(Arc::new(store));
+        self
+    }
+
+    /// Loads instances when sessions are opened on them, and saves and unloads them when they are not used.
+    /// Without this, the host has to open instances itself, eg. with [`registry::EditorRegistry`], and they are never unloaded.
+    pub fn with_lifecycle(mut self, lifecycle : lifecycle::LifecycleConfig) -> Self {
+        self.lifecycle = Some(lifecycle);
+        self
+    }
+
+    /// Sets how long disconnecting clients and saving files may take when the app exits. Defaults to 10 seconds.
+    pub fn with_shutdown_timeout(mut self, shutdown_timeout : Duration) -> Self {
+        self.shutdown_timeout = shutdown_timeout;
+        self
+    }
+
 }
 
 impl Plugin for EditorPlugin {
     fn build(self, app : &mut App) {
 
-        app.add_systems(Startup, run_webserver.pass((self.bind_addrs, self.display_game_addr)));
+        if let Some(lifecycle) = self.lifecycle {
+            app.add_systems(Startup, spawn_lifecycle.pass((lifecycle, self.store.clone())));
+        }
+        app.add_systems(Startup, run_webserver.pass(WebserverConfig {
+            bind_addrs        : self.bind_addrs,
+            bind_policy       : self.bind_policy,
+            display_game_addr : self.display_game_addr,
+            asset_dir         : self.asset_dir,
+            connection_limits : self.connection_limits,
+            tls               : self.tls,
+            proxy             : self.proxy,
+            security          : self.security,
+            metrics           : self.metrics,
+            admin_secret      : self.admin_secret,
+            store             : self.store,
+            shutdown_timeout  : self.shutdown_timeout
+        }));
+        app.add_systems(Startup, spawn_audit_log.pass(self.audit_sink));
+        app.add_systems(Startup, spawn_login_guard.pass(self.login_limits));
 
         app.add_systems(Cycle, instances::read_instance_events);
         app.add_systems(Cycle, instances::session::read_session_events);
         app.add_systems(Cycle, instances::session::update_state);
+        app.add_systems(Cycle, audit::update_audit_log);
+        app.add_systems(Cycle, peer::guard::cleanup_login_guards);
+        app.add_systems(Cycle, lifecycle::update_instance_lifecycle);
 
     }
 }
 
 
+async fn spawn_audit_log(
+    In(sink) : In<Box<dyn audit::AuditSink>>,
+    cmds     : Commands
+) {
+    cmds.spawn(audit::EditorAuditLog::new(sink)).await;
+}
+
+
+async fn spawn_login_guard(
+    In(limits) : In<peer::guard::LoginLimits>,
+    cmds       : Commands
+) {
+    cmds.spawn(peer::guard::LoginGuard::new(limits)).await;
+}
+
+
+async fn spawn_lifecycle(
+    In((config, store)) : In<(lifecycle::LifecycleConfig, Option<Arc<dyn store::EditorStore>>)>,
+    cmds                : Commands
+) {
+    cmds.spawn(lifecycle::InstanceLifecycle::new(config, store)).await;
+}
+
+
+/// What the editor server is started with, taken from the [`EditorPlugin`].
+struct WebserverConfig {
+    bind_addrs        : Vec<SocketAddr>,
+    bind_policy       : webserver::BindPolicy,
+    display_game_addr : String,
+    asset_dir         : PathBuf,
+    connection_limits : peer::limits::ConnectionLimits,
+    tls               : Option<webserver::tls::TlsConfig>,
+    proxy             : webserver::proxy::ProxyConfig,
+    security          : webserver::security::SecurityConfig,
+    metrics           : metrics::MetricsEndpoint,
+    admin_secret      : Option<String>,
+    store             : Option<Arc<dyn store::EditorStore>>,
+    shutdown_timeout  : Duration
+}
+
 async fn run_webserver(
-    In((bind_addrs, display_game_addr)) : In<(Vec<SocketAddr>, String)>,
-    cmds                                : Commands
+    In(config) : In<WebserverConfig>,
+    cmds       : Commands
 ) {
 
     info!("Starting editor server...");
+    let WebserverConfig { bind_addrs, bind_policy, display_game_addr, asset_dir, connection_limits, tls, proxy, security, metrics, admin_secret, store, shutdown_timeout } = config;
     match (UntilExitFuture::new(cmds.clone(), webserver::run(
         cmds.clone(),
         bind_addrs.as_slice(),
-        &display_game_addr
+        bind_policy,
+        &display_game_addr,
+        asset_dir,
+        connection_limits,
+        tls,
+        proxy,
+        security,
+        metrics,
+        admin_secret
     )).await) {
         Some(Err(err)) => {
             error!("Failed to start editor server: {}", err);
             cmds.exit(AppExit::Err(err.into()));
         },
-        _ => {
+        Some(Ok(())) => {
             debug!("Shut down editor server.");
+        },
+        // The app is exiting. The listeners were dropped, so no new connections are accepted.
+        None => {
+            shutdown::shutdown(cmds, store, shutdown_timeout).await;
         }
     }
 
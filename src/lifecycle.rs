@@ -0,0 +1,403 @@
+use crate::instances::EditorInstance;
+use crate::instances::session::EditorSession;
+use crate::store::{ self, EditorStore };
+use crate::lease::LeaseConfig;
+use lighthousemc_database::{ LighthouseDB, DBPlotID };
+use voxidian_logger::{ debug, warn, error };
+use axecs::prelude::*;
+use std::collections::{ BTreeMap, BTreeSet };
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{ Instant, Duration };
+use tokio::sync::oneshot;
+
+
+/// Loads the editor instance of a plot when a session is opened on it, and saves and unloads it once nobody has used it for a while.
+/// See [`crate::EditorPlugin::with_lifecycle`].
+pub struct LifecycleConfig {
+    database     : Arc<LighthouseDB>,
+    idle_timeout : Duration,
+    hooks        : Arc<dyn InstanceHooks>,
+    leases       : Option<LeaseConfig>
+}
+
+impl LifecycleConfig {
+
+    pub fn new(database : Arc<LighthouseDB>) -> Self { Self {
+        database,
+        idle_timeout : Duration::from_secs(300),
+        hooks        : Arc::new(NoHooks),
+        leases       : None
+    } }
+
+    /// Sets how long an instance stays loaded without any sessions. Defaults to 5 minutes.
+    pub fn with_idle_timeout(mut self, idle_timeout : Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    /// Sets what is called around loading and unloading instances, eg. to lock plots.
+    pub fn with_hooks<H : InstanceHooks>(mut self, hooks : H) -> Self {
+        self.hooks = Arc::new(hooks);
+        self
+    }
+
+    /// Holds a renewable lease on each plot while its instance is loaded, so that no other node edits it at the same time.
+    /// If a lease can not be renewed, the instance is unloaded without saving, and its sessions are disconnected.
+    pub fn with_leases(mut self, leases : LeaseConfig) -> Self {
+        self.leases = Some(leases);
+        self
+    }
+
+}
+
+
+/// Called around loading and unloading editor instances, so that the host can lock plots against other nodes.
+pub trait InstanceHooks : Send + Sync + 'static {
+
+    /// Called before an instance is loaded. If this fails, the instance is not loaded and the plot's sessions are closed.
+    fn before_load(&self, plot_id : DBPlotID) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send + '_>> {
+        let _ = plot_id;
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Called after an instance was saved and unloaded, or failed to load after [`InstanceHooks::before_load`] succeeded.
+    fn after_unload(&self, plot_id : DBPlotID) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        let _ = plot_id;
+        Box::pin(async { })
+    }
+
+}
+
+struct NoHooks;
+impl InstanceHooks for NoHooks { }
+
+
+#[derive(Component)]
+pub(crate) struct InstanceLifecycle {
+    config     : LifecycleConfig,
+    store      : Option<Arc<dyn EditorStore>>,
+    loading    : BTreeSet<DBPlotID>,
+    unloading  : BTreeSet<DBPlotID>,
+    /// When each loaded instance last stopped having sessions.
+    idle_since : BTreeMap<DBPlotID, Instant>,
+    held       : BTreeMap<DBPlotID, HeldLease>,
+    renewing   : BTreeSet<DBPlotID>
+}
+
+#[derive(Clone, Copy)]
+struct HeldLease {
+    expires_at : Instant,
+    renew_at   : Instant
+}
+
+impl HeldLease {
+    fn new(acquired_at : Instant, leases : &LeaseConfig) -> Self { Self {
+        expires_at : acquired_at + leases.ttl,
+        renew_at   : acquired_at + leases.renew_interval
+    } }
+}
+
+impl InstanceLifecycle {
+
+    pub(crate) fn new(config : LifecycleConfig, store : Option<Arc<dyn EditorStore>>) -> Self { Self {
+        config,
+        store,
+        loading    : BTreeSet::new(),
+        unloading  : BTreeSet::new(),
+        idle_since : BTreeMap::new(),
+        held       : BTreeMap::new(),
+        renewing   : BTreeSet::new()
+    } }
+
+}
+
+
+pub(crate) async fn update_instance_lifecycle(
+        cmds       : Commands,
+        instances  : Entities<(&EditorInstance)>,
+        sessions   : Entities<(&EditorSession)>,
+    mut lifecycles : Entities<(&mut InstanceLifecycle)>
+) {
+    let Some(lifecycle) = lifecycles.iter_mut().next() else { return; };
+    let now = Instant::now();
+
+    // Load the instances of plots that sessions were opened on.
+    let wanted = sessions.iter().filter(|session| ! session.is_closed()).map(|session| session.plot_id()).collect::<BTreeSet<_>>();
+    for &plot_id in &wanted {
+        if (lifecycle.loading.contains(&plot_id) || lifecycle.unloading.contains(&plot_id)) { continue; }
+        if (instances.iter().any(|instance| instance.plot_id() == plot_id)) { continue; }
+        lifecycle.loading.insert(plot_id);
+        tokio::spawn(load_instance(cmds.clone(), plot_id, Arc::clone(&lifecycle.config.database), lifecycle.store.clone(), Arc::clone(&lifecycle.config.hooks), lifecycle.config.leases.clone()));
+    }
+
+    // Unload the instances that nobody has used for a while.
+    for instance in &instances {
+        let plot_id = instance.plot_id();
+        if (wanted.contains(&plot_id)) {
+            lifecycle.idle_since.remove(&plot_id);
+            continue;
+        }
+        let idle_since = *lifecycle.idle_since.entry(plot_id).or_insert(now);
+        if (now.duration_since(idle_since) >= lifecycle.config.idle_timeout && lifecycle.unloading.insert(plot_id)) {
+            tokio::spawn(unload_instance(cmds.clone(), plot_id, lifecycle.store.clone(), Arc::clone(&lifecycle.config.hooks), lifecycle.config.leases.clone()));
+        }
+    }
+    lifecycle.idle_since.retain(|plot_id, _| instances.iter().any(|instance| instance.plot_id() == *plot_id));
+
+    // Renew leases. Instances that were loaded some other way take a lease straight away.
+    if let Some(leases) = &lifecycle.config.leases {
+        for instance in &instances {
+            let plot_id = instance.plot_id();
+            if (lifecycle.unloading.contains(&plot_id) || lifecycle.renewing.contains(&plot_id)) { continue; }
+            if (lifecycle.held.get(&plot_id).is_some_and(|held| now < held.renew_at)) { continue; }
+            lifecycle.renewing.insert(plot_id);
+            tokio::spawn(renew_lease(cmds.clone(), plot_id, Arc::clone(&lifecycle.config.hooks), leases.clone()));
+        }
+        lifecycle.held.retain(|plot_id, _| instances.iter().any(|instance| instance.plot_id() == *plot_id) || lifecycle.loading.contains(plot_id));
+    }
+}
+
+
+async fn load_instance(cmds : Commands, plot_id : DBPlotID, database : Arc<LighthouseDB>, store : Option<Arc<dyn EditorStore>>, hooks : Arc<dyn InstanceHooks>, leases : Option<LeaseConfig>) {
+    let acquired_at = Instant::now();
+    let result      = try_load_instance(plot_id, database, store.as_deref(), &*hooks, leases.as_ref()).await;
+    if let Err((err, _)) = &result {
+        error!("Failed to load editor instance of plot {}: {}", plot_id, err);
+    }
+
+    let loaded = result.is_ok();
+    let held   = leases.as_ref().map(|leases| HeldLease::new(acquired_at, leases));
+    let (tx, rx) = oneshot::channel();
+    let mut tx     = Some(tx);
+    let mut result = Some(result);
+    cmds.run_system(async move |cmds : Commands, instances : Scoped<Entities<(&'static EditorInstance)>>, sessions : Scoped<Entities<(&'static mut EditorSession)>>, lifecycles : Scoped<Entities<(&'static mut InstanceLifecycle)>>| {
+        let mut spawned = false;
+        match (result.take().unwrap()) {
+            Ok(instance) => {
+                if (! (&instances.lock().await).into_iter().any(|instance| instance.plot_id() == plot_id)) {
+                    cmds.spawn(instance).await;
+                    spawned = true;
+                    debug!("Loaded editor instance of plot {}.", plot_id);
+                }
+            },
+            Err((_, reason)) => {
+                for session in &mut sessions.lock().await {
+                    if (session.plot_id() == plot_id) { session.kick(reason); }
+                }
+            }
+        }
+        for lifecycle in &mut lifecycles.lock().await {
+            lifecycle.loading.remove(&plot_id);
+            if let (Some(held), true) = (held, spawned) {
+                lifecycle.held.insert(plot_id, held);
+            }
+        }
+        let _ = tx.take().unwrap().send(spawned);
+    }).await;
+
+    // Another instance of the plot was spawned while this one loaded, eg. by the registry, so this one is given back.
+    if (loaded && ! rx.await.unwrap_or(false)) {
+        hooks.after_unload(plot_id).await;
+        if let Some(leases) = &leases { release_lease(plot_id, leases).await; }
+    }
+}
+
+/// Takes the plot's lease, calls the hooks, and loads the instance. Everything is given back if a later step fails.
+async fn try_load_instance(plot_id : DBPlotID, database : Arc<LighthouseDB>, store : Option<&dyn EditorStore>, hooks : &dyn InstanceHooks, leases : Option<&LeaseConfig>) -> Result<EditorInstance, (String, &'static str)> {
+    if let Some(leases) = leases {
+        match (leases.store.acquire(plot_id, leases.node_id, leases.ttl).await) {
+            Ok(true)  => { },
+            Ok(false) => { return Err(("another node holds its lease".to_string(), "The plot is open on another server")); },
+            Err(err)  => { return Err((format!("lease could not be acquired: {}", err), "The plot could not be loaded")); }
+        }
+    }
+    let result = match (hooks.before_load(plot_id).await) {
+        Err(err) => Err(format!("plot could not be locked: {}", err)),
+        Ok(())   => {
+            // SAFETY: The lifecycle manager only loads plots without an instance, one at a time,
+            //         and the plot was leased and locked by the host above.
+            match (unsafe{ EditorInstance::create(plot_id, database) }.await) {
+                Ok(Some(mut instance)) => {
+                    if let Some(store) = store { store::restore_instance(store, &mut instance).await; }
+                    Ok(instance)
+                },
+                Ok(None)               => { hooks.after_unload(plot_id).await; Err("plot does not exist".to_string()) },
+                Err(err)               => { hooks.after_unload(plot_id).await; Err(format!("database error: {:?}", err)) }
+            }
+        }
+    };
+    if (result.is_err()) {
+        if let Some(leases) = leases { release_lease(plot_id, leases).await; }
+    }
+    result.map_err(|err| (err, "The plot could not be loaded"))
+}
+
+
+async fn unload_instance(cmds : Commands, plot_id : DBPlotID, store : Option<Arc<dyn EditorStore>>, hooks : Arc<dyn InstanceHooks>, leases : Option<LeaseConfig>) {
+    // Saving without the lease could overwrite another node's changes.
+    if let Some(leases) = &leases {
+        match (leases.store.acquire(plot_id, leases.node_id, leases.ttl).await) {
+            Ok(true)  => { },
+            Ok(false) => {
+                lose_lease(cmds, plot_id, hooks).await;
+                return;
+            },
+            Err(err) => {
+                warn!("Kept editor instance of plot {} loaded, as its lease could not be checked: {}", plot_id, err);
+                finish_unloading(cmds, plot_id).await;
+                return;
+            }
+        }
+    }
+
+    // Changed files are kept in memory until they are saved.
+    let saved = store::save_instances(cmds.clone(), store, move |id| id == plot_id).await;
+
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
+    cmds.run_system(async move |cmds : Commands, instances : Scoped<Entities<(Entity, &'static EditorInstance)>>, sessions : Scoped<Entities<(&'static EditorSession)>>| {
+        let mut unloaded = false;
+        // A session may have been opened while saving.
+        let in_use = (&sessions.lock().await).into_iter().any(|session| session.plot_id() == plot_id && ! session.is_closed());
+        if (saved && ! in_use) {
+            if let Some((entity, _)) = (&instances.lock().await).into_iter().find(|(_, instance)| instance.plot_id() == plot_id) {
+                cmds.despawn(entity).await;
+                unloaded = true;
+            }
+        }
+        let _ = tx.take().unwrap().send(unloaded);
+    }).await;
+
+    if (rx.await.unwrap_or(false)) {
+        hooks.after_unload(plot_id).await;
+        if let Some(leases) = &leases { release_lease(plot_id, leases).await; }
+        debug!("Unloaded editor instance of plot {}.", plot_id);
+    } else if (! saved) {
+        warn!("Kept editor instance of plot {} loaded, as its changed files could not be saved.", plot_id);
+    }
+    finish_unloading(cmds, plot_id).await;
+}
+
+async fn finish_unloading(cmds : Commands, plot_id : DBPlotID) {
+    cmds.run_system(async move |lifecycles : Scoped<Entities<(&'static mut InstanceLifecycle)>>| {
+        for lifecycle in &mut lifecycles.lock().await {
+            lifecycle.unloading.remove(&plot_id);
+            // Try again after another idle period.
+            lifecycle.idle_since.remove(&plot_id);
+        }
+    }).await;
+}
+
+
+async fn renew_lease(cmds : Commands, plot_id : DBPlotID, hooks : Arc<dyn InstanceHooks>, leases : LeaseConfig) {
+    let started = Instant::now();
+    let result  = leases.store.acquire(plot_id, leases.node_id, leases.ttl).await;
+    if let Err(err) = &result {
+        warn!("Failed to renew the lease of plot {}: {}", plot_id, err);
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
+    cmds.run_system(async move |lifecycles : Scoped<Entities<(&'static mut InstanceLifecycle)>>| {
+        let mut lost = false;
+        for lifecycle in &mut lifecycles.lock().await {
+            lifecycle.renewing.remove(&plot_id);
+            match (&result) {
+                Ok(true)  => { lifecycle.held.insert(plot_id, HeldLease::new(started, &leases)); },
+                Ok(false) => { lost = true; },
+                // The lease is still held until it expires, so retry shortly.
+                Err(_) => match (lifecycle.held.get_mut(&plot_id)) {
+                    Some(held) if (Instant::now() < held.expires_at) => { held.renew_at = Instant::now() + Duration::from_secs(1); },
+                    _                                                => { lost = true; }
+                }
+            }
+            if (lost) { lifecycle.held.remove(&plot_id); }
+        }
+        let _ = tx.take().unwrap().send(lost);
+    }).await;
+
+    if (rx.await.unwrap_or(false)) {
+        lose_lease(cmds, plot_id, hooks).await;
+    }
+}
+
+/// Disconnects every session on a plot whose lease was lost, and unloads its instance without saving, as another node may now be editing it.
+async fn lose_lease(cmds : Commands, plot_id : DBPlotID, hooks : Arc<dyn InstanceHooks>) {
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
+    cmds.run_system(async move |cmds : Commands, instances : Scoped<Entities<(Entity, &'static EditorInstance)>>, sessions : Scoped<Entities<(&'static mut EditorSession)>>, lifecycles : Scoped<Entities<(&'static mut InstanceLifecycle)>>| {
+        for session in &mut sessions.lock().await {
+            if (session.plot_id() == plot_id) { session.kick("The plot was opened on another server"); }
+        }
+        let mut unsaved = false;
+        if let Some((entity, instance)) = (&instances.lock().await).into_iter().find(|(_, instance)| instance.plot_id() == plot_id) {
+            unsaved = instance.has_unsaved_changes();
+            cmds.despawn(entity).await;
+        }
+        for lifecycle in &mut lifecycles.lock().await {
+            lifecycle.unloading.remove(&plot_id);
+            lifecycle.idle_since.remove(&plot_id);
+            lifecycle.held.remove(&plot_id);
+        }
+        let _ = tx.take().unwrap().send(unsaved);
+    }).await;
+    if (rx.await.unwrap_or(false)) {
+        error!("Lost the lease of plot {}. Its changed files were discarded, as another node may be editing it.", plot_id);
+    } else {
+        warn!("Lost the lease of plot {}, so its editor instance was unloaded.", plot_id);
+    }
+    hooks.after_unload(plot_id).await;
+}
+
+async fn release_lease(plot_id : DBPlotID, leases : &LeaseConfig) {
+    if let Err(err) = leases.store.release(plot_id, leases.node_id).await {
+        // It expires by itself.
+        warn!("Failed to release the lease of plot {}: {}", plot_id, err);
+    }
+}
+
+
+/// Whether instances are loaded by the lifecycle manager, rather than by whoever opens sessions.
+pub(crate) fn is_managed<'l, I : IntoIterator<Item = &'l InstanceLifecycle>>(lifecycles : I) -> bool {
+    lifecycles.into_iter().next().is_some()
+}
+
+
+/// Calls [`InstanceHooks::after_unload`] and releases the lease of every loaded instance, as the app is exiting.
+/// Their changed files should already have been saved.
+pub(crate) async fn release_all(cmds : Commands) {
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
+    cmds.run_system(async move |instances : Scoped<Entities<(&'static EditorInstance)>>, lifecycles : Scoped<Entities<(&'static InstanceLifecycle)>>| {
+        let config = (&lifecycles.lock().await).into_iter().next().map(|lifecycle| (Arc::clone(&lifecycle.config.hooks), lifecycle.config.leases.clone()));
+        let plots  = (&instances.lock().await).into_iter().map(|instance| instance.plot_id()).collect::<Vec<_>>();
+        let _ = tx.take().unwrap().send(config.map(|config| (config, plots)));
+    }).await;
+    let Ok(Some(((hooks, leases), plots))) = rx.await else { return; };
+    for plot_id in plots {
+        hooks.after_unload(plot_id).await;
+        if let Some(leases) = &leases { release_lease(plot_id, leases).await; }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lease::MemoryLeaseStore;
+    use uuid::Uuid;
+
+    #[test]
+    fn leases_are_renewed_before_they_expire() {
+        let leases = LeaseConfig::new(MemoryLeaseStore::new(), Uuid::from_u128(1))
+            .with_ttl(Duration::from_secs(30))
+            .with_renew_interval(Duration::from_secs(10));
+        let now  = Instant::now();
+        let held = HeldLease::new(now, &leases);
+        assert_eq!(held.renew_at, now + Duration::from_secs(10));
+        assert_eq!(held.expires_at, now + Duration::from_secs(30));
+        assert!(held.renew_at < held.expires_at);
+    }
+
+}
@@ -0,0 +1,225 @@
+use crate::instances::EditorInstance;
+use crate::instances::session::{ EditorSession, EditorSessionStep };
+use lighthousemc_editor_common::packet::DecodeError;
+use axecs::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{ AtomicU64, AtomicI64, Ordering };
+use std::time::Instant;
+use tokio::sync::oneshot;
+
+
+/// Where the Prometheus metrics are served. See [`crate::EditorPlugin::with_metrics`].
+#[derive(Clone, Copy, Debug, Default)]
+pub enum MetricsEndpoint {
+    #[default]
+    Disabled,
+    /// At `/metrics` on the editor server, next to every other route.
+    Editor,
+    /// At `/metrics` on a separate listener, so that it can be kept off the public network.
+    Separate(SocketAddr)
+}
+
+
+/// Counters that are kept for the whole process, shared by every instance and session.
+pub(crate) static METRICS : Metrics = Metrics::new();
+
+pub(crate) struct Metrics {
+    packets_in          : Mutex<BTreeMap<&'static str, PacketCounts>>,
+    packets_out         : Mutex<BTreeMap<&'static str, PacketCounts>>,
+    decode_errors       : [AtomicU64; 4],
+    patch_failures      : AtomicU64,
+    overwrite_fallbacks : AtomicU64,
+    sessions_activated  : AtomicU64,
+    sessions_kicked     : AtomicU64,
+    connections         : AtomicI64,
+    pending_connections : AtomicI64,
+    refused_connections : AtomicU64,
+    diff_seconds        : Histogram
+}
+
+#[derive(Clone, Copy, Default)]
+struct PacketCounts {
+    count : u64,
+    bytes : u64
+}
+
+const DECODE_ERROR_NAMES : [&str; 4] = ["EndOfBuffer", "InvalidData", "UnconsumedBuffer", "UnknownPacketPrefix"];
+
+/// Upper bounds of the diff time buckets, in seconds.
+const DIFF_BUCKETS : [f64; 10] = [0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.1, 1.0];
+
+struct Histogram {
+    buckets : [AtomicU64; DIFF_BUCKETS.len()],
+    count   : AtomicU64,
+    sum_ns  : AtomicU64
+}
+
+impl Metrics {
+
+    const fn new() -> Self { Self {
+        packets_in          : Mutex::new(BTreeMap::new()),
+        packets_out         : Mutex::new(BTreeMap::new()),
+        decode_errors       : [const { AtomicU64::new(0) }; 4],
+        patch_failures      : AtomicU64::new(0),
+        overwrite_fallbacks : AtomicU64::new(0),
+        sessions_activated  : AtomicU64::new(0),
+        sessions_kicked     : AtomicU64::new(0),
+        connections         : AtomicI64::new(0),
+        pending_connections : AtomicI64::new(0),
+        refused_connections : AtomicU64::new(0),
+        diff_seconds        : Histogram {
+            buckets : [const { AtomicU64::new(0) }; DIFF_BUCKETS.len()],
+            count   : AtomicU64::new(0),
+            sum_ns  : AtomicU64::new(0)
+        }
+    } }
+
+    pub(crate) fn packet_in(&self, kind : &'static str, bytes : usize) {
+        let mut packets = self.packets_in.lock().unwrap();
+        let     counts  = packets.entry(kind).or_default();
+        counts.count += 1;
+        counts.bytes += bytes as u64;
+    }
+
+    pub(crate) fn packet_out(&self, kind : &'static str, bytes : usize) {
+        let mut packets = self.packets_out.lock().unwrap();
+        let     counts  = packets.entry(kind).or_default();
+        counts.count += 1;
+        counts.bytes += bytes as u64;
+    }
+
+    pub(crate) fn decode_error(&self, err : &DecodeError) {
+        let index = match (err) {
+            DecodeError::EndOfBuffer            => 0,
+            DecodeError::InvalidData(_)         => 1,
+            DecodeError::UnconsumedBuffer       => 2,
+            DecodeError::UnknownPacketPrefix(_) => 3
+        };
+        self.decode_errors[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// A client's patches did not apply cleanly to the central text.
+    pub(crate) fn patch_failure(&self) { self.patch_failures.fetch_add(1, Ordering::Relaxed); }
+
+    /// A client was sent a whole file because a patch for it could not be made.
+    pub(crate) fn overwrite_fallback(&self) { self.overwrite_fallbacks.fetch_add(1, Ordering::Relaxed); }
+
+    pub(crate) fn session_activated(&self) { self.sessions_activated.fetch_add(1, Ordering::Relaxed); }
+
+    pub(crate) fn session_kicked(&self) { self.sessions_kicked.fetch_add(1, Ordering::Relaxed); }
+
+    /// A websocket connection was opened (`1`) or closed (`-1`), and whether it had not logged in yet.
+    pub(crate) fn connection(&self, delta : i64, pending : bool) {
+        self.connections.fetch_add(delta, Ordering::Relaxed);
+        if (pending) { self.pending_connections.fetch_add(delta, Ordering::Relaxed); }
+    }
+
+    pub(crate) fn connection_authenticated(&self) { self.pending_connections.fetch_sub(1, Ordering::Relaxed); }
+
+    pub(crate) fn connection_refused(&self) { self.refused_connections.fetch_add(1, Ordering::Relaxed); }
+
+    /// Runs a diff, recording how long it took.
+    pub(crate) fn time_diff<T, F : FnOnce() -> T>(&self, f : F) -> T {
+        let start = Instant::now();
+        let out   = f();
+        let took  = start.elapsed();
+        let secs  = took.as_secs_f64();
+        let histogram = &self.diff_seconds;
+        for (bound, bucket) in DIFF_BUCKETS.iter().zip(&histogram.buckets) {
+            if (secs <= *bound) { bucket.fetch_add(1, Ordering::Relaxed); }
+        }
+        histogram.count.fetch_add(1, Ordering::Relaxed);
+        histogram.sum_ns.fetch_add(took.as_nanos() as u64, Ordering::Relaxed);
+        out
+    }
+
+    /// Writes every metric in the Prometheus text format.
+    fn render(&self, instances : usize, pending_sessions : usize, active_sessions : usize) -> String {
+        let mut out = String::new();
+
+        let _ = writeln!(out, "# HELP lighthousemc_editor_instances Open editor instances.");
+        let _ = writeln!(out, "# TYPE lighthousemc_editor_instances gauge");
+        let _ = writeln!(out, "lighthousemc_editor_instances {}", instances);
+        let _ = writeln!(out, "# HELP lighthousemc_editor_sessions Editor sessions by status.");
+        let _ = writeln!(out, "# TYPE lighthousemc_editor_sessions gauge");
+        let _ = writeln!(out, "lighthousemc_editor_sessions{{status=\"pending\"}} {}", pending_sessions);
+        let _ = writeln!(out, "lighthousemc_editor_sessions{{status=\"active\"}} {}", active_sessions);
+        counter(&mut out, "lighthousemc_editor_sessions_activated_total", "Sessions that were logged in to.", self.sessions_activated.load(Ordering::Relaxed));
+        counter(&mut out, "lighthousemc_editor_sessions_kicked_total", "Sessions that were closed by the server.", self.sessions_kicked.load(Ordering::Relaxed));
+
+        let _ = writeln!(out, "# HELP lighthousemc_editor_connections Open websocket connections by whether they have logged in.");
+        let _ = writeln!(out, "# TYPE lighthousemc_editor_connections gauge");
+        let connections = self.connections.load(Ordering::Relaxed);
+        let pending     = self.pending_connections.load(Ordering::Relaxed);
+        let _ = writeln!(out, "lighthousemc_editor_connections{{status=\"pending\"}} {}", pending);
+        let _ = writeln!(out, "lighthousemc_editor_connections{{status=\"active\"}} {}", connections - pending);
+        counter(&mut out, "lighthousemc_editor_connections_refused_total", "Websocket connections refused by connection limits.", self.refused_connections.load(Ordering::Relaxed));
+
+        for (direction, packets) in [("in", &self.packets_in), ("out", &self.packets_out)] {
+            let packets = packets.lock().unwrap();
+            let _ = writeln!(out, "# HELP lighthousemc_editor_packets_{}_total Packets {} by kind.", direction, if (direction == "in") { "received" } else { "sent" });
+            let _ = writeln!(out, "# TYPE lighthousemc_editor_packets_{}_total counter", direction);
+            for (kind, counts) in packets.iter() {
+                let _ = writeln!(out, "lighthousemc_editor_packets_{}_total{{kind=\"{}\"}} {}", direction, kind, counts.count);
+            }
+            let _ = writeln!(out, "# HELP lighthousemc_editor_packet_bytes_{}_total Packet bytes {} by kind.", direction, if (direction == "in") { "received" } else { "sent" });
+            let _ = writeln!(out, "# TYPE lighthousemc_editor_packet_bytes_{}_total counter", direction);
+            for (kind, counts) in packets.iter() {
+                let _ = writeln!(out, "lighthousemc_editor_packet_bytes_{}_total{{kind=\"{}\"}} {}", direction, kind, counts.bytes);
+            }
+        }
+
+        let _ = writeln!(out, "# HELP lighthousemc_editor_decode_errors_total Packets that could not be decoded, by error.");
+        let _ = writeln!(out, "# TYPE lighthousemc_editor_decode_errors_total counter");
+        for (name, count) in DECODE_ERROR_NAMES.iter().zip(&self.decode_errors) {
+            let _ = writeln!(out, "lighthousemc_editor_decode_errors_total{{error=\"{}\"}} {}", name, count.load(Ordering::Relaxed));
+        }
+
+        counter(&mut out, "lighthousemc_editor_patch_failures_total", "Client patches that did not apply cleanly.", self.patch_failures.load(Ordering::Relaxed));
+        counter(&mut out, "lighthousemc_editor_overwrite_fallbacks_total", "Files resent whole because a patch could not be made.", self.overwrite_fallbacks.load(Ordering::Relaxed));
+
+        let histogram = &self.diff_seconds;
+        let _ = writeln!(out, "# HELP lighthousemc_editor_diff_seconds Time spent computing diffs.");
+        let _ = writeln!(out, "# TYPE lighthousemc_editor_diff_seconds histogram");
+        for (bound, bucket) in DIFF_BUCKETS.iter().zip(&histogram.buckets) {
+            let _ = writeln!(out, "lighthousemc_editor_diff_seconds_bucket{{le=\"{}\"}} {}", bound, bucket.load(Ordering::Relaxed));
+        }
+        let count = histogram.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "lighthousemc_editor_diff_seconds_bucket{{le=\"+Inf\"}} {}", count);
+        let _ = writeln!(out, "lighthousemc_editor_diff_seconds_sum {}", histogram.sum_ns.load(Ordering::Relaxed) as f64 / 1_000_000_000.0);
+        let _ = writeln!(out, "lighthousemc_editor_diff_seconds_count {}", count);
+
+        out
+    }
+
+}
+
+fn counter(out : &mut String, name : &str, help : &str, value : u64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
+
+/// Renders the metrics, counting instances and sessions as they are now.
+pub(crate) async fn render(cmds : Commands) -> String {
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
+    cmds.run_system(async move |instances : Scoped<Entities<(&'static EditorInstance)>>, sessions : Scoped<Entities<(&'static EditorSession)>>| {
+        let instances = (&instances.lock().await).into_iter().count();
+        let (mut pending, mut active) = (0, 0);
+        for session in &sessions.lock().await {
+            if (session.is_closed()) { continue; }
+            match (session.session_step()) {
+                EditorSessionStep::Pending { .. } => { pending += 1; },
+                EditorSessionStep::Active  { .. } => { active  += 1; }
+            }
+        }
+        let _ = tx.take().unwrap().send((instances, pending, active));
+    }).await;
+    let (instances, pending, active) = rx.await.unwrap_or_default();
+    METRICS.render(instances, pending, active)
+}
@@ -1,5 +1,8 @@
-use lighthousemc_editor_common::packet::{ self, PrefixedPacketEncode, PrefixedPacketDecode, DecodeError };
+use crate::metrics::METRICS;
+use lighthousemc_editor_common::packet::{ self, PrefixedPacketEncode, PrefixedPacketDecode, DecodeError, PacketMeta };
 use lighthousemc_editor_common::packet::s2c::*;
+use lighthousemc_editor_common::packet::c2s::*;
+use std::borrow::Cow;
 use std::time::Duration;
 use tokio::time::timeout;
 use axum::extract::ws::{ WebSocket, Message as WebSocketMessage };
@@ -7,12 +10,32 @@ use axum::body::Bytes;
 
 
 pub(crate) async fn send_packet(socket : &mut WebSocket, p : impl PrefixedPacketEncode) -> Result<(), ()> {
-    match (timeout(Duration::from_secs(1), socket.send(WebSocketMessage::Binary(Bytes::from(packet::encode(p))))).await) {
+    let data = packet::encode(p);
+    METRICS.packet_out(data.first().and_then(|prefix| S2CPackets::name_of_prefix(*prefix)).unwrap_or("Unknown"), data.len());
+    match (timeout(Duration::from_secs(1), socket.send(WebSocketMessage::Binary(Bytes::from(data)))).await) {
         Ok(out) => out.map_err(|_| ()),
         Err(_) => Err(())
     }
 }
 
+/// How long clients wait before reconnecting after the server restarts.
+const RESTART_RETRY_AFTER : Duration = Duration::from_secs(5);
+
+/// Tells the client why it is being disconnected, and closes the websocket.
+pub(crate) async fn disconnect(socket : &mut WebSocket, reason : Cow<'static, str>) {
+    let _ = send_packet(socket, DisconnectS2CPacket { reason, retry_after_ms : None }).await;
+    let _ = timeout(Duration::from_secs(1), socket.send(WebSocketMessage::Close(None))).await;
+}
+
+/// Tells the client that the server is restarting, so that it reconnects by itself, and closes the websocket.
+pub(crate) async fn disconnect_restarting(socket : &mut WebSocket) {
+    let _ = send_packet(socket, DisconnectS2CPacket {
+        reason         : Cow::Borrowed("The server is restarting."),
+        retry_after_ms : Some(RESTART_RETRY_AFTER.as_millis() as u32)
+    }).await;
+    let _ = timeout(Duration::from_secs(1), socket.send(WebSocketMessage::Close(None))).await;
+}
+
 pub(crate) async fn read_packet<P : PrefixedPacketDecode>(socket : &mut WebSocket) -> Result<P, ()> {
     let out = socket.recv().await;
     handle_packet_result::<P>(socket, out).await
@@ -27,15 +50,21 @@ pub(crate) async fn try_read_packet<P : PrefixedPacketDecode>(socket : &mut WebS
 
 async fn handle_packet_result<P : PrefixedPacketDecode>(socket : &mut WebSocket, out : Option<Result<WebSocketMessage, axum::Error>>) -> Result<P, ()> {
     let out = match (out) {
-        Some(Ok(WebSocketMessage::Binary(data))) => { match (packet::decode::<P>(&data)) {
-            Ok(out) => Ok(Ok(out)),
-            Err(err) => { match (err) {
-                DecodeError::EndOfBuffer            => Err("incomplete packet".into()),
-                DecodeError::InvalidData(_)         => Err("invalid packet data".into()),
-                DecodeError::UnconsumedBuffer       => Err("invalid packet".into()),
-                DecodeError::UnknownPacketPrefix(_) => Err("unknown packet".into()),
-            } }
-        } },
+        Some(Ok(WebSocketMessage::Binary(data))) => {
+            METRICS.packet_in(packet_in_name(&data), data.len());
+            match (packet::decode::<P>(&data)) {
+                Ok(out) => Ok(Ok(out)),
+                Err(err) => {
+                    METRICS.decode_error(&err);
+                    match (err) {
+                        DecodeError::EndOfBuffer            => Err("incomplete packet".into()),
+                        DecodeError::InvalidData(_)         => Err("invalid packet data".into()),
+                        DecodeError::UnconsumedBuffer       => Err("invalid packet".into()),
+                        DecodeError::UnknownPacketPrefix(_) => Err("unknown packet".into()),
+                    }
+                }
+            }
+        },
         Some(Ok(_))  => Err("bad packet format".into()),
         Some(Err(_)) => Err("connection interrupted".into()),
         None         => Ok(Err(()))
@@ -43,8 +72,17 @@ async fn handle_packet_result<P : PrefixedPacketDecode>(socket : &mut WebSocket,
     match (out) {
         Ok(out) => out,
         Err(err) => {
-            let _ = send_packet(socket, DisconnectS2CPacket { reason : err }).await;
+            disconnect(socket, err).await;
             Err(())
         }
     }
 }
+
+/// The kind of a received packet, for metrics.
+fn packet_in_name(data : &[u8]) -> &'static str {
+    match (data.first()) {
+        Some(&prefix) if (prefix == <HandshakeC2SPacket as PacketMeta>::PREFIX) => "Handshake",
+        Some(&prefix) => C2SPackets::name_of_prefix(prefix).unwrap_or("Unknown"),
+        None          => "Unknown"
+    }
+}
@@ -0,0 +1,130 @@
+use crate::instances::{ EditorInstance, EditorInstanceEvent };
+use crate::instances::session::{ EditorSession, EditorSessionStep, EditorPermission };
+use crate::audit::AuditEvent;
+use crate::peer::guard::codes_match;
+use lighthousemc_editor_common::packet::s2c::FileContents;
+use lighthousemc_database::{ DBPlotID, DBFSFileID };
+use uuid::Uuid;
+use axecs::prelude::*;
+use tokio::sync::oneshot;
+
+
+pub struct FileDownload {
+    pub fsname : String,
+    pub mime   : String,
+    pub data   : Vec<u8>
+}
+
+/// Why a file could not be read or written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FileAccessError {
+    /// No active session has the given code.
+    NoSession,
+    /// The session may only suggest edits.
+    ReadOnly,
+    NoFile
+}
+
+
+/// Reads the contents of a file on the plot of the active session with the given code.
+pub(crate) async fn read_file(cmds : Commands, session_code : String, file_id : DBFSFileID) -> Result<FileDownload, FileAccessError> {
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
+    cmds.run_system(async move |instances, sessions| {
+        let _ = tx.take().unwrap().send(try_read_file(&session_code, file_id, instances, sessions).await);
+    }).await;
+    rx.await.unwrap_or(Err(FileAccessError::NoFile))
+}
+
+async fn try_read_file(
+        session_code : &str,
+        file_id      : DBFSFileID,
+    mut instances    : Scoped<Entities<(&'static EditorInstance)>>,
+    mut sessions     : Scoped<Entities<(&'static EditorSession)>>
+) -> Result<FileDownload, FileAccessError> {
+    let (plot_id, _, _, _) = find_active_session(session_code, &mut sessions).await.ok_or(FileAccessError::NoSession)?;
+    for instance in &instances.lock().await {
+        if (instance.plot_id() == plot_id) {
+            let file = instance.state.files().get(&file_id).ok_or(FileAccessError::NoFile)?;
+            return Ok(FileDownload {
+                fsname : file.fsname().to_string(),
+                mime   : match (file.contents()) {
+                    FileContents::Binary { .. } => file.mime().to_string(),
+                    FileContents::Text(_)       => format!("{}; charset=utf-8", file.mime())
+                },
+                data   : file.bytes().to_vec()
+            });
+        }
+    }
+    Err(FileAccessError::NoFile)
+}
+
+
+/// Replaces the contents of a file on the plot of the active session with the given code.
+pub(crate) async fn write_file(cmds : Commands, session_code : String, file_id : DBFSFileID, data : Vec<u8>) -> Result<(), FileAccessError> {
+    let (tx, rx) = oneshot::channel();
+    let mut tx   = Some(tx);
+    let mut data = Some(data);
+    cmds.run_system(async move |instances, sessions| {
+        let _ = tx.take().unwrap().send(try_write_file(&session_code, file_id, data.take().unwrap(), instances, sessions).await);
+    }).await;
+    rx.await.unwrap_or(Err(FileAccessError::NoFile))
+}
+
+async fn try_write_file(
+        session_code : &str,
+        file_id      : DBFSFileID,
+        data         : Vec<u8>,
+    mut instances    : Scoped<Entities<(&'static mut EditorInstance)>>,
+    mut sessions     : Scoped<Entities<(&'static EditorSession)>>
+) -> Result<(), FileAccessError> {
+    let (plot_id, client_uuid, client_name, permission) = find_active_session(session_code, &mut sessions).await.ok_or(FileAccessError::NoSession)?;
+    require_write(permission)?;
+    for instance in &mut instances.lock().await {
+        if (instance.plot_id() == plot_id) {
+            let file = instance.state.files_mut().get_mut(&file_id).ok_or(FileAccessError::NoFile)?;
+            let size = data.len() as u64;
+            file.set_bytes(data, Some((client_uuid, &client_name)));
+            file.mark_unsaved();
+            instance.push_event(EditorInstanceEvent::OverwriteFile { file_id });
+            let path = instance.state.file_path(file_id).unwrap_or_default();
+            instance.audit(client_uuid, &client_name, AuditEvent::FileUploaded { file_id, path, size });
+            return Ok(());
+        }
+    }
+    Err(FileAccessError::NoFile)
+}
+
+fn require_write(permission : EditorPermission) -> Result<(), FileAccessError> {
+    match (permission) {
+        EditorPermission::Write   => Ok(()),
+        EditorPermission::Suggest => Err(FileAccessError::ReadOnly)
+    }
+}
+
+
+/// Every active session is compared, so that timing does not reveal anything about the code.
+async fn find_active_session(session_code : &str, sessions : &mut Scoped<Entities<(&'static EditorSession)>>) -> Option<(DBPlotID, Uuid, String, EditorPermission)> {
+    let mut found = None;
+    for session in &sessions.lock().await {
+        if let EditorSessionStep::Active { .. } = session.session_step() {
+            if (codes_match(session.session_code(), session_code) && found.is_none()) {
+                found = Some((session.plot_id(), session.client_uuid(), session.client_name().to_string(), session.permission()));
+            }
+        }
+    }
+    found
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggest_sessions_can_not_write() {
+        assert_eq!(require_write(EditorPermission::Write), Ok(()));
+        assert_eq!(require_write(EditorPermission::Suggest), Err(FileAccessError::ReadOnly));
+    }
+
+}
@@ -0,0 +1,303 @@
+use voxidian_logger::warn;
+use axecs::prelude::*;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{ Instant, Duration };
+use tokio::sync::oneshot;
+
+
+/// The least estimated entropy that a session code chosen by the host must have.
+pub const MIN_SESSION_CODE_BITS : u32 = 96;
+
+
+/// How many failed logins an address is allowed, and how it is slowed down and banned after that.
+#[derive(Clone, Copy, Debug)]
+pub struct LoginLimits {
+    /// Failed logins allowed before each further attempt has to wait.
+    pub free_attempts   : u32,
+    /// The wait after the first failure past `free_attempts`. It doubles with each further failure.
+    pub initial_backoff : Duration,
+    pub max_backoff     : Duration,
+    /// Failed logins after which the address is banned.
+    pub ban_after       : u32,
+    pub ban_duration    : Duration,
+    /// Failures are forgotten after this long without any.
+    pub forget_after    : Duration
+}
+
+impl Default for LoginLimits {
+    fn default() -> Self { Self {
+        free_attempts   : 5,
+        initial_backoff : Duration::from_secs(1),
+        max_backoff     : Duration::from_secs(60),
+        ban_after       : 20,
+        ban_duration    : Duration::from_secs(60 * 60),
+        forget_after    : Duration::from_secs(60 * 60)
+    } }
+}
+
+
+/// Tracks failed logins per address, so that session codes can not be guessed.
+#[derive(Component)]
+pub struct LoginGuard {
+    limits    : LoginLimits,
+    addresses : HashMap<IpAddr, LoginFailures>
+}
+
+struct LoginFailures {
+    count        : u32,
+    last_failure : Instant,
+    retry_at     : Instant,
+    banned_until : Option<Instant>
+}
+
+/// Why a login attempt was refused before its session code was checked.
+#[derive(Clone, Copy, Debug)]
+pub enum LoginRefusal {
+    Backoff { retry_at : Instant },
+    Banned { until : Instant }
+}
+
+impl LoginGuard {
+
+    pub(crate) fn new(limits : LoginLimits) -> Self { Self {
+        limits,
+        addresses : HashMap::new()
+    } }
+
+    pub fn limits(&self) -> LoginLimits {
+        self.limits
+    }
+
+    /// Whether an address may try to log in right now.
+    pub fn check(&self, address : IpAddr) -> Result<(), LoginRefusal> {
+        let Some(failures) = self.addresses.get(&address) else { return Ok(()); };
+        let now = Instant::now();
+        if let Some(until) = failures.banned_until.filter(|until| *until > now) {
+            return Err(LoginRefusal::Banned { until });
+        }
+        if (failures.retry_at > now) {
+            return Err(LoginRefusal::Backoff { retry_at : failures.retry_at });
+        }
+        Ok(())
+    }
+
+    pub(crate) fn record_failure(&mut self, address : IpAddr) {
+        let now    = Instant::now();
+        let limits = self.limits;
+        let failures = self.addresses.entry(address).or_insert(LoginFailures { count : 0, last_failure : now, retry_at : now, banned_until : None });
+        if (now.duration_since(failures.last_failure) >= limits.forget_after) {
+            failures.count = 0;
+        }
+        failures.count        += 1;
+        failures.last_failure  = now;
+        let over = failures.count.saturating_sub(limits.free_attempts);
+        if (over > 0) {
+            let backoff = limits.initial_backoff.saturating_mul(1 << (over - 1).min(16)).min(limits.max_backoff);
+            failures.retry_at = now + backoff;
+        }
+        if (failures.count >= limits.ban_after) {
+            failures.banned_until = Some(now + limits.ban_duration);
+            warn!("Banned {} from logging in to the editor for {}s after {} failed attempts.", address, limits.ban_duration.as_secs(), failures.count);
+        } else {
+            warn!("Failed editor login from {} ({} in a row).", address, failures.count);
+        }
+    }
+
+    /// Forgets the failed attempts of an address, unless it is banned.
+    pub(crate) fn record_success(&mut self, address : IpAddr) {
+        let Some(failures) = self.addresses.get_mut(&address) else { return; };
+        let now = Instant::now();
+        if (failures.banned_until.is_some_and(|until| until > now)) { return; }
+        failures.count    = 0;
+        failures.retry_at = now;
+    }
+
+
+    /// Addresses that are currently banned, and until when.
+    pub fn bans(&self) -> Vec<(IpAddr, Instant)> {
+        let now = Instant::now();
+        self.addresses.iter().filter_map(|(address, failures)| failures.banned_until.filter(|until| *until > now).map(|until| (*address, until))).collect()
+    }
+
+    pub fn is_banned(&self, address : IpAddr) -> bool {
+        matches!(self.check(address), Err(LoginRefusal::Banned { .. }))
+    }
+
+    /// Lifts a ban and forgets the failed attempts of an address. Returns `false` if it had none.
+    pub fn unban(&mut self, address : IpAddr) -> bool {
+        self.addresses.remove(&address).is_some()
+    }
+
+    /// Forgets addresses whose failures have expired.
+    pub(crate) fn cleanup(&mut self) {
+        let now          = Instant::now();
+        let forget_after = self.limits.forget_after;
+        self.addresses.retain(|_, failures| {
+            failures.banned_until.is_some_and(|until| until > now) || now.duration_since(failures.last_failure) < forget_after
+        });
+    }
+
+}
+
+
+/// Roughly estimates how many bits of entropy a session code has, from its length and the kinds of characters in it.
+/// Codes that repeat characters a lot are counted as if they were only as long as their distinct characters.
+pub fn session_code_bits(code : &str) -> u32 {
+    let mut pool = 0;
+    if (code.chars().any(|ch| ch.is_ascii_lowercase())) { pool += 26; }
+    if (code.chars().any(|ch| ch.is_ascii_uppercase())) { pool += 26; }
+    if (code.chars().any(|ch| ch.is_ascii_digit())) { pool += 10; }
+    if (code.chars().any(|ch| ! ch.is_ascii_alphanumeric())) { pool += 32; }
+    if (pool == 0) { return 0; }
+    let mut distinct = code.chars().collect::<Vec<_>>();
+    distinct.sort();
+    distinct.dedup();
+    let len = code.chars().count().min(distinct.len() * 2);
+    (len as f64 * (pool as f64).log2()) as u32
+}
+
+/// Compares session codes in time that does not depend on where they differ.
+pub(crate) fn codes_match(a : &str, b : &str) -> bool {
+    a.len() == b.len() && openssl::memcmp::eq(a.as_bytes(), b.as_bytes())
+}
+
+
+/// Whether an address may try to log in right now. For requests that are not handled by a system holding the guard.
+pub(crate) async fn check_address(cmds : Commands, address : IpAddr) -> Result<(), LoginRefusal> {
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
+    cmds.run_system(async move |guards : Scoped<Entities<(&'static LoginGuard)>>| {
+        let refusal = (&guards.lock().await).into_iter().find_map(|guard| guard.check(address).err());
+        let _ = tx.take().unwrap().send(refusal);
+    }).await;
+    match (rx.await.ok().flatten()) {
+        Some(refusal) => Err(refusal),
+        None          => Ok(())
+    }
+}
+
+/// Records a wrong secret given by an address. See [`check_address`].
+pub(crate) async fn record_failure(cmds : Commands, address : IpAddr) {
+    cmds.run_system(async move |guards : Scoped<Entities<(&'static mut LoginGuard)>>| {
+        for guard in &mut guards.lock().await {
+            guard.record_failure(address);
+        }
+    }).await;
+}
+
+
+pub(crate) async fn cleanup_login_guards(
+    mut guards : Entities<(&mut LoginGuard)>
+) {
+    for guard in &mut guards {
+        guard.cleanup();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instances::session::EditorSession;
+    use std::net::Ipv4Addr;
+
+    const ADDRESS : IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+    fn guard() -> LoginGuard {
+        LoginGuard::new(LoginLimits {
+            free_attempts   : 2,
+            initial_backoff : Duration::from_secs(60),
+            max_backoff     : Duration::from_secs(60 * 4),
+            ban_after       : 10,
+            ban_duration    : Duration::from_secs(60 * 60),
+            forget_after    : Duration::from_secs(60 * 60)
+        })
+    }
+
+    fn backoff(guard : &LoginGuard) -> Option<Duration> {
+        match (guard.check(ADDRESS)) {
+            Err(LoginRefusal::Backoff { retry_at }) => Some(retry_at.saturating_duration_since(Instant::now())),
+            _                                       => None
+        }
+    }
+
+    #[test]
+    fn random_codes_are_strong() {
+        for _ in 0..64 {
+            let code = EditorSession::random_code::<24>().unwrap();
+            assert!(session_code_bits(&code) >= MIN_SESSION_CODE_BITS, "{}", code);
+        }
+    }
+
+    #[test]
+    fn simple_codes_are_weak() {
+        assert_eq!(session_code_bits(""), 0);
+        assert!(session_code_bits("hunter2") < MIN_SESSION_CODE_BITS);
+        assert!(session_code_bits("0123456789012345678901234567890123456789") < MIN_SESSION_CODE_BITS);
+        // Repeating a few characters does not make a code stronger.
+        assert_eq!(session_code_bits(&"ab".repeat(64)), session_code_bits("abab"));
+        assert!(session_code_bits("Xk3-q9Lr7TzM2wPa8vYc4NbH") >= MIN_SESSION_CODE_BITS);
+    }
+
+    #[test]
+    fn codes_match_exactly() {
+        assert!(codes_match("Xk3-q9Lr", "Xk3-q9Lr"));
+        assert!(! codes_match("Xk3-q9Lr", "Xk3-q9Ls"));
+        assert!(! codes_match("Xk3-q9Lr", "Xk3-q9L"));
+        assert!(! codes_match("", "a"));
+    }
+
+    #[test]
+    fn free_attempts_are_not_delayed() {
+        let mut guard = guard();
+        for _ in 0..2 {
+            guard.record_failure(ADDRESS);
+            assert!(guard.check(ADDRESS).is_ok());
+        }
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_its_limit() {
+        let mut guard = guard();
+        for _ in 0..2 { guard.record_failure(ADDRESS); }
+        let mut previous = Duration::ZERO;
+        for expected in [60, 120, 240] {
+            guard.record_failure(ADDRESS);
+            let backoff = backoff(&guard).unwrap();
+            assert!(backoff > previous && backoff <= Duration::from_secs(expected) && backoff > Duration::from_secs(expected - 5));
+            previous = backoff;
+        }
+        guard.record_failure(ADDRESS);
+        assert!(backoff(&guard).unwrap() <= Duration::from_secs(240));
+    }
+
+    #[test]
+    fn repeated_failures_are_banned() {
+        let mut guard = guard();
+        for _ in 0..9 { guard.record_failure(ADDRESS); }
+        assert!(! guard.is_banned(ADDRESS));
+        guard.record_failure(ADDRESS);
+        assert!(guard.is_banned(ADDRESS));
+        assert_eq!(guard.bans().len(), 1);
+        assert!(guard.unban(ADDRESS));
+        assert!(guard.check(ADDRESS).is_ok());
+    }
+
+    #[test]
+    fn success_forgets_failures() {
+        let mut guard = guard();
+        for _ in 0..3 { guard.record_failure(ADDRESS); }
+        assert!(guard.check(ADDRESS).is_err());
+        guard.record_success(ADDRESS);
+        assert!(guard.check(ADDRESS).is_ok());
+        let other = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+        for _ in 0..3 { guard.record_failure(other); }
+        assert!(guard.check(ADDRESS).is_ok());
+        // A ban outlasts a success.
+        for _ in 0..10 { guard.record_failure(other); }
+        guard.record_success(other);
+        assert!(guard.is_banned(other));
+    }
+
+}
@@ -0,0 +1,136 @@
+use crate::metrics::METRICS;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{ Arc, Mutex };
+use std::time::Duration;
+
+
+/// How long websocket connections may stay open, and how many may be open at once.
+#[derive(Clone, Copy, Debug)]
+pub struct ConnectionLimits {
+    /// How long a new connection has to send its handshake.
+    pub handshake_timeout           : Duration,
+    /// Connections that have not logged in yet, from one address.
+    pub max_pending_per_address     : usize,
+    /// Connections that have not logged in yet, from all addresses.
+    pub max_pending                 : usize,
+    /// Connections from one address, logged in or not.
+    pub max_connections_per_address : usize,
+    /// Connections from all addresses, logged in or not.
+    pub max_connections             : usize,
+    /// How often logged in clients are sent keepalives. The client disconnects itself if it gets none for 5 seconds.
+    pub keepalive_interval          : Duration,
+    /// How long a logged in client may send nothing, not even a keepalive reply, before it is disconnected.
+    pub keepalive_timeout           : Duration,
+    /// How long a logged in client may send nothing but keepalive replies before it is disconnected.
+    pub idle_timeout                : Duration,
+    /// The largest file that may be uploaded over HTTP, in bytes.
+    pub max_upload_size             : usize
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self { Self {
+        handshake_timeout           : Duration::from_secs(10),
+        max_pending_per_address     : 4,
+        max_pending                 : 256,
+        max_connections_per_address : 16,
+        max_connections             : 1024,
+        keepalive_interval          : Duration::from_secs(2),
+        keepalive_timeout           : Duration::from_secs(10),
+        idle_timeout                : Duration::from_secs(30 * 60),
+        max_upload_size             : 16 * 1024 * 1024
+    } }
+}
+
+
+/// Counts the open websocket connections, shared by every connection handler.
+pub(crate) struct ConnectionTracker {
+    limits : ConnectionLimits,
+    counts : Mutex<ConnectionCounts>
+}
+
+#[derive(Default)]
+struct ConnectionCounts {
+    pending   : usize,
+    total     : usize,
+    addresses : HashMap<IpAddr, AddressCounts>
+}
+
+#[derive(Default)]
+struct AddressCounts {
+    pending : usize,
+    total   : usize
+}
+
+impl ConnectionTracker {
+
+    pub(crate) fn new(limits : ConnectionLimits) -> Self { Self {
+        limits,
+        counts : Mutex::new(ConnectionCounts::default())
+    } }
+
+    /// Counts a new connection from an address, or returns why it is refused.
+    pub(crate) fn try_open(self : &Arc<Self>, address : IpAddr) -> Result<ConnectionPermit, &'static str> {
+        let mut counts = self.counts.lock().unwrap();
+        let     limits = self.limits;
+        if (counts.total >= limits.max_connections || counts.pending >= limits.max_pending) {
+            METRICS.connection_refused();
+            return Err("The editor server is too busy. Try again later.");
+        }
+        let address_counts = counts.addresses.get(&address);
+        if (address_counts.is_some_and(|address_counts| address_counts.total >= limits.max_connections_per_address || address_counts.pending >= limits.max_pending_per_address)) {
+            METRICS.connection_refused();
+            return Err("Too many connections from your address.");
+        }
+        METRICS.connection(1, true);
+        counts.pending += 1;
+        counts.total   += 1;
+        let address_counts = counts.addresses.entry(address).or_default();
+        address_counts.pending += 1;
+        address_counts.total   += 1;
+        Ok(ConnectionPermit { tracker : Arc::clone(self), address, pending : true })
+    }
+
+}
+
+
+/// A counted connection. It is uncounted when dropped.
+pub(crate) struct ConnectionPermit {
+    tracker : Arc<ConnectionTracker>,
+    address : IpAddr,
+    pending : bool
+}
+
+impl ConnectionPermit {
+
+    pub(crate) fn limits(&self) -> ConnectionLimits {
+        self.tracker.limits
+    }
+
+    /// Stops counting the connection as pending, once it has logged in.
+    pub(crate) fn authenticate(&mut self) {
+        if (! self.pending) { return; }
+        self.pending = false;
+        METRICS.connection_authenticated();
+        let mut counts = self.tracker.counts.lock().unwrap();
+        counts.pending -= 1;
+        if let Some(address_counts) = counts.addresses.get_mut(&self.address) {
+            address_counts.pending -= 1;
+        }
+    }
+
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        METRICS.connection(-1, self.pending);
+        let mut counts = self.tracker.counts.lock().unwrap();
+        counts.total -= 1;
+        if (self.pending) { counts.pending -= 1; }
+        if let Some(address_counts) = counts.addresses.get_mut(&self.address) {
+            address_counts.total -= 1;
+            if (self.pending) { address_counts.pending -= 1; }
+            if (address_counts.total == 0) { counts.addresses.remove(&self.address); }
+        }
+    }
+}
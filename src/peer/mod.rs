@@ -1,9 +1,14 @@
 use crate::instances::EditorInstance;
-use crate::instances::session::{ EditorSession, EditorSessionStep };
+use crate::audit::AuditEvent;
+use crate::instances::session::{ EditorSession, EditorSessionStep, EditorPermission };
 use lighthousemc_editor_common::packet::s2c::*;
 use lighthousemc_editor_common::packet::c2s::*;
 use axecs::prelude::*;
 use core::ops::{ Deref, DerefMut };
+use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::time::Instant;
+use tokio::time::timeout;
 use tokio::sync::mpsc;
 use tokio::task::yield_now;
 use axum::extract::ws::WebSocket;
@@ -11,6 +16,14 @@ use axum::extract::ws::WebSocket;
 
 mod comms;
 
+pub mod guard;
+use guard::{ LoginGuard, LoginRefusal };
+
+pub mod limits;
+use limits::{ ConnectionLimits, ConnectionPermit };
+
+pub(crate) mod files;
+
 pub struct WebSocketWrapper {
     socket : WebSocket
 }
@@ -27,7 +40,8 @@ unsafe impl Sync for WebSocketWrapper { }
 
 pub enum OutgoingPeerCommand {
     Send(S2CPackets<'static>),
-    Close
+    /// Disconnects the client, showing it the reason.
+    Close(Cow<'static, str>)
 }
 
 pub enum IncomingPeerEvent {
@@ -36,34 +50,78 @@ pub enum IncomingPeerEvent {
 }
 
 
-pub(super) async fn handle_editor_websocket(cmds : Commands, mut socket : WebSocket) {
-    let Ok(handshake) = comms::read_packet::<HandshakeC2SPacket>(&mut socket).await else { return; };
+pub(super) async fn handle_editor_websocket(cmds : Commands, mut socket : WebSocket, address : SocketAddr, permit : Result<ConnectionPermit, &'static str>) {
+    let permit = match (permit) {
+        Ok(permit) => permit,
+        Err(reason) => {
+            comms::disconnect(&mut socket, reason.into()).await;
+            return;
+        }
+    };
+    let limits = permit.limits();
+    let handshake = match (timeout(limits.handshake_timeout, comms::read_packet::<HandshakeC2SPacket>(&mut socket)).await) {
+        Ok(Ok(handshake)) => handshake,
+        Ok(Err(_))        => { return; },
+        Err(_)            => {
+            comms::disconnect(&mut socket, "Took too long to log in".into()).await;
+            return;
+        }
+    };
     let mut socket = Some(WebSocketWrapper { socket });
-    cmds.run_system(async move |cmds, instances, sessions| {
-        try_login_editor_websocket(cmds, socket.take().unwrap(), &handshake, instances, sessions).await;
+    let mut permit = Some(permit);
+    cmds.run_system(async move |cmds, instances, sessions, guards| {
+        try_login_editor_websocket(cmds, socket.take().unwrap(), address, permit.take().unwrap(), &handshake, instances, sessions, guards).await;
     }).await;
 }
 
 async fn try_login_editor_websocket(
         cmds      : Commands,
     mut socket    : WebSocketWrapper,
+        address   : SocketAddr,
+    mut permit    : ConnectionPermit,
         handshake : &HandshakeC2SPacket<'_>,
-    mut instances : Scoped<Entities<(&'static EditorInstance)>>,
-    mut sessions  : Scoped<Entities<(&'static mut EditorSession)>>
+    mut instances : Scoped<Entities<(&'static mut EditorInstance)>>,
+    mut sessions  : Scoped<Entities<(&'static mut EditorSession)>>,
+    mut guards    : Scoped<Entities<(&'static mut LoginGuard)>>
 ) {
-    // Find the relevant session.
-    let mut result = None;
+    if (cmds.is_exiting()) {
+        comms::disconnect_restarting(&mut socket).await;
+        return;
+    }
+
+    // Refuse addresses that failed to log in too often.
+    let refusal = (&guards.lock().await).into_iter().find_map(|guard| guard.check(address.ip()).err());
+    if let Some(refusal) = refusal {
+        let reason = match (refusal) {
+            LoginRefusal::Backoff { retry_at } => format!("Too many failed attempts. Try again in {} seconds.", retry_at.saturating_duration_since(Instant::now()).as_secs() + 1),
+            LoginRefusal::Banned  { .. }       => "Too many failed attempts. Try again later.".to_string()
+        };
+        comms::disconnect(&mut socket, reason.into()).await;
+        return;
+    }
+
+    // Find the relevant session. Every pending session is compared, so that timing does not reveal anything.
+    let mut result      = None;
+    let mut not_loaded  = false;
     {
         for (session) in &mut sessions.lock().await {
             if let EditorSessionStep::Pending { .. } = session.session_step() {
-                if (&*handshake.session_code == session.session_code()) {
+                if (guard::codes_match(&handshake.session_code, session.session_code()) && ! session.is_closed()) {
+                    // The instance may still be loading, or may have been unloaded.
+                    not_loaded = true;
                     // Find the relevant instance.
-                    for (instance) in &instances.lock().await {
+                    for (instance) in &mut instances.lock().await {
                         if (instance.plot_id() == session.plot_id()) {
 
+                            not_loaded = false;
                             let (outgoing_commands_tx, outgoing_commands_rx) = mpsc::unbounded_channel();
                             let (incoming_events_tx, incoming_events_rx) = mpsc::unbounded_channel();
-                            session.activate(outgoing_commands_tx, incoming_events_rx);
+                            let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::Chat(instance.chat_history_packet())));
+                            let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::CommentThreads(instance.comments_packet())));
+                            let can_write = session.permission() == EditorPermission::Write;
+                            let _ = outgoing_commands_tx.send(OutgoingPeerCommand::Send(S2CPackets::SuggestionMode(SuggestionModeS2CPacket { suggesting : ! can_write, can_write })));
+                            session.activate(outgoing_commands_tx, incoming_events_rx, address);
+                            instance.audit(session.client_uuid(), session.client_name(), AuditEvent::SessionOpened);
                             result = Some((
                                 outgoing_commands_rx,
                                 incoming_events_tx,
@@ -76,30 +134,62 @@ async fn try_login_editor_websocket(
             }
         }
     };
+    // The code was right, so this is not a failed attempt. The session stays pending, so the client can retry.
+    if (not_loaded) {
+        comms::disconnect(&mut socket, "The plot is still loading. Try again in a moment.".into()).await;
+        return;
+    }
+    for login_guard in &mut guards.lock().await {
+        if (result.is_some()) { login_guard.record_success(address.ip()); }
+        else { login_guard.record_failure(address.ip()); }
+    }
     if let Some((outgoing_commands_rx, incoming_events_tx, initial_state_packet)) = result {
-        run_editor_websocket(cmds, &mut socket, outgoing_commands_rx, &incoming_events_tx, initial_state_packet).await;
+        permit.authenticate();
+        let reason = run_editor_websocket(cmds.clone(), &mut socket, permit.limits(), outgoing_commands_rx, &incoming_events_tx, initial_state_packet).await;
+        // Everything the client sent is queued before the close, so that it is still applied during shutdown.
         let _ = incoming_events_tx.send(IncomingPeerEvent::Close);
-        let _ = comms::send_packet(&mut socket, DisconnectS2CPacket { reason : "Connection interrupted".into() }).await;
+        if (reason.is_none() && cmds.is_exiting()) {
+            comms::disconnect_restarting(&mut socket).await;
+        } else {
+            comms::disconnect(&mut socket, reason.unwrap_or(Cow::Borrowed("Connection interrupted"))).await;
+        }
     } else {
-        let _ = comms::send_packet(&mut socket, DisconnectS2CPacket { reason : "Invalid session code. Has it expired?".into() }).await;
+        comms::disconnect(&mut socket, "Invalid session code. Has it expired?".into()).await;
     }
 }
 
+/// Returns the reason that the session was closed for, if it was closed by the server.
 async fn run_editor_websocket(
         cmds                 : Commands,
         socket               : &mut WebSocket,
+        limits               : ConnectionLimits,
     mut outgoing_commands_rx : mpsc::UnboundedReceiver<OutgoingPeerCommand>,
         incoming_events_tx   : &mpsc::UnboundedSender<IncomingPeerEvent>,
         initial_state_packet : InitialStateS2CPacket<'_>,
-) {
-    if let Err(_) = comms::send_packet(socket, initial_state_packet).await { return; };
-    if let Err(_) = comms::send_packet(socket, LoginSuccessS2CPacket).await { return; };
+) -> Option<Cow<'static, str>> {
+    if let Err(_) = comms::send_packet(socket, initial_state_packet).await { return None; };
+    if let Err(_) = comms::send_packet(socket, LoginSuccessS2CPacket).await { return None; };
 
+    let mut last_recieved  = Instant::now();
+    let mut last_active    = Instant::now();
+    let mut next_keepalive = Instant::now() + limits.keepalive_interval;
     'main_loop : while (! cmds.is_exiting()) {
 
+        if (Instant::now() >= next_keepalive) {
+            if let Err(_) = comms::send_packet(socket, KeepaliveS2CPacket).await { break 'main_loop; }
+            next_keepalive = Instant::now() + limits.keepalive_interval;
+        }
+
         match (comms::try_read_packet::<C2SPackets>(socket).await) {
-            Ok(Some(packet)) => { if let Err(_) = incoming_events_tx.send(IncomingPeerEvent::Recieve(packet)) { break 'main_loop; } },
-            Ok(None) => { }
+            Ok(Some(packet)) => {
+                last_recieved = Instant::now();
+                if (! matches!(packet, C2SPackets::Keepalive(_))) { last_active = last_recieved; }
+                if let Err(_) = incoming_events_tx.send(IncomingPeerEvent::Recieve(packet)) { break 'main_loop; }
+            },
+            Ok(None) => {
+                if (last_recieved.elapsed() >= limits.keepalive_timeout) { return Some(Cow::Borrowed("Timed out")); }
+                if (last_active.elapsed() >= limits.idle_timeout) { return Some(Cow::Borrowed("Disconnected for inactivity")); }
+            }
             Err(_) => { break 'main_loop; },
         }
 
@@ -112,7 +202,7 @@ async fn run_editor_websocket(
                         Err(_) => { break 'main_loop; },
                     } },
 
-                    OutgoingPeerCommand::Close => { break 'main_loop; }
+                    OutgoingPeerCommand::Close(reason) => { return Some(reason); }
 
                 } },
                 Err(mpsc::error::TryRecvError::Empty) => { break 'recv_outgoing; },
@@ -122,4 +212,5 @@ async fn run_editor_websocket(
 
         yield_now().await;
     }
+    None
 }
@@ -0,0 +1,218 @@
+use crate::instances::EditorInstance;
+use crate::lifecycle::{ self, InstanceLifecycle };
+use crate::store::{ self, EditorStore };
+use crate::instances::session::{ EditorSession, EditorPermission };
+use crate::peer::guard::{ MIN_SESSION_CODE_BITS, session_code_bits };
+use lighthousemc_database::{ LighthouseDB, DBPlotID, DBError };
+use axecs::prelude::*;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+
+/// Creates editor instances and sessions, checking against the existing ones that each plot has
+/// one instance and each player has one session per plot.
+///
+/// The host is still responsible for locking plots so that no other node manages them at the same time,
+/// unless instances are loaded by the [lifecycle manager](crate::lifecycle), which calls its hooks for that.
+#[derive(Clone)]
+pub struct EditorRegistry {
+    cmds     : Commands,
+    database : Arc<LighthouseDB>,
+    store    : Option<Arc<dyn EditorStore>>
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+    /// The plot does not exist.
+    NoSuchPlot,
+    /// Another session already uses the session code.
+    DuplicateCode,
+    /// A random session code could not be generated.
+    RandomCode,
+    /// The session code is too short or simple. See [`MIN_SESSION_CODE_BITS`].
+    WeakCode,
+    Database(DBError)
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f : &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self) {
+            Self::NoSuchPlot    => write!(f, "plot does not exist"),
+            Self::DuplicateCode => write!(f, "session code is already in use"),
+            Self::RandomCode    => write!(f, "failed to generate session code"),
+            Self::WeakCode      => write!(f, "session code is too easy to guess"),
+            Self::Database(err) => write!(f, "database error: {:?}", err)
+        }
+    }
+}
+
+
+impl EditorRegistry {
+
+    pub fn new(cmds : Commands, database : Arc<LighthouseDB>) -> Self {
+        Self { cmds, database, store : None }
+    }
+
+    /// Restores what was saved alongside the files of the instances that this opens, like their blame.
+    /// This should read from the same place as the store given to [`crate::EditorPlugin::with_store`].
+    pub fn with_store<S : EditorStore>(mut self, store : S) -> Self {
+        self.store = Some(Arc::new(store));
+        self
+    }
+
+
+    /// Opens the editor instance of a plot, if it is not open already. Returns `false` if it was.
+    pub async fn open_instance(&self, plot_id : DBPlotID) -> Result<bool, RegistryError> {
+        if (self.has_instance(plot_id).await) { return Ok(false); }
+        // SAFETY: The instance is only spawned below if no other instance manages the plot.
+        let Some(mut instance) = unsafe{ EditorInstance::create(plot_id, Arc::clone(&self.database)) }.await.map_err(RegistryError::Database)?
+            else { return Err(RegistryError::NoSuchPlot); };
+        if let Some(store) = &self.store {
+            store::restore_instance(&**store, &mut instance).await;
+        }
+        let (tx, rx) = oneshot::channel();
+        let mut tx       = Some(tx);
+        let mut instance = Some(instance);
+        self.cmds.run_system(async move |cmds : Commands, instances : Scoped<Entities<(&'static EditorInstance)>>| {
+            let exists = (&instances.lock().await).into_iter().any(|instance| instance.plot_id() == plot_id);
+            if (! exists) {
+                cmds.spawn(instance.take().unwrap()).await;
+            }
+            let _ = tx.take().unwrap().send(! exists);
+        }).await;
+        Ok(rx.await.unwrap_or(false))
+    }
+
+    pub async fn has_instance(&self, plot_id : DBPlotID) -> bool {
+        let (tx, rx) = oneshot::channel();
+        let mut tx = Some(tx);
+        self.cmds.run_system(async move |instances : Scoped<Entities<(&'static EditorInstance)>>| {
+            let _ = tx.take().unwrap().send((&instances.lock().await).into_iter().any(|instance| instance.plot_id() == plot_id));
+        }).await;
+        rx.await.unwrap_or(false)
+    }
+
+
+    /// Whether instances are loaded by the [lifecycle manager](crate::lifecycle) when sessions are opened on them.
+    pub async fn is_lifecycle_managed(&self) -> bool {
+        let (tx, rx) = oneshot::channel();
+        let mut tx = Some(tx);
+        self.cmds.run_system(async move |lifecycles : Scoped<Entities<(&'static InstanceLifecycle)>>| {
+            let _ = tx.take().unwrap().send(lifecycle::is_managed(&lifecycles.lock().await));
+        }).await;
+        rx.await.unwrap_or(false)
+    }
+
+
+    /// Opens a session for a player on a plot, opening the plot's instance if needed. Returns the session code.
+    /// If the lifecycle manager is used, the instance is loaded by it instead, shortly after.
+    ///
+    /// If the player already has a session on the plot that they have not logged in to yet, it is reused with the new expiry and permission,
+    /// keeping its code. If they are already connected, that session is closed and replaced.
+    pub async fn open_session<const SESSION_CODE_LEN : usize>(
+        &self,
+        plot_id     : DBPlotID,
+        client_uuid : Uuid,
+        client_name : String,
+        expires_in  : Duration,
+        permission  : EditorPermission
+    ) -> Result<String, RegistryError> {
+        if ((SESSION_CODE_LEN as u32) * 6 < MIN_SESSION_CODE_BITS) { return Err(RegistryError::WeakCode); }
+        let session_code = EditorSession::random_code::<SESSION_CODE_LEN>().map_err(|_| RegistryError::RandomCode)?;
+        self.open_session_inner(plot_id, client_uuid, client_name, expires_in, permission, session_code, false).await
+    }
+
+    /// Like [`EditorRegistry::open_session`], but with a chosen session code, which also replaces the code of a reused session.
+    /// The code must have at least [`MIN_SESSION_CODE_BITS`] of estimated entropy.
+    pub async fn open_session_with(
+        &self,
+        plot_id      : DBPlotID,
+        client_uuid  : Uuid,
+        client_name  : String,
+        expires_in   : Duration,
+        permission   : EditorPermission,
+        session_code : String
+    ) -> Result<String, RegistryError> {
+        if (session_code_bits(&session_code) < MIN_SESSION_CODE_BITS) { return Err(RegistryError::WeakCode); }
+        self.open_session_inner(plot_id, client_uuid, client_name, expires_in, permission, session_code, true).await
+    }
+
+    /// Replaces the session code of a player's session on a plot with a new random one, eg. if the old one was leaked. Returns the new code.
+    ///
+    /// Returns `Ok(None)` if they have no session there that nobody has logged in to yet, as the client is already using the old code.
+    pub async fn regenerate_code<const SESSION_CODE_LEN : usize>(&self, plot_id : DBPlotID, client_uuid : Uuid) -> Result<Option<String>, RegistryError> {
+        if ((SESSION_CODE_LEN as u32) * 6 < MIN_SESSION_CODE_BITS) { return Err(RegistryError::WeakCode); }
+        let session_code = EditorSession::random_code::<SESSION_CODE_LEN>().map_err(|_| RegistryError::RandomCode)?;
+        let (tx, rx) = oneshot::channel();
+        let mut tx           = Some(tx);
+        let mut session_code = Some(session_code);
+        self.cmds.run_system(async move |sessions : Scoped<Entities<(&'static mut EditorSession)>>| {
+            let session_code = session_code.take().unwrap();
+            let mut sessions = sessions.lock().await;
+
+            // Session codes are checked against closed sessions too, as they may not have been removed yet.
+            if ((&sessions).into_iter().any(|session| session.session_code() == session_code)) {
+                let _ = tx.take().unwrap().send(Err(RegistryError::DuplicateCode));
+                return;
+            }
+
+            for session in &mut sessions {
+                if (session.plot_id() == plot_id && session.client_uuid() == client_uuid && ! session.is_closed()) {
+                    let replaced = session.replace_code(session_code.clone());
+                    let _ = tx.take().unwrap().send(Ok(replaced.then_some(session_code)));
+                    return;
+                }
+            }
+            let _ = tx.take().unwrap().send(Ok(None));
+        }).await;
+        rx.await.unwrap_or(Ok(None))
+    }
+
+    async fn open_session_inner(
+        &self,
+        plot_id      : DBPlotID,
+        client_uuid  : Uuid,
+        client_name  : String,
+        expires_in   : Duration,
+        permission   : EditorPermission,
+        session_code : String,
+        replace_code : bool
+    ) -> Result<String, RegistryError> {
+        if (! self.is_lifecycle_managed().await) {
+            self.open_instance(plot_id).await?;
+        }
+        let (tx, rx) = oneshot::channel();
+        let mut tx   = Some(tx);
+        let mut args = Some((client_name, session_code));
+        self.cmds.run_system(async move |cmds : Commands, sessions : Scoped<Entities<(&'static mut EditorSession)>>| {
+            let (client_name, session_code) = args.take().unwrap();
+            let mut sessions = sessions.lock().await;
+
+            if ((&sessions).into_iter().any(|session| session.session_code() == session_code && ! (session.plot_id() == plot_id && session.client_uuid() == client_uuid && ! session.is_closed()))) {
+                let _ = tx.take().unwrap().send(Err(RegistryError::DuplicateCode));
+                return;
+            }
+
+            for session in &mut sessions {
+                if (session.plot_id() == plot_id && session.client_uuid() == client_uuid && ! session.is_closed()) {
+                    if (session.reuse_pending(expires_in, permission, replace_code.then(|| session_code.clone()))) {
+                        let _ = tx.take().unwrap().send(Ok(session.session_code().to_string()));
+                        return;
+                    }
+                    session.kick("Opened the editor again somewhere else");
+                }
+            }
+
+            // SAFETY: Any other session of the player on the plot was closed above, the code was checked to be unique,
+            //         and the plot's instance was opened.
+            let session = unsafe{ EditorSession::create_with(plot_id, client_uuid, client_name, expires_in, session_code.clone()) }.with_permission(permission);
+            cmds.spawn(session).await;
+            let _ = tx.take().unwrap().send(Ok(session_code));
+        }).await;
+        rx.await.unwrap_or(Err(RegistryError::NoSuchPlot))
+    }
+
+}
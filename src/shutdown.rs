@@ -0,0 +1,55 @@
+use crate::instances::{ self, session::{ self, EditorSession, EditorSessionStep } };
+use crate::audit;
+use crate::lifecycle;
+use crate::store::{ self, EditorStore };
+use voxidian_logger::{ info, pass, warn };
+use axecs::prelude::*;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::{ timeout, sleep };
+
+
+/// Runs once the editor server has stopped accepting connections.
+/// Waits for every client to be told that the server is restarting, applies the last edits that they sent, and saves every instance.
+/// Plots are then released with [`InstanceHooks::after_unload`](crate::lifecycle::InstanceHooks::after_unload), if the lifecycle manager is used.
+pub(crate) async fn shutdown(cmds : Commands, store : Option<Arc<dyn EditorStore>>, shutdown_timeout : Duration) {
+    info!("Shutting down editor server...");
+    let flushed = timeout(shutdown_timeout, async {
+        wait_for_peers(cmds.clone()).await;
+        cmds.run_system(session::update_state).await;
+        cmds.run_system(instances::read_instance_events).await;
+        cmds.run_system(audit::update_audit_log).await;
+        cmds.run_system(audit::flush_audit_log).await;
+        let saved = store::save_instances(cmds.clone(), store, |_| true).await;
+        lifecycle::release_all(cmds.clone()).await;
+        saved
+    }).await;
+    match (flushed) {
+        Ok(true)  => { pass!("Shut down editor server."); },
+        Ok(false) => { warn!("Shut down editor server, but some changed files were not saved."); },
+        Err(_)    => { warn!("Shutting down the editor server took longer than {}s. Some edits may not have been saved.", shutdown_timeout.as_secs()); }
+    }
+}
+
+
+/// Keeps applying what clients send until every one of them has disconnected.
+/// Clients are disconnected by their websocket tasks once they see that the app is exiting.
+async fn wait_for_peers(cmds : Commands) {
+    loop {
+        cmds.run_system(session::read_session_events).await;
+        // Patches wait in the file shadows of their session until they are handed to the instance.
+        cmds.run_system(session::update_state).await;
+        cmds.run_system(instances::read_instance_events).await;
+        let (tx, rx) = oneshot::channel();
+        let mut tx = Some(tx);
+        cmds.run_system(async move |sessions : Scoped<Entities<(&'static EditorSession)>>| {
+            let connected = (&sessions.lock().await).into_iter().any(|session| ! session.is_closed() && matches!(session.session_step(), EditorSessionStep::Active { .. }));
+            let _ = tx.take().unwrap().send(connected);
+        }).await;
+        if (! rx.await.unwrap_or(false)) { break; }
+        sleep(Duration::from_millis(10)).await;
+    }
+    // Once more, so that sessions closed in the last pass are recorded as having left.
+    cmds.run_system(session::read_session_events).await;
+}
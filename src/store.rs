@@ -0,0 +1,151 @@
+use crate::instances::{ EditorInstance, FileBlame, CommentThreads };
+use lighthousemc_editor_common::packet::s2c::FileContents;
+use lighthousemc_database::{ DBPlotID, DBFSFileID };
+use voxidian_logger::{ debug, warn, error };
+use axecs::prelude::*;
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+
+/// Writes files and comment threads that were changed in the editor back to where the host keeps them. See [`crate::EditorPlugin::with_store`].
+///
+/// Without one, edits only live in memory, and the host has to save them itself, eg. on [`EditorHostEvent::ActionRequested`](crate::instances::EditorHostEvent::ActionRequested).
+pub trait EditorStore : Send + Sync + 'static {
+
+    /// Writes the contents of files on a plot. If this fails, the files are saved again next time.
+    fn save_files(&self, plot_id : DBPlotID, files : Vec<UnsavedFile>) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send + '_>>;
+
+    /// Reads the [`UnsavedFile::blame`] that was last saved for each file on a plot, when its instance is loaded.
+    /// Files without any keep the blame of a freshly loaded file.
+    fn load_blame(&self, plot_id : DBPlotID) -> Pin<Box<dyn Future<Output = Result<BTreeMap<DBFSFileID, Vec<u8>>, String>> + Send + '_>> {
+        let _ = plot_id;
+        Box::pin(async { Ok(BTreeMap::new()) })
+    }
+
+    /// Writes the comment threads of a plot, encoded with [`CommentThreads::to_bytes`]. If this fails, they are saved again next time.
+    fn save_comments(&self, plot_id : DBPlotID, comments : Vec<u8>) -> Pin<Box<dyn Future<Output = Result<(), String>> + Send + '_>> {
+        let _ = (plot_id, comments);
+        Box::pin(async { Ok(()) })
+    }
+
+    /// Reads the comment threads that were last saved for a plot, when its instance is loaded.
+    fn load_comments(&self, plot_id : DBPlotID) -> Pin<Box<dyn Future<Output = Result<Option<Vec<u8>>, String>> + Send + '_>> {
+        let _ = plot_id;
+        Box::pin(async { Ok(None) })
+    }
+
+}
+
+#[derive(Clone, Debug)]
+pub struct UnsavedFile {
+    pub file_id : DBFSFileID,
+    /// The full path of the file, with directories separated by `/`.
+    pub path    : String,
+    pub data    : Vec<u8>,
+    /// Who last changed each line, encoded with [`FileBlame::to_bytes`]. `None` if the file is binary.
+    pub blame   : Option<Vec<u8>>
+}
+
+
+/// Restores what was saved alongside the files of an instance that was just loaded.
+pub(crate) async fn restore_instance(store : &dyn EditorStore, instance : &mut EditorInstance) {
+    let plot_id = instance.plot_id();
+    match (store.load_blame(plot_id).await) {
+        Ok(blames) => {
+            for (file_id, data) in blames {
+                // The file may have been changed by something other than the editor since.
+                let blame = FileBlame::from_bytes(&data).filter(|blame| instance.state().files().get(&file_id).is_some_and(|file| match (file.contents()) {
+                    FileContents::Text(text)    => blame.fits(text),
+                    FileContents::Binary { .. } => false
+                }));
+                match (blame) {
+                    Some(blame) => { instance.set_file_blame(file_id, blame); },
+                    None        => { debug!("Ignored stored blame of file {} on plot {}, as it does not match the file.", file_id, plot_id); }
+                }
+            }
+        },
+        Err(err) => { warn!("Failed to load the blame of plot {}: {}", plot_id, err); }
+    }
+    match (store.load_comments(plot_id).await) {
+        Ok(Some(data)) => match (CommentThreads::from_bytes(&data)) {
+            Some(comments) => { instance.set_comments(comments); },
+            None           => { warn!("Ignored invalid stored comment threads of plot {}.", plot_id); }
+        },
+        Ok(None) => { },
+        Err(err) => { warn!("Failed to load the comment threads of plot {}: {}", plot_id, err); }
+    }
+}
+
+
+/// Saves the changed files and comment threads of every instance that `filter` accepts. Returns `false` if any could not be saved.
+pub(crate) async fn save_instances<F : Fn(DBPlotID) -> bool + Send + Sync + 'static>(cmds : Commands, store : Option<Arc<dyn EditorStore>>, filter : F) -> bool {
+    let Some(store) = store else {
+        let (tx, rx) = oneshot::channel();
+        let mut tx = Some(tx);
+        cmds.run_system(async move |instances : Scoped<Entities<(&'static EditorInstance)>>| {
+            let unsaved = (&instances.lock().await).into_iter()
+                .filter(|instance| filter(instance.plot_id()) && instance.has_unsaved_changes())
+                .map(|instance| instance.plot_id())
+                .collect::<Vec<_>>();
+            let _ = tx.take().unwrap().send(unsaved);
+        }).await;
+        let unsaved = rx.await.unwrap_or_default();
+        for plot_id in &unsaved {
+            warn!("No editor store is set, so changes on plot {} were not saved.", plot_id);
+        }
+        return unsaved.is_empty();
+    };
+
+    let (tx, rx) = oneshot::channel();
+    let mut tx = Some(tx);
+    cmds.run_system(async move |instances : Scoped<Entities<(&'static mut EditorInstance)>>| {
+        let unsaved = (&mut instances.lock().await).into_iter()
+            .filter(|instance| filter(instance.plot_id()))
+            .map(|instance| (instance.plot_id(), instance.state.take_unsaved_files(), instance.take_unsaved_comments()))
+            .filter(|(_, files, comments)| ! files.is_empty() || comments.is_some())
+            .collect::<Vec<_>>();
+        let _ = tx.take().unwrap().send(unsaved);
+    }).await;
+    let unsaved = rx.await.unwrap_or_default();
+
+    let mut failed = Vec::new();
+    for (plot_id, files, comments) in unsaved {
+        let mut failed_files = Vec::new();
+        if (! files.is_empty()) {
+            let file_ids = files.iter().map(|file| file.file_id).collect::<Vec<_>>();
+            match (store.save_files(plot_id, files).await) {
+                Ok(()) => { debug!("Saved {} editor files on plot {}.", file_ids.len(), plot_id); },
+                Err(err) => {
+                    error!("Failed to save editor files on plot {}: {}", plot_id, err);
+                    failed_files = file_ids;
+                }
+            }
+        }
+        let mut failed_comments = false;
+        if let Some(comments) = comments {
+            if let Err(err) = store.save_comments(plot_id, comments).await {
+                error!("Failed to save comment threads on plot {}: {}", plot_id, err);
+                failed_comments = true;
+            }
+        }
+        if (! failed_files.is_empty() || failed_comments) {
+            failed.push((plot_id, failed_files, failed_comments));
+        }
+    }
+    if (failed.is_empty()) { return true; }
+
+    // Keep them marked as changed, so that they are saved again later.
+    let mut failed = Some(failed);
+    cmds.run_system(async move |instances : Scoped<Entities<(&'static mut EditorInstance)>>| {
+        let failed = failed.take().unwrap();
+        for instance in &mut instances.lock().await {
+            if let Some((_, file_ids, failed_comments)) = failed.iter().find(|(plot_id, _, _)| *plot_id == instance.plot_id()) {
+                instance.state.mark_unsaved(file_ids.iter().copied());
+                if (*failed_comments) { instance.mark_comments_unsaved(); }
+            }
+        }
+    }).await;
+    false
+}
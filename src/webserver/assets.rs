@@ -0,0 +1,74 @@
+use axum::http::{ StatusCode, HeaderValue, HeaderMap };
+use axum::http::header::{ CONTENT_TYPE, CONTENT_ENCODING, CACHE_CONTROL, ETAG, VARY, ACCEPT_ENCODING, IF_NONE_MATCH };
+use axum::response::{ IntoResponse, Response };
+
+
+/// A build output whose file name contains a hash of its contents, so it never changes under the same URL.
+pub(super) struct HashedAsset {
+    pub(super) file_name : &'static str,
+    pub(super) hash      : &'static str,
+    pub(super) mime      : &'static str,
+    pub(super) raw       : &'static [u8],
+    pub(super) brotli    : &'static [u8],
+    pub(super) gzip      : &'static [u8]
+}
+
+macro hashed_asset( $env:literal, $mime:expr ) {
+    HashedAsset {
+        file_name : env!($env),
+        hash      : env!(concat!($env, "_HASH")),
+        mime      : $mime,
+        raw       : include_bytes!(concat!(env!("OUT_DIR"), "/frontend/", env!($env))),
+        brotli    : include_bytes!(concat!(env!("OUT_DIR"), "/frontend/", env!($env), ".br")),
+        gzip      : include_bytes!(concat!(env!("OUT_DIR"), "/frontend/", env!($env), ".gz"))
+    }
+}
+
+pub(super) static FRONTEND_JS   : HashedAsset = hashed_asset!("LIGHTHOUSEMC_EDITOR_FRONTEND_JS", super::mime::JS);
+pub(super) static FRONTEND_WASM : HashedAsset = hashed_asset!("LIGHTHOUSEMC_EDITOR_FRONTEND_WASM", super::mime::WASM);
+
+
+pub(super) fn route_hashed_asset(asset : &'static HashedAsset, headers : &HeaderMap) -> Response {
+    let etag = format!("\"{}\"", asset.hash);
+    let not_modified = headers.get_all(IF_NONE_MATCH).iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| { let tag = tag.trim(); tag == "*" || tag.trim_start_matches("W/") == etag });
+
+    let mut response = if (not_modified) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        let (encoding, data) = match (preferred_encoding(headers)) {
+            Some("br")   => (Some("br"), asset.brotli),
+            Some("gzip") => (Some("gzip"), asset.gzip),
+            _            => (None, asset.raw)
+        };
+        let mut response = data.into_response();
+        response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static(asset.mime));
+        if let Some(encoding) = encoding {
+            response.headers_mut().insert(CONTENT_ENCODING, HeaderValue::from_static(encoding));
+        }
+        response
+    };
+    response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static("public, max-age=31536000, immutable"));
+    response.headers_mut().insert(VARY, HeaderValue::from_static("Accept-Encoding"));
+    if let Ok(etag) = HeaderValue::from_str(&etag) {
+        response.headers_mut().insert(ETAG, etag);
+    }
+    response
+}
+
+
+/// The best precompressed encoding that the client accepts: brotli, then gzip.
+fn preferred_encoding(headers : &HeaderMap) -> Option<&'static str> {
+    let mut accepted = Vec::new();
+    for value in headers.get_all(ACCEPT_ENCODING).iter().filter_map(|value| value.to_str().ok()) {
+        for entry in value.split(',') {
+            let mut parts    = entry.split(';');
+            let     encoding = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+            let     quality  = parts.find_map(|param| param.trim().strip_prefix("q=").and_then(|q| q.parse::<f32>().ok())).unwrap_or(1.0);
+            if (quality > 0.0) { accepted.push(encoding); }
+        }
+    }
+    ["br", "gzip"].into_iter().find(|encoding| accepted.iter().any(|accepted| accepted == encoding || accepted == "*"))
+}
@@ -0,0 +1,357 @@
+use crate::util::str_replace_multiple;
+use crate::peer::limits::{ ConnectionLimits, ConnectionTracker };
+use crate::peer::guard::{ self, session_code_bits, MIN_SESSION_CODE_BITS };
+use crate::peer::files::{ self, FileAccessError };
+use crate::metrics::MetricsEndpoint;
+use voxidian_logger::{ pass, info, warn, error };
+use axecs::prelude::*;
+use std::io;
+use std::sync::Arc;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::future::IntoFuture;
+use tokio::net::TcpListener;
+use tokio::task::JoinSet;
+use axum::{ Router, routing, middleware };
+use axum::http::{ StatusCode, HeaderValue, HeaderMap, Uri };
+use axum::http::header::{ CONTENT_TYPE, CONTENT_DISPOSITION, CACHE_CONTROL, COOKIE };
+use axum::response::{ IntoResponse, Html, Response };
+use axum::extract::{ State, Path, RawQuery, ConnectInfo, DefaultBodyLimit };
+use axum::extract::ws::WebSocketUpgrade;
+use axum::body::Bytes;
+
+
+mod vendor;
+
+mod assets;
+
+mod admin;
+
+pub mod tls;
+
+pub mod proxy;
+
+pub mod security;
+
+
+mod mime {
+    pub const TEXT         : &'static str = "text/plain";
+    pub const PNG          : &'static str = "image/png";
+    pub const JS           : &'static str = "application/javascript";
+    pub const WASM         : &'static str = "application/wasm";
+    pub const CSS          : &'static str = "text/css";
+    pub const JSON         : &'static str = "application/json";
+    pub const SVG          : &'static str = "image/svg+xml";
+    pub const TTF          : &'static str = "font/ttf";
+    pub const WOFF         : &'static str = "font/woff";
+    pub const WOFF2        : &'static str = "font/woff2";
+    pub const OCTET_STREAM : &'static str = "application/octet-stream";
+}
+
+
+/// What to do when some of the bind addresses can not be listened on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BindPolicy {
+    /// Start as long as at least one address could be listened on.
+    #[default]
+    RequireAny,
+    /// Fail to start unless every address could be listened on.
+    RequireAll
+}
+
+
+pub async fn run(
+    cmds                 : Commands,
+    bind_addrs           : &[SocketAddr],
+    bind_policy          : BindPolicy,
+    display_game_address : &str,
+    asset_dir            : PathBuf,
+    connection_limits    : ConnectionLimits,
+    tls                  : Option<tls::TlsConfig>,
+    proxy                : proxy::ProxyConfig,
+    security             : security::SecurityConfig,
+    metrics              : MetricsEndpoint,
+    admin_secret         : Option<String>
+) -> Result<(), io::Error> {
+    let base_path = proxy.base_path().to_string();
+    // Pages served from the public URL are always allowed to connect.
+    let security = match (proxy.public_url().and_then(|public_url| public_url.parse::<Uri>().ok())) {
+        Some(public_url) => match ((public_url.scheme_str(), public_url.authority())) {
+            (Some(scheme), Some(authority)) => security.with_allowed_origin(format!("{}://{}", scheme, authority)),
+            _                               => security
+        },
+        None => security
+    };
+    let app = Router::new();
+
+    // Third-party assets
+    vendor::check_asset_dir(&asset_dir).await;
+    let asset_dir = Arc::new(asset_dir);
+    let app = app.route("/editor/vendor/{*path}", routing::get(move |Path(path) : Path<String>| vendor::route_vendor_asset(Arc::clone(&asset_dir), path)));
+
+    // Static assets
+    let app = app.route("/robots.txt",                                  routing::get(async || route_asset(mime::TEXT, include_str!   ("../assets/misc/robots.txt"                                                   ).into_response())));
+    let app = app.route("/assets/image/logo_transparent.png",           routing::get(async || route_asset(mime::PNG,  include_bytes! ("../assets/image/logo_transparent.png"                                        ).into_response())));
+
+    // Frontend, named by content hash
+    let app = [&assets::FRONTEND_JS, &assets::FRONTEND_WASM].into_iter().fold(app, |app, asset| {
+        app.route(&format!("/editor/{}", asset.file_name), routing::get(move |headers : HeaderMap| async move { assets::route_hashed_asset(asset, &headers) }))
+    });
+
+    // Root
+    let root_html = include_str!("../assets/template/root.html").replace("{{DISPLAY_GAME_ADDRESS}}", display_game_address).replace("{{BASE_PATH}}", &base_path);
+    let app = app.route("/", routing::get(Html(root_html.clone())));
+
+    // Editor
+    const EDITOR : &'static str = str_replace_multiple!( include_str!("../assets/template/editor.html"), [
+        ("{{LIGHTHOUSEMC_EDITOR_VERSION}}",       env!("CARGO_PKG_VERSION"                 )),
+        ("{{LIGHTHOUSEMC_EDITOR_COMMIT}}",        env!("LIGHTHOUSEMC_EDITOR_COMMIT"        )),
+        ("{{LIGHTHOUSEMC_EDITOR_COMMIT_HASH}}",   env!("LIGHTHOUSEMC_EDITOR_COMMIT_HASH"   )),
+        ("{{LIGHTHOUSEMC_EDITOR_FRONTEND_JS}}",   env!("LIGHTHOUSEMC_EDITOR_FRONTEND_JS"   )),
+        ("{{LIGHTHOUSEMC_EDITOR_FRONTEND_WASM}}", env!("LIGHTHOUSEMC_EDITOR_FRONTEND_WASM" ))
+    ] );
+    // The page is revalidated every time, as it links to the current frontend assets.
+    let editor_html = EDITOR.replace("{{BASE_PATH}}", &base_path);
+    let security_headers = Arc::new(security::SecurityHeaders::new(&security, &[&root_html, &editor_html]));
+    let editor = ([(CACHE_CONTROL, "no-cache")], Html(editor_html));
+    let app = app.route("/editor", routing::get(editor.clone()));
+    let app = app.route("/editor/", routing::get(editor));
+
+    // Editor Websocket
+    let connections = Arc::new(ConnectionTracker::new(connection_limits));
+    let security    = Arc::new(security);
+    let app = app.route("/editor/ws", routing::any(move |upgrade : WebSocketUpgrade, connect_info : ConnectInfo<SocketAddr>, headers : HeaderMap, uri : Uri, cmds : State<Commands>| {
+        handle_editor_websocket(upgrade, connect_info, headers, uri, cmds, Arc::clone(&connections), Arc::clone(&security))
+    }));
+
+    // Editor Files
+    let file_security = Arc::clone(&security);
+    let app = app.route("/editor/file/{file_id}", routing::get(route_file_download)
+        .put(move |path : Path<u64>, connect_info : ConnectInfo<SocketAddr>, headers : HeaderMap, uri : Uri, cmds : State<Commands>, body : Bytes| {
+            route_file_upload(path, connect_info, headers, uri, cmds, Arc::clone(&file_security), body)
+        })
+        .layer(DefaultBodyLimit::max(connection_limits.max_upload_size))
+    );
+
+    // Admin
+    let app = match (admin_secret) {
+        Some(admin_secret) => {
+            if (session_code_bits(&admin_secret) < MIN_SESSION_CODE_BITS) {
+                warn!("The editor admin secret is weak. Use at least {} bits of randomness.", MIN_SESSION_CODE_BITS);
+            }
+            let admin_secret   : Arc<str> = Arc::from(admin_secret);
+            let admin_base     : Arc<str> = Arc::from(base_path.as_str());
+            let (secret_a, secret_b, secret_c) = (Arc::clone(&admin_secret), Arc::clone(&admin_secret), admin_secret);
+            app.route("/admin",               routing::get(move |ConnectInfo(address) : ConnectInfo<SocketAddr>, headers : HeaderMap, cmds : State<Commands>| admin::route_dashboard(Arc::clone(&secret_a), address.ip(), headers, cmds.0, Arc::clone(&admin_base))))
+               .route("/admin/api/instances", routing::get(move |ConnectInfo(address) : ConnectInfo<SocketAddr>, headers : HeaderMap, cmds : State<Commands>| admin::route_instances(Arc::clone(&secret_b), address.ip(), headers, cmds.0)))
+               .route("/admin/api/sessions",  routing::get(move |ConnectInfo(address) : ConnectInfo<SocketAddr>, headers : HeaderMap, cmds : State<Commands>| admin::route_sessions(Arc::clone(&secret_c), address.ip(), headers, cmds.0)))
+        },
+        None => app
+    };
+
+    // Metrics
+    let app = if let MetricsEndpoint::Editor = metrics { app.route("/metrics", routing::get(route_metrics)) } else { app };
+
+    // Fallback
+    let not_found = (StatusCode::NOT_FOUND, Html(include_str!("../assets/template/404.html").replace("{{BASE_PATH}}", &base_path)));
+    let app = app.fallback(not_found.clone());
+
+    // Path prefix
+    let app = if (base_path.is_empty()) { app } else { Router::new().nest(&base_path, app).fallback(not_found) };
+    let app = app.layer(middleware::from_fn_with_state(security_headers, security::apply_headers));
+    let app = app.layer(middleware::from_fn_with_state(Arc::new(proxy), proxy::apply_forwarded));
+
+    // state
+    let app = app.with_state(cmds.clone());
+
+    // Run
+    let listeners = bind_all(bind_addrs, bind_policy).await?;
+    let mut servers = JoinSet::new();
+    if let MetricsEndpoint::Separate(metrics_addr) = metrics {
+        let metrics_listener = TcpListener::bind(metrics_addr).await?;
+        info!("Editor metrics listening on {}.", metrics_addr);
+        let metrics_app = Router::new().route("/metrics", routing::get(route_metrics)).with_state(cmds.clone());
+        servers.spawn(axum::serve(metrics_listener, metrics_app).into_future());
+    }
+    match (tls) {
+        Some(tls) => {
+            let tls = Arc::new(tls::TlsState::load(tls).await?);
+            tokio::spawn(Arc::clone(&tls).watch(cmds));
+            if let Some(redirect_from) = tls.redirect_from() {
+                let redirect_listener = TcpListener::bind(redirect_from).await?;
+                let https_addrs       = listeners.iter().map(|listener| listener.local_addr()).collect::<io::Result<Vec<_>>>()?;
+                servers.spawn(async move {
+                    if let Err(err) = tls::serve_redirect(redirect_listener, https_addrs).await {
+                        error!("Editor HTTP redirect server failed: {}", err);
+                    }
+                    Ok(())
+                });
+            }
+            for listener in listeners {
+                servers.spawn(tls::serve(listener, app.clone(), Arc::clone(&tls)));
+            }
+            pass!("Started editor server with TLS.");
+        },
+        None => {
+            for listener in listeners {
+                servers.spawn(axum::serve(listener, app.clone().into_make_service_with_connect_info::<SocketAddr>()).into_future());
+            }
+            pass!("Started editor server.");
+        }
+    }
+    // Listeners only stop on errors, or when this future is dropped as the app exits, which aborts them.
+    while let Some(result) = servers.join_next().await {
+        match (result) {
+            Ok(Ok(())) => { },
+            Ok(Err(err)) => { return Err(err); },
+            Err(err)     => { return Err(io::Error::other(err)); }
+        }
+    }
+    Ok(())
+}
+
+
+/// Listens on each address separately, reporting the ones that failed.
+async fn bind_all(bind_addrs : &[SocketAddr], bind_policy : BindPolicy) -> Result<Vec<TcpListener>, io::Error> {
+    let mut listeners  = Vec::with_capacity(bind_addrs.len());
+    let mut last_error = None;
+    for &bind_addr in bind_addrs {
+        match (TcpListener::bind(bind_addr).await) {
+            Ok(listener) => {
+                info!("Editor server listening on {}.", bind_addr);
+                listeners.push(listener);
+            },
+            Err(err) => {
+                error!("Failed to listen on {}: {}", bind_addr, err);
+                if (bind_policy == BindPolicy::RequireAll) { return Err(err); }
+                last_error = Some(err);
+            }
+        }
+    }
+    if (listeners.is_empty()) {
+        return Err(last_error.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no bind addresses")));
+    }
+    Ok(listeners)
+}
+
+
+fn route_asset(content_type : &'static str, data : impl IntoResponse) -> impl IntoResponse {
+    let mut response = data.into_response();
+    response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    response
+}
+
+
+async fn route_metrics(cmds : State<Commands>) -> impl IntoResponse {
+    route_asset("text/plain; version=0.0.4", crate::metrics::render(cmds.0).await)
+}
+
+
+async fn handle_editor_websocket(
+    upgrade              : WebSocketUpgrade,
+    ConnectInfo(address) : ConnectInfo<SocketAddr>,
+    headers              : HeaderMap,
+    uri                  : Uri,
+    cmds                 : State<Commands>,
+    connections          : Arc<ConnectionTracker>,
+    security             : Arc<security::SecurityConfig>
+) -> Response {
+    // Other sites must not be able to connect from a visitor's browser.
+    if (! security.origin_allowed(&headers, &uri)) {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    // Refused connections are still upgraded, so that the client can be told why.
+    let permit = connections.try_open(address.ip());
+    upgrade.protocols(["lighthousemc-editor"])
+        .on_upgrade(async move |socket| crate::peer::handle_editor_websocket(cmds.0, socket, address, permit).await)
+        .into_response()
+}
+
+
+async fn route_file_download(
+    Path(file_id)        : Path<u64>,
+    RawQuery(query)      : RawQuery,
+    ConnectInfo(address) : ConnectInfo<SocketAddr>,
+    headers              : HeaderMap,
+    cmds                 : State<Commands>
+) -> Response {
+    let Some(session_code) = session_code_from_cookies(&headers) else { return StatusCode::UNAUTHORIZED.into_response(); };
+    if (guard::check_address(cmds.0.clone(), address.ip()).await.is_err()) { return StatusCode::TOO_MANY_REQUESTS.into_response(); }
+    let download = match (files::read_file(cmds.0.clone(), session_code, file_id).await) {
+        Ok(download)                    => download,
+        Err(FileAccessError::NoSession) => {
+            guard::record_failure(cmds.0, address.ip()).await;
+            return StatusCode::UNAUTHORIZED.into_response();
+        },
+        Err(FileAccessError::ReadOnly)  => { return StatusCode::FORBIDDEN.into_response(); },
+        Err(FileAccessError::NoFile)    => { return StatusCode::NOT_FOUND.into_response(); }
+    };
+    let mut response = download.data.into_response();
+    if let Ok(mime) = HeaderValue::from_str(&download.mime) {
+        response.headers_mut().insert(CONTENT_TYPE, mime);
+    }
+    if (query.is_some_and(|query| query.split('&').any(|param| param == "download"))) {
+        let disposition = HeaderValue::from_str(&format!("attachment; filename=\"{}\"", download.fsname.replace(['"', '\\'], "_")))
+            .unwrap_or(HeaderValue::from_static("attachment"));
+        response.headers_mut().insert(CONTENT_DISPOSITION, disposition);
+    }
+    response
+}
+
+
+async fn route_file_upload(
+    Path(file_id)        : Path<u64>,
+    ConnectInfo(address) : ConnectInfo<SocketAddr>,
+    headers              : HeaderMap,
+    uri                  : Uri,
+    cmds                 : State<Commands>,
+    security             : Arc<security::SecurityConfig>,
+    body                 : Bytes
+) -> StatusCode {
+    // Other sites must not be able to upload from a visitor's browser, which sends the session cookie along.
+    if (! security.editor_request_allowed(&headers, &uri)) {
+        return StatusCode::FORBIDDEN;
+    }
+    let Some(session_code) = session_code_from_cookies(&headers) else { return StatusCode::UNAUTHORIZED; };
+    if (guard::check_address(cmds.0.clone(), address.ip()).await.is_err()) { return StatusCode::TOO_MANY_REQUESTS; }
+    match (files::write_file(cmds.0.clone(), session_code, file_id, body.to_vec()).await) {
+        Ok(())                          => StatusCode::NO_CONTENT,
+        Err(FileAccessError::NoSession) => {
+            guard::record_failure(cmds.0, address.ip()).await;
+            StatusCode::UNAUTHORIZED
+        },
+        Err(FileAccessError::ReadOnly)  => StatusCode::FORBIDDEN,
+        Err(FileAccessError::NoFile)    => StatusCode::NOT_FOUND
+    }
+}
+
+
+fn session_code_from_cookies(headers : &HeaderMap) -> Option<String> {
+    for value in headers.get_all(COOKIE) {
+        let Ok(value) = value.to_str() else { continue; };
+        for cookie in value.split(';') {
+            if let Some(("lighthousemc-editor-session", session_code)) = cookie.trim().split_once('=') {
+                return Some(percent_decode(session_code));
+            }
+        }
+    }
+    None
+}
+
+fn percent_decode(s : &str) -> String {
+    let     bytes = s.as_bytes();
+    let mut out   = Vec::with_capacity(bytes.len());
+    let mut i     = 0;
+    while (i < bytes.len()) {
+        if (bytes[i] == b'%') {
+            if let Some(byte) = s.get((i + 1)..(i + 3)).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
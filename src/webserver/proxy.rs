@@ -0,0 +1,136 @@
+use std::net::{ IpAddr, SocketAddr };
+use std::sync::Arc;
+use axum::http::{ Request, HeaderMap, Uri };
+use axum::http::uri::{ Scheme, Authority };
+use axum::body::Body;
+use axum::extract::{ State, ConnectInfo };
+use axum::middleware::Next;
+use axum::response::Response;
+
+
+/// Where the editor is reachable from, when it is served behind a reverse proxy. See [`crate::EditorPlugin::with_proxy`].
+#[derive(Clone, Debug, Default)]
+pub struct ProxyConfig {
+    base_path       : String,
+    public_url      : Option<String>,
+    trusted_proxies : Vec<IpAddr>
+}
+
+impl ProxyConfig {
+
+    /// Serves every route under a path prefix, like `/mc`.
+    pub fn with_base_path<S : AsRef<str>>(mut self, base_path : S) -> Self {
+        self.base_path = normalise_base_path(base_path.as_ref());
+        self
+    }
+
+    /// Sets the URL that players reach the server at, like `https://example.net/mc`. Its path becomes the base path.
+    pub fn with_public_url<S : AsRef<str>>(mut self, public_url : S) -> Self {
+        let public_url = public_url.as_ref().trim_end_matches('/');
+        if let Ok(uri) = public_url.parse::<Uri>() {
+            self.base_path = normalise_base_path(uri.path());
+        }
+        self.public_url = Some(public_url.to_string());
+        self
+    }
+
+    /// Trusts `Forwarded` and `X-Forwarded-*` headers on requests from an address, which should be the reverse proxy.
+    /// Headers from any other address are ignored, as clients could otherwise pick their own address.
+    pub fn with_trusted_proxy(mut self, address : IpAddr) -> Self {
+        self.trusted_proxies.push(address);
+        self
+    }
+
+    /// The path prefix of every route, without a trailing slash. Empty if there is none.
+    pub fn base_path(&self) -> &str { &self.base_path }
+
+    pub fn public_url(&self) -> Option<&str> { self.public_url.as_deref() }
+
+    /// The link that a player opens the editor with, if a public URL was set.
+    pub fn editor_url(&self, session_code : &str) -> Option<String> {
+        Some(format!("{}/editor#DO-NOT-SHARE_{}", self.public_url.as_ref()?, session_code))
+    }
+
+}
+
+fn normalise_base_path(base_path : &str) -> String {
+    let base_path = base_path.trim_matches('/');
+    if (base_path.is_empty()) { String::new() } else { format!("/{}", base_path) }
+}
+
+
+/// Replaces the client address and URI scheme and host of requests from trusted proxies with the forwarded ones.
+pub(super) async fn apply_forwarded(State(proxy) : State<Arc<ProxyConfig>>, mut request : Request<Body>, next : Next) -> Response {
+    let peer = request.extensions().get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(address)| *address);
+    if let Some(peer) = peer {
+        if (proxy.trusted_proxies.contains(&peer.ip())) {
+            let forwarded = Forwarded::from_headers(request.headers());
+            if let Some(client) = forwarded.client {
+                request.extensions_mut().insert(ConnectInfo(SocketAddr::new(client, 0)));
+            }
+            if let (Some(scheme), Some(host)) = (forwarded.scheme, forwarded.host) {
+                let mut parts = request.uri().clone().into_parts();
+                if let (Ok(scheme), Ok(host)) = (scheme.parse::<Scheme>(), host.parse::<Authority>()) {
+                    parts.scheme    = Some(scheme);
+                    parts.authority = Some(host);
+                    if (parts.path_and_query.is_none()) { parts.path_and_query = Some("/".parse().unwrap()); }
+                    if let Ok(uri) = Uri::from_parts(parts) {
+                        *request.uri_mut() = uri;
+                    }
+                }
+            }
+        }
+    }
+    next.run(request).await
+}
+
+
+#[derive(Default)]
+struct Forwarded {
+    client : Option<IpAddr>,
+    scheme : Option<String>,
+    host   : Option<String>
+}
+
+impl Forwarded {
+
+    /// Reads the standard `Forwarded` header, falling back to `X-Forwarded-*`.
+    /// Only the entry closest to the proxy is used, as earlier ones were added by the client.
+    fn from_headers(headers : &HeaderMap) -> Self {
+        let mut out = Self::default();
+        let last_header = |name : &str| headers.get_all(name).iter().filter_map(|value| value.to_str().ok()).last().map(|value| value.to_string());
+
+        if let Some(forwarded) = last_header("forwarded") {
+            let entry = forwarded.rsplit(',').next().unwrap_or_default();
+            for pair in entry.split(';') {
+                let Some((key, value)) = pair.trim().split_once('=') else { continue; };
+                let value = value.trim_matches('"');
+                match (key.to_ascii_lowercase().as_str()) {
+                    "for"   => { out.client = parse_node(value); },
+                    "proto" => { out.scheme = Some(value.to_ascii_lowercase()); },
+                    "host"  => { out.host   = Some(value.to_string()); },
+                    _       => { }
+                }
+            }
+        }
+
+        if (out.client.is_none()) {
+            out.client = last_header("x-forwarded-for").and_then(|value| parse_node(value.rsplit(',').next().unwrap_or_default().trim()));
+        }
+        if (out.scheme.is_none()) {
+            out.scheme = last_header("x-forwarded-proto").map(|value| value.rsplit(',').next().unwrap_or_default().trim().to_ascii_lowercase());
+        }
+        if (out.host.is_none()) {
+            out.host = last_header("x-forwarded-host").map(|value| value.rsplit(',').next().unwrap_or_default().trim().to_string());
+        }
+        out
+    }
+
+}
+
+/// Parses an address that may have a port, and IPv6 brackets.
+fn parse_node(node : &str) -> Option<IpAddr> {
+    if let Ok(address) = node.parse::<IpAddr>() { return Some(address); }
+    if let Ok(address) = node.parse::<SocketAddr>() { return Some(address.ip()); }
+    node.strip_prefix('[').and_then(|node| node.split_once(']')).and_then(|(address, _)| address.parse().ok())
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+use axum::http::{ Request, HeaderMap, HeaderValue, Uri };
+use axum::http::header::{ HOST, ORIGIN, CONTENT_TYPE, CONTENT_SECURITY_POLICY, X_FRAME_OPTIONS, X_CONTENT_TYPE_OPTIONS, REFERRER_POLICY };
+use axum::body::Body;
+use axum::extract::State;
+use axum::middleware::Next;
+use axum::response::Response;
+use openssl::sha::sha256;
+use openssl::base64;
+
+
+/// The header that the editor sets on HTTP requests that change something.
+/// Pages on other sites can only send it after a CORS preflight, which the editor never allows.
+pub(super) const EDITOR_REQUEST_HEADER : &str = "x-lighthousemc-editor";
+
+
+/// Which other sites may use the editor. See [`crate::EditorPlugin::with_security`].
+/// By default, only pages on the same origin as the editor may connect to it, and nothing may frame it.
+#[derive(Clone, Debug, Default)]
+pub struct SecurityConfig {
+    allowed_origins : Vec<String>,
+    frame_ancestors : Vec<String>
+}
+
+impl SecurityConfig {
+
+    /// Allows pages on an origin, like `https://example.net`, to open editor websockets.
+    pub fn with_allowed_origin<S : AsRef<str>>(mut self, origin : S) -> Self {
+        self.allowed_origins.push(normalise_origin(origin.as_ref()));
+        self
+    }
+
+    /// Allows pages on an origin, like `https://example.net`, to show the editor in a frame.
+    pub fn with_frame_ancestor<S : AsRef<str>>(mut self, origin : S) -> Self {
+        self.frame_ancestors.push(normalise_origin(origin.as_ref()));
+        self
+    }
+
+    pub fn allowed_origins(&self) -> &[String] { &self.allowed_origins }
+
+    pub fn frame_ancestors(&self) -> &[String] { &self.frame_ancestors }
+
+    /// Whether a websocket upgrade request comes from an allowed page.
+    /// Requests without an `Origin` header are let through, as browsers send one with every websocket upgrade.
+    /// That does not hold for other requests, which must also pass [`SecurityConfig::editor_request_allowed`].
+    pub(super) fn origin_allowed(&self, headers : &HeaderMap, uri : &Uri) -> bool {
+        let Some(origin) = headers.get(ORIGIN) else { return true; };
+        let Ok(origin) = origin.to_str() else { return false; };
+        let origin = normalise_origin(origin);
+        if (self.allowed_origins.iter().any(|allowed| *allowed == origin)) { return true; }
+        // Same origin. The host was replaced with the forwarded one if the request came through a trusted proxy.
+        let host = uri.authority().map(|authority| authority.as_str()).or_else(|| headers.get(HOST).and_then(|host| host.to_str().ok()));
+        let Some(host) = host else { return false; };
+        origin.split_once("://").is_some_and(|(_, origin_host)| origin_host.eq_ignore_ascii_case(host))
+    }
+
+    /// Whether an HTTP request that changes something was sent by the editor itself, from an allowed page.
+    pub(super) fn editor_request_allowed(&self, headers : &HeaderMap, uri : &Uri) -> bool {
+        headers.contains_key(EDITOR_REQUEST_HEADER) && self.origin_allowed(headers, uri)
+    }
+
+}
+
+fn normalise_origin(origin : &str) -> String {
+    origin.trim().trim_end_matches('/').to_ascii_lowercase()
+}
+
+
+/// The headers added to every HTML response.
+pub(super) struct SecurityHeaders {
+    csp             : HeaderValue,
+    frame_framable  : bool
+}
+
+impl SecurityHeaders {
+
+    /// `inline_html` is every page with inline scripts, which are allowed by hash.
+    pub(super) fn new(config : &SecurityConfig, inline_html : &[&str]) -> Self {
+        let script_hashes = inline_html.iter().flat_map(|html| inline_script_hashes(html)).map(|hash| format!(" 'sha256-{}'", hash)).collect::<String>();
+        let frame_ancestors = if (config.frame_ancestors.is_empty()) { "'none'".to_string() } else { config.frame_ancestors.join(" ") };
+        // Monaco is loaded by its AMD loader from the same origin, sets styles inline, and starts its workers from blobs.
+        // The landing and error pages show images from other sites.
+        let csp = format!(
+            "default-src 'self'; script-src 'self' 'wasm-unsafe-eval'{}; style-src 'self' 'unsafe-inline'; img-src 'self' data: blob: https:; font-src 'self' data:; worker-src 'self' blob:; connect-src 'self'; object-src 'none'; base-uri 'none'; form-action 'none'; frame-ancestors {}",
+            script_hashes, frame_ancestors
+        );
+        Self {
+            csp            : HeaderValue::from_str(&csp).unwrap_or(HeaderValue::from_static("default-src 'self'")),
+            frame_framable : ! config.frame_ancestors.is_empty()
+        }
+    }
+
+}
+
+/// Hashes the contents of each `<script>` element without a `src`.
+fn inline_script_hashes(html : &str) -> Vec<String> {
+    let mut hashes = Vec::new();
+    let mut rest   = html;
+    while let Some(start) = rest.find("<script") {
+        rest = &rest[start..];
+        let Some(tag_end) = rest.find('>') else { break; };
+        let tag = &rest[..tag_end];
+        let Some(end) = rest.find("</script>") else { break; };
+        if (! tag.contains("src=")) {
+            hashes.push(base64::encode_block(&sha256(rest[(tag_end + 1)..end].as_bytes())));
+        }
+        rest = &rest[(end + "</script>".len())..];
+    }
+    hashes
+}
+
+
+pub(super) async fn apply_headers(State(headers) : State<Arc<SecurityHeaders>>, request : Request<Body>, next : Next) -> Response {
+    let mut response = next.run(request).await;
+    let is_html = response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok()).is_some_and(|value| value.starts_with("text/html"));
+    let response_headers = response.headers_mut();
+    response_headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    if (is_html) {
+        response_headers.insert(CONTENT_SECURITY_POLICY, headers.csp.clone());
+        if (! headers.frame_framable) {
+            response_headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+        }
+        response_headers.insert(REFERRER_POLICY, HeaderValue::from_static("no-referrer"));
+        response_headers.insert("cross-origin-opener-policy", HeaderValue::from_static("same-origin"));
+        response_headers.insert("permissions-policy", HeaderValue::from_static("camera=(), microphone=(), geolocation=()"));
+    }
+    response
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs : &[(&'static str, &'static str)]) -> HeaderMap {
+        pairs.iter().map(|(name, value)| (name.parse().unwrap(), HeaderValue::from_static(value))).collect()
+    }
+
+    #[test]
+    fn same_and_allowed_origins() {
+        let config = SecurityConfig::default().with_allowed_origin("https://Example.net/");
+        let uri    = Uri::from_static("/editor/ws");
+        assert!(config.origin_allowed(&headers(&[("host", "editor.test"), ("origin", "https://editor.test")]), &uri));
+        assert!(config.origin_allowed(&headers(&[("host", "editor.test"), ("origin", "https://example.net")]), &uri));
+        assert!(! config.origin_allowed(&headers(&[("host", "editor.test"), ("origin", "https://evil.test")]), &uri));
+        assert!(config.origin_allowed(&headers(&[("host", "editor.test")]), &uri));
+    }
+
+    #[test]
+    fn changes_need_the_editor_header() {
+        let config = SecurityConfig::default();
+        let uri    = Uri::from_static("/editor/file/1");
+        assert!(config.editor_request_allowed(&headers(&[("host", "editor.test"), ("origin", "https://editor.test"), (EDITOR_REQUEST_HEADER, "1")]), &uri));
+        assert!(config.editor_request_allowed(&headers(&[("host", "editor.test"), (EDITOR_REQUEST_HEADER, "1")]), &uri));
+        assert!(! config.editor_request_allowed(&headers(&[("host", "editor.test")]), &uri));
+        assert!(! config.editor_request_allowed(&headers(&[("host", "editor.test"), ("origin", "https://evil.test"), (EDITOR_REQUEST_HEADER, "1")]), &uri));
+    }
+
+    #[test]
+    fn csp_only_allows_own_fonts() {
+        let headers = SecurityHeaders::new(&SecurityConfig::default(), &["<script>let a = 1;</script><script src=\"x.js\"></script>"]);
+        let csp     = headers.csp.to_str().unwrap();
+        assert!(csp.contains("font-src 'self' data:;"));
+        assert!(! csp.contains("fonts.g"));
+        assert_eq!(csp.matches("'sha256-").count(), 1);
+    }
+
+}
@@ -0,0 +1,352 @@
+use voxidian_logger::{ info, warn, error };
+use axecs::prelude::*;
+use std::convert::Infallible;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{ Arc, RwLock };
+use std::time::{ Duration, SystemTime };
+use tokio::fs;
+use tokio::net::{ TcpListener, TcpStream };
+use tokio::time::{ sleep, timeout };
+use tokio_openssl::SslStream;
+use openssl::ssl::{ Ssl, SslAcceptor, SslMethod };
+use openssl::x509::X509;
+use openssl::pkey::PKey;
+use axum::Router;
+use axum::http::{ Request, StatusCode, Uri };
+use axum::http::header::{ HOST, LOCATION };
+use axum::response::{ IntoResponse, Response };
+use axum::extract::ConnectInfo;
+use hyper::body::Incoming;
+use hyper_util::rt::{ TokioIo, TokioExecutor };
+use hyper_util::server::conn::auto::Builder as ConnectionBuilder;
+use tower::Service;
+
+
+/// Where the certificate chain and private key come from, both PEM encoded.
+#[derive(Clone, Debug)]
+pub enum TlsSource {
+    /// Read from files, which are reloaded when they change.
+    Files { cert_path : PathBuf, key_path : PathBuf },
+    Pem { cert : Vec<u8>, key : Vec<u8> }
+}
+
+/// Serves the editor over HTTPS instead of HTTP. See [`crate::EditorPlugin::with_tls`].
+#[derive(Clone, Debug)]
+pub struct TlsConfig {
+    source          : TlsSource,
+    reload_interval : Duration,
+    redirect_from   : Option<SocketAddr>
+}
+
+impl TlsConfig {
+
+    pub fn from_files<C : Into<PathBuf>, K : Into<PathBuf>>(cert_path : C, key_path : K) -> Self { Self {
+        source          : TlsSource::Files { cert_path : cert_path.into(), key_path : key_path.into() },
+        reload_interval : Duration::from_secs(30),
+        redirect_from   : None
+    } }
+
+    pub fn from_pem<C : Into<Vec<u8>>, K : Into<Vec<u8>>>(cert : C, key : K) -> Self { Self {
+        source          : TlsSource::Pem { cert : cert.into(), key : key.into() },
+        reload_interval : Duration::from_secs(30),
+        redirect_from   : None
+    } }
+
+    /// Sets how often certificate files are checked for changes. Defaults to 30 seconds.
+    pub fn with_reload_interval(mut self, reload_interval : Duration) -> Self {
+        self.reload_interval = reload_interval;
+        self
+    }
+
+    /// Also listens for plain HTTP on an address, redirecting every request to HTTPS.
+    pub fn with_http_redirect(mut self, redirect_from : SocketAddr) -> Self {
+        self.redirect_from = Some(redirect_from);
+        self
+    }
+
+    pub fn source(&self) -> &TlsSource { &self.source }
+
+}
+
+
+/// Builds an acceptor from a certificate chain and private key.
+pub fn acceptor_from_pem(cert : &[u8], key : &[u8]) -> io::Result<SslAcceptor> {
+    let mut chain = X509::stack_from_pem(cert).map_err(io::Error::other)?.into_iter();
+    let Some(leaf) = chain.next() else { return Err(io::Error::new(io::ErrorKind::InvalidData, "certificate file contains no certificates")); };
+    let key = PKey::private_key_from_pem(key).map_err(io::Error::other)?;
+    let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).map_err(io::Error::other)?;
+    builder.set_certificate(&leaf).map_err(io::Error::other)?;
+    for cert in chain {
+        builder.add_extra_chain_cert(cert).map_err(io::Error::other)?;
+    }
+    builder.set_private_key(&key).map_err(io::Error::other)?;
+    builder.check_private_key().map_err(io::Error::other)?;
+    Ok(builder.build())
+}
+
+
+/// The acceptor currently in use, replaced when the certificate files change.
+pub(super) struct TlsState {
+    config   : TlsConfig,
+    acceptor : RwLock<SslAcceptor>,
+    modified : RwLock<Option<(SystemTime, SystemTime)>>
+}
+
+impl TlsState {
+
+    pub(super) async fn load(config : TlsConfig) -> io::Result<Self> {
+        let (acceptor, modified) = match (&config.source) {
+            TlsSource::Files { cert_path, key_path } => {
+                let modified = files_modified(cert_path, key_path).await;
+                (acceptor_from_pem(&fs::read(cert_path).await?, &fs::read(key_path).await?)?, modified)
+            },
+            TlsSource::Pem { cert, key } => (acceptor_from_pem(cert, key)?, None)
+        };
+        Ok(Self { config, acceptor : RwLock::new(acceptor), modified : RwLock::new(modified) })
+    }
+
+    pub(super) fn redirect_from(&self) -> Option<SocketAddr> { self.config.redirect_from }
+
+    /// Reloads the certificate files whenever they change. A broken certificate is logged and the previous one kept.
+    pub(super) async fn watch(self : Arc<Self>, cmds : Commands) {
+        let TlsSource::Files { .. } = &self.config.source else { return; };
+        while (! cmds.is_exiting()) {
+            sleep(self.config.reload_interval).await;
+            self.reload_if_changed().await;
+        }
+    }
+
+    /// Reloads the certificate files if they changed since they were last read. Returns whether a new certificate is in use.
+    async fn reload_if_changed(&self) -> bool {
+        let TlsSource::Files { cert_path, key_path } = &self.config.source else { return false; };
+        let modified = files_modified(cert_path, key_path).await;
+        if (modified.is_none() || modified == *self.modified.read().unwrap()) { return false; }
+        let loaded = match ((fs::read(cert_path).await, fs::read(key_path).await)) {
+            (Ok(cert), Ok(key)) => acceptor_from_pem(&cert, &key),
+            (Err(err), _) | (_, Err(err)) => Err(err)
+        };
+        *self.modified.write().unwrap() = modified;
+        match (loaded) {
+            Ok(acceptor) => {
+                *self.acceptor.write().unwrap() = acceptor;
+                info!("Reloaded editor TLS certificate.");
+                true
+            },
+            Err(err) => {
+                error!("Failed to reload editor TLS certificate, keeping the previous one: {}", err);
+                false
+            }
+        }
+    }
+
+    fn new_ssl(&self) -> Result<Ssl, openssl::error::ErrorStack> {
+        Ssl::new(self.acceptor.read().unwrap().context())
+    }
+
+}
+
+async fn files_modified(cert_path : &PathBuf, key_path : &PathBuf) -> Option<(SystemTime, SystemTime)> {
+    let cert = fs::metadata(cert_path).await.ok()?.modified().ok()?;
+    let key  = fs::metadata(key_path).await.ok()?.modified().ok()?;
+    Some((cert, key))
+}
+
+
+/// Accepts connections on a listener, serving the router over TLS.
+pub(super) async fn serve(listener : TcpListener, app : Router, tls : Arc<TlsState>) -> io::Result<()> {
+    loop {
+        let (stream, address) = accept(&listener).await;
+        let app = app.clone();
+        let tls = Arc::clone(&tls);
+        tokio::spawn(async move {
+            let Ok(ssl) = tls.new_ssl() else { return; };
+            let Ok(mut stream) = SslStream::new(ssl, stream) else { return; };
+            match (timeout(Duration::from_secs(10), Pin::new(&mut stream).accept()).await) {
+                Ok(Ok(())) => { },
+                _ => { return; }
+            }
+            let service = hyper::service::service_fn(move |mut request : Request<Incoming>| {
+                request.extensions_mut().insert(ConnectInfo(address));
+                app.clone().call(request)
+            });
+            let _ = ConnectionBuilder::new(TokioExecutor::new()).serve_connection_with_upgrades(TokioIo::new(stream), service).await;
+        });
+    }
+}
+
+async fn accept(listener : &TcpListener) -> (TcpStream, SocketAddr) {
+    loop {
+        match (listener.accept().await) {
+            Ok(accepted) => { return accepted; },
+            Err(err) => {
+                // Usually too many open files. Wait instead of spinning.
+                warn!("Failed to accept editor connection: {}", err);
+                sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+
+/// Redirects every plain HTTP request to the same path over HTTPS,
+/// on the port of the HTTPS listener for the address that the request came in on.
+pub(super) async fn serve_redirect(listener : TcpListener, https_addrs : Vec<SocketAddr>) -> io::Result<()> {
+    loop {
+        let (stream, _) = accept(&listener).await;
+        let Some(https_port) = stream.local_addr().ok().and_then(|local| https_port_for(&https_addrs, local)) else { continue; };
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request : Request<Incoming>| async move {
+                Ok::<_, Infallible>(redirect_to_https(&request, https_port))
+            });
+            let _ = ConnectionBuilder::new(TokioExecutor::new()).serve_connection(TokioIo::new(stream), service).await;
+        });
+    }
+}
+
+/// The port of the HTTPS listener that a plain HTTP connection to a local address is sent to.
+/// Listeners on the same IP come first, then ones on every address of the same kind.
+fn https_port_for(https_addrs : &[SocketAddr], local : SocketAddr) -> Option<u16> {
+    let ip = local.ip().to_canonical();
+    https_addrs.iter().find(|addr| addr.ip().to_canonical() == ip)
+        .or_else(|| https_addrs.iter().find(|addr| addr.ip().is_unspecified() && addr.is_ipv4() == ip.is_ipv4()))
+        .or_else(|| https_addrs.iter().find(|addr| addr.ip().is_unspecified()))
+        .or_else(|| https_addrs.first())
+        .map(|addr| addr.port())
+}
+
+fn redirect_to_https<B>(request : &Request<B>, https_port : u16) -> Response {
+    let Some(host) = request.headers().get(HOST).and_then(|host| host.to_str().ok()) else { return StatusCode::BAD_REQUEST.into_response(); };
+    // Drop the port, keeping IPv6 addresses intact.
+    let host = match (host.rsplit_once(':')) {
+        Some((name, port)) if (! port.contains(']')) => name,
+        _ => host
+    };
+    let path = request.uri().path_and_query().map_or("/", |path| path.as_str());
+    let location = if (https_port == 443) { format!("https://{}{}", host, path) } else { format!("https://{}:{}{}", host, https_port, path) };
+    let Ok(location) = location.parse::<Uri>() else { return StatusCode::BAD_REQUEST.into_response(); };
+    (StatusCode::PERMANENT_REDIRECT, [(LOCATION, location.to_string())]).into_response()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use openssl::asn1::Asn1Time;
+    use openssl::ec::{ EcGroup, EcKey };
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::x509::X509NameBuilder;
+    use std::net::{ Ipv4Addr, Ipv6Addr };
+    use uuid::Uuid;
+
+    /// A certificate signed by its own key, both PEM encoded.
+    fn self_signed(name : &str) -> (Vec<u8>, Vec<u8>) {
+        let key = PKey::from_ec_key(EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap()).unwrap();
+        let mut subject = X509NameBuilder::new().unwrap();
+        subject.append_entry_by_text("CN", name).unwrap();
+        let subject = subject.build();
+        let mut cert = X509::builder().unwrap();
+        cert.set_version(2).unwrap();
+        cert.set_subject_name(&subject).unwrap();
+        cert.set_issuer_name(&subject).unwrap();
+        cert.set_pubkey(&key).unwrap();
+        cert.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        cert.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        cert.sign(&key, MessageDigest::sha256()).unwrap();
+        (cert.build().to_pem().unwrap(), key.private_key_to_pem_pkcs8().unwrap())
+    }
+
+    fn certificate_der(tls : &TlsState) -> Vec<u8> {
+        tls.acceptor.read().unwrap().context().certificate().unwrap().to_der().unwrap()
+    }
+
+    fn pem_to_der(cert : &[u8]) -> Vec<u8> {
+        X509::from_pem(cert).unwrap().to_der().unwrap()
+    }
+
+    /// Writes a file with a modification time after any earlier write, even on filesystems with coarse timestamps.
+    fn write_later(path : &std::path::Path, data : &[u8], secs : u64) {
+        std::fs::write(path, data).unwrap();
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(SystemTime::now() + Duration::from_secs(secs)).unwrap();
+    }
+
+    #[test]
+    fn acceptor_from_matching_pem() {
+        let (cert, key) = self_signed("localhost");
+        assert!(acceptor_from_pem(&cert, &key).is_ok());
+    }
+
+    #[test]
+    fn acceptor_rejects_bad_pem() {
+        let (cert, _)     = self_signed("localhost");
+        let (_, other_key) = self_signed("localhost");
+        assert!(acceptor_from_pem(&cert, &other_key).is_err());
+        assert!(acceptor_from_pem(b"", &other_key).is_err());
+        assert!(acceptor_from_pem(&cert, b"not a key").is_err());
+    }
+
+    #[tokio::test]
+    async fn certificate_files_are_reloaded() {
+        let dir = std::env::temp_dir().join(format!("lighthousemc-editor-tls-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem"));
+        let (cert, key) = self_signed("first");
+        write_later(&cert_path, &cert, 0);
+        write_later(&key_path, &key, 0);
+
+        let tls = TlsState::load(TlsConfig::from_files(&cert_path, &key_path)).await.unwrap();
+        assert_eq!(certificate_der(&tls), pem_to_der(&cert));
+        assert!(! tls.reload_if_changed().await);
+
+        let (new_cert, new_key) = self_signed("second");
+        write_later(&cert_path, &new_cert, 10);
+        write_later(&key_path, &new_key, 10);
+        assert!(tls.reload_if_changed().await);
+        assert_eq!(certificate_der(&tls), pem_to_der(&new_cert));
+
+        // A broken certificate keeps the previous one.
+        write_later(&cert_path, b"broken", 20);
+        assert!(! tls.reload_if_changed().await);
+        assert_eq!(certificate_der(&tls), pem_to_der(&new_cert));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    fn redirect(host : Option<&str>, path : &str, https_port : u16) -> Option<String> {
+        let mut request = Request::builder().uri(path);
+        if let Some(host) = host { request = request.header(HOST, host); }
+        let response = redirect_to_https(&request.body(()).unwrap(), https_port);
+        if (response.status() != StatusCode::PERMANENT_REDIRECT) { return None; }
+        Some(response.headers()[LOCATION].to_str().unwrap().to_string())
+    }
+
+    #[test]
+    fn redirect_keeps_host_and_path() {
+        assert_eq!(redirect(Some("example.com:8080"), "/editor/?a=b", 8443).as_deref(), Some("https://example.com:8443/editor/?a=b"));
+        assert_eq!(redirect(Some("example.com"), "/", 443).as_deref(), Some("https://example.com/"));
+        assert_eq!(redirect(Some("[::1]:80"), "/", 8443).as_deref(), Some("https://[::1]:8443/"));
+        assert_eq!(redirect(Some("[::1]"), "/", 443).as_deref(), Some("https://[::1]/"));
+        assert_eq!(redirect(None, "/", 443), None);
+        assert_eq!(redirect(Some("bad host"), "/", 443), None);
+    }
+
+    #[test]
+    fn redirect_uses_listener_of_request_address() {
+        let https_addrs = [
+            SocketAddr::new(Ipv4Addr::new(10, 0, 0, 5).into(), 443),
+            SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 8443),
+            SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 9443)
+        ];
+        let port_for = |ip : std::net::IpAddr| https_port_for(&https_addrs, SocketAddr::new(ip, 80));
+        assert_eq!(port_for(Ipv4Addr::new(10, 0, 0, 5).into()), Some(443));
+        assert_eq!(port_for(Ipv4Addr::LOCALHOST.into()), Some(8443));
+        assert_eq!(port_for(Ipv4Addr::LOCALHOST.to_ipv6_mapped().into()), Some(8443));
+        assert_eq!(port_for(Ipv6Addr::LOCALHOST.into()), Some(9443));
+        assert_eq!(https_port_for(&https_addrs[..2], SocketAddr::new(Ipv4Addr::new(192, 0, 2, 1).into(), 80)), Some(443));
+        assert_eq!(https_port_for(&[], SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 80)), None);
+    }
+
+}
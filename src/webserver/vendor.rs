@@ -0,0 +1,64 @@
+use voxidian_logger::warn;
+use std::path::{ Path, PathBuf, Component };
+use std::sync::Arc;
+use tokio::fs;
+use axum::http::{ StatusCode, HeaderValue };
+use axum::http::header::{ CONTENT_TYPE, CACHE_CONTROL };
+use axum::response::{ IntoResponse, Response };
+
+
+/// The third-party asset versions that the editor and landing pages are written against.
+/// Must match the versions pinned in `tools/fetch_editor_assets.sh`.
+const VENDOR_VERSIONS : &[(&str, &str)] = &[
+    ("monaco-editor",    "0.52.2"),
+    ("devicon",          "2.16.0"),
+    ("fira-code",        "5.2.6"),
+    ("noto-sans",        "5.2.6"),
+    ("roboto-condensed", "5.2.6"),
+    ("russo-one",        "5.2.6")
+];
+
+
+/// Warns if the asset directory is missing or was fetched for different versions.
+pub(super) async fn check_asset_dir(asset_dir : &Path) {
+    let Ok(versions) = fs::read_to_string(asset_dir.join("versions.txt")).await else {
+        warn!("Editor asset directory {:?} is missing or incomplete. Run `tools/fetch_editor_assets.sh {}` to create it.", asset_dir, asset_dir.display());
+        return;
+    };
+    for (name, expected) in VENDOR_VERSIONS {
+        let found = versions.lines().find_map(|line| line.split_once(' ').filter(|(line_name, _)| line_name == name).map(|(_, version)| version.trim()));
+        if (found != Some(*expected)) {
+            warn!("Editor asset {} is version {}, but {} is expected. Run `tools/fetch_editor_assets.sh {}` to update it.", name, found.unwrap_or("(missing)"), expected, asset_dir.display());
+        }
+    }
+}
+
+
+pub(super) async fn route_vendor_asset(asset_dir : Arc<PathBuf>, path : String) -> Response {
+    // Only plain relative paths, so that nothing outside of the asset directory can be read.
+    let relative = Path::new(&path);
+    if (path.contains('\\') || ! relative.components().all(|component| matches!(component, Component::Normal(_)))) {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Ok(data) = fs::read(asset_dir.join(relative)).await else { return StatusCode::NOT_FOUND.into_response(); };
+    let mut response = data.into_response();
+    response.headers_mut().insert(CONTENT_TYPE, HeaderValue::from_static(mime_of(relative)));
+    // Assets are pinned, so they only change when the server is updated.
+    response.headers_mut().insert(CACHE_CONTROL, HeaderValue::from_static("public, max-age=86400"));
+    response
+}
+
+
+fn mime_of(path : &Path) -> &'static str {
+    match (path.extension().and_then(|ext| ext.to_str())) {
+        Some("js")    => super::mime::JS,
+        Some("css")   => super::mime::CSS,
+        Some("json")  => super::mime::JSON,
+        Some("svg")   => super::mime::SVG,
+        Some("ttf")   => super::mime::TTF,
+        Some("woff")  => super::mime::WOFF,
+        Some("woff2") => super::mime::WOFF2,
+        Some("png")   => super::mime::PNG,
+        _             => super::mime::OCTET_STREAM
+    }
+}
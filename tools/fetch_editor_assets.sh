@@ -0,0 +1,68 @@
+#!/usr/bin/env sh
+# Downloads the third-party assets used by the editor page into an asset directory,
+# which is then served by the editor server. See `EditorPlugin::with_asset_dir`.
+#
+# Usage: tools/fetch_editor_assets.sh [asset_dir]
+#
+# Versions are pinned here. Update `VENDOR_VERSIONS` in `src/webserver/vendor.rs` when changing them.
+
+set -eu
+
+MONACO_EDITOR_VERSION="0.52.2"
+DEVICON_VERSION="2.16.0"
+FIRA_CODE_VERSION="5.2.6"
+NOTO_SANS_VERSION="5.2.6"
+ROBOTO_CONDENSED_VERSION="5.2.6"
+RUSSO_ONE_VERSION="5.2.6"
+
+ASSET_DIR="${1:-editor_assets}"
+REGISTRY="https://registry.npmjs.org"
+
+TMP_DIR="$(mktemp -d)"
+trap 'rm -rf "$TMP_DIR"' EXIT
+
+# fetch <package> <version> <destination>
+fetch() {
+    name="$(basename "$1")"
+    echo "Fetching $1@$2..."
+    mkdir -p "$TMP_DIR/$name"
+    curl -fsSL "$REGISTRY/$1/-/$name-$2.tgz" | tar -xz -C "$TMP_DIR/$name"
+    rm -rf "$3"
+    mkdir -p "$(dirname "$3")"
+    mv "$TMP_DIR/$name/package" "$3"
+}
+
+# font_css <font_dir> <css files...>
+font_css() {
+    dir="$1"
+    shift
+    : > "$dir/editor.css"
+    for css in "$@"; do
+        cat "$dir/$css" >> "$dir/editor.css"
+    done
+}
+
+mkdir -p "$ASSET_DIR"
+
+fetch "monaco-editor" "$MONACO_EDITOR_VERSION" "$ASSET_DIR/monaco-editor"
+fetch "devicon"       "$DEVICON_VERSION"       "$ASSET_DIR/devicon"
+
+fetch "@fontsource/fira-code"        "$FIRA_CODE_VERSION"        "$ASSET_DIR/fonts/fira-code"
+font_css "$ASSET_DIR/fonts/fira-code" 300.css 400.css 500.css 600.css 700.css
+fetch "@fontsource/noto-sans"        "$NOTO_SANS_VERSION"        "$ASSET_DIR/fonts/noto-sans"
+font_css "$ASSET_DIR/fonts/noto-sans" 100.css 200.css 300.css 400.css 500.css 600.css 700.css 800.css 900.css 400-italic.css
+fetch "@fontsource/roboto-condensed" "$ROBOTO_CONDENSED_VERSION" "$ASSET_DIR/fonts/roboto-condensed"
+font_css "$ASSET_DIR/fonts/roboto-condensed" 300.css 400.css 500.css 700.css
+fetch "@fontsource/russo-one"        "$RUSSO_ONE_VERSION"        "$ASSET_DIR/fonts/russo-one"
+font_css "$ASSET_DIR/fonts/russo-one" 400.css
+
+cat > "$ASSET_DIR/versions.txt" <<VERSIONS
+monaco-editor $MONACO_EDITOR_VERSION
+devicon $DEVICON_VERSION
+fira-code $FIRA_CODE_VERSION
+noto-sans $NOTO_SANS_VERSION
+roboto-condensed $ROBOTO_CONDENSED_VERSION
+russo-one $RUSSO_ONE_VERSION
+VERSIONS
+
+echo "Editor assets written to $ASSET_DIR."